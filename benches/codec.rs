@@ -0,0 +1,67 @@
+#[macro_use]
+extern crate criterion;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_ubjson;
+
+use std::collections::HashMap;
+
+use criterion::{black_box, Criterion};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+#[derive(Serialize, Deserialize)]
+struct Node {
+    id: u32,
+    weight: f64,
+    label: String,
+    child: Option<Box<Node>>,
+}
+
+fn nested(depth: u32) -> Node {
+    (0..depth)
+        .fold(None, |child, id| {
+            Some(Node {
+                id,
+                weight: f64::from(id) * 0.5,
+                label: format!("node-{}", id),
+                child: child.map(Box::new),
+            })
+        })
+        .unwrap()
+}
+
+fn bench<T>(c: &mut Criterion, name: &str, value: &T)
+where
+    T: Serialize + DeserializeOwned,
+{
+    let bytes = serde_ubjson::to_vec(value).unwrap();
+    c.bench_function(&format!("{}/ser", name), |b| {
+        let mut buf = Vec::with_capacity(bytes.len());
+        b.iter(|| {
+            buf.clear();
+            serde_ubjson::to_buf(black_box(value), &mut buf).unwrap();
+        })
+    });
+    c.bench_function(&format!("{}/de", name), |b| {
+        b.iter(|| serde_ubjson::from_slice::<T>(black_box(&bytes)).unwrap())
+    });
+}
+
+fn vec_f64(c: &mut Criterion) {
+    let value: Vec<f64> = (0..100_000).map(|i| f64::from(i) * 1.25).collect();
+    bench(c, "vec_f64", &value);
+}
+
+fn nested_struct(c: &mut Criterion) {
+    bench(c, "nested_struct", &nested(100));
+}
+
+fn hash_map(c: &mut Criterion) {
+    let value: HashMap<String, i32> = (0..10_000).map(|i| (format!("key-{}", i), i)).collect();
+    bench(c, "hash_map", &value);
+}
+
+criterion_group!(benches, vec_f64, nested_struct, hash_map);
+criterion_main!(benches);
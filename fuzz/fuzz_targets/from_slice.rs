@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use serde_ubjson::Value;
+
+// Decoding arbitrary bytes may fail, but it must never panic.
+fuzz_target!(|data: &[u8]| {
+    let _ = serde_ubjson::from_slice::<Value>(data);
+});
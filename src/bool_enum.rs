@@ -0,0 +1,228 @@
+//! Helpers for serializing a fieldless enum of two variants as a boolean, for use with
+//! `#[serde(with = "serde_ubjson::bool_enum")]`.
+//!
+//! The first variant is written as `F` and the second as `T`, taking a single byte where the
+//! variant index would otherwise take two. Enums with more variants, or with variants that have
+//! fields, fail to serialize.
+//!
+//! ```ignore
+//! #[derive(Serialize, Deserialize)]
+//! enum Side {
+//!     Buy,
+//!     Sell,
+//! }
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Order {
+//!     #[serde(with = "serde_ubjson::bool_enum")]
+//!     side: Side,
+//! }
+//! ```
+
+use serde::de::value::U32Deserializer;
+use serde::de::{Deserialize, Deserializer};
+use serde::ser::{self, Impossible, Serialize, Serializer};
+
+use crate::error::Error;
+
+pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: Serialize,
+    S: Serializer,
+{
+    let flag = value.serialize(VariantFlag).map_err(ser::Error::custom)?;
+    serializer.serialize_bool(flag)
+}
+
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    let index = u32::from(bool::deserialize(deserializer)?);
+    T::deserialize(U32Deserializer::<D::Error>::new(index))
+}
+
+fn unexpected() -> Error {
+    ser::Error::custom("expected the first or second unit variant of an enum")
+}
+
+/// Captures the index of a unit variant as the boolean it is written as, rejecting anything else.
+struct VariantFlag;
+
+impl Serializer for VariantFlag {
+    type Ok = bool;
+    type Error = Error;
+
+    type SerializeSeq = Impossible<bool, Error>;
+    type SerializeTuple = Impossible<bool, Error>;
+    type SerializeTupleStruct = Impossible<bool, Error>;
+    type SerializeTupleVariant = Impossible<bool, Error>;
+    type SerializeMap = Impossible<bool, Error>;
+    type SerializeStruct = Impossible<bool, Error>;
+    type SerializeStructVariant = Impossible<bool, Error>;
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<bool, Error> {
+        match variant_index {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(unexpected()),
+        }
+    }
+
+    fn serialize_bool(self, _v: bool) -> Result<bool, Error> {
+        Err(unexpected())
+    }
+
+    fn serialize_i8(self, _v: i8) -> Result<bool, Error> {
+        Err(unexpected())
+    }
+
+    fn serialize_i16(self, _v: i16) -> Result<bool, Error> {
+        Err(unexpected())
+    }
+
+    fn serialize_i32(self, _v: i32) -> Result<bool, Error> {
+        Err(unexpected())
+    }
+
+    fn serialize_i64(self, _v: i64) -> Result<bool, Error> {
+        Err(unexpected())
+    }
+
+    fn serialize_i128(self, _v: i128) -> Result<bool, Error> {
+        Err(unexpected())
+    }
+
+    fn serialize_u8(self, _v: u8) -> Result<bool, Error> {
+        Err(unexpected())
+    }
+
+    fn serialize_u16(self, _v: u16) -> Result<bool, Error> {
+        Err(unexpected())
+    }
+
+    fn serialize_u32(self, _v: u32) -> Result<bool, Error> {
+        Err(unexpected())
+    }
+
+    fn serialize_u64(self, _v: u64) -> Result<bool, Error> {
+        Err(unexpected())
+    }
+
+    fn serialize_u128(self, _v: u128) -> Result<bool, Error> {
+        Err(unexpected())
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<bool, Error> {
+        Err(unexpected())
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<bool, Error> {
+        Err(unexpected())
+    }
+
+    fn serialize_char(self, _v: char) -> Result<bool, Error> {
+        Err(unexpected())
+    }
+
+    fn serialize_str(self, _v: &str) -> Result<bool, Error> {
+        Err(unexpected())
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<bool, Error> {
+        Err(unexpected())
+    }
+
+    fn serialize_none(self) -> Result<bool, Error> {
+        Err(unexpected())
+    }
+
+    fn serialize_some<T>(self, _value: &T) -> Result<bool, Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(unexpected())
+    }
+
+    fn serialize_unit(self) -> Result<bool, Error> {
+        Err(unexpected())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<bool, Error> {
+        Err(unexpected())
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, _value: &T) -> Result<bool, Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(unexpected())
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<bool, Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(unexpected())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Err(unexpected())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Error> {
+        Err(unexpected())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        Err(unexpected())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(unexpected())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Err(unexpected())
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Error> {
+        Err(unexpected())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Err(unexpected())
+    }
+}
@@ -0,0 +1,72 @@
+//! Helpers for serializing a fixed-size byte array, for use with
+//! `#[serde(with = "serde_ubjson::byte_array")]`.
+//!
+//! Serde hands `[u8; N]` to the serializer as a tuple, so by default every byte gets its own
+//! marker. With this module the array is written as a strongly-typed `U` array instead, `[$U#`
+//! followed by the count and the bytes themselves, the same as `serde_bytes` would produce for a
+//! slice.
+//!
+//! ```ignore
+//! #[derive(Serialize, Deserialize)]
+//! struct Block {
+//!     #[serde(with = "serde_ubjson::byte_array")]
+//!     hash: [u8; 32],
+//! }
+//! ```
+
+use std::fmt;
+
+use serde::de::{self, Deserializer, SeqAccess, Visitor};
+use serde::ser::Serializer;
+
+pub fn serialize<S, const N: usize>(bytes: &[u8; N], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_bytes(bytes)
+}
+
+pub fn deserialize<'de, D, const N: usize>(deserializer: D) -> Result<[u8; N], D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_bytes(ByteArrayVisitor)
+}
+
+struct ByteArrayVisitor<const N: usize>;
+
+impl<'de, const N: usize> Visitor<'de> for ByteArrayVisitor<N> {
+    type Value = [u8; N];
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "{} bytes", N)
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<[u8; N], E>
+    where
+        E: de::Error,
+    {
+        if v.len() != N {
+            return Err(E::invalid_length(v.len(), &self));
+        }
+        let mut bytes = [0; N];
+        bytes.copy_from_slice(v);
+        Ok(bytes)
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<[u8; N], A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut bytes = [0; N];
+        for (i, b) in bytes.iter_mut().enumerate() {
+            *b = seq
+                .next_element()?
+                .ok_or_else(|| de::Error::invalid_length(i, &self))?;
+        }
+        if seq.next_element::<de::IgnoredAny>()?.is_some() {
+            return Err(de::Error::invalid_length(N + 1, &self));
+        }
+        Ok(bytes)
+    }
+}
@@ -0,0 +1,57 @@
+//! Helpers for serializing `chrono` timestamps, for use with `#[serde(with = "...")]`.
+//!
+//! ```ignore
+//! #[derive(Serialize, Deserialize)]
+//! struct Event {
+//!     #[serde(with = "serde_ubjson::chrono::rfc3339")]
+//!     at: DateTime<Utc>,
+//! }
+//! ```
+
+/// Serialize a `DateTime<Utc>` as an RFC 3339 string, such as `2020-01-02T03:04:05Z`.
+pub mod rfc3339 {
+    use ::chrono::{DateTime, SecondsFormat, Utc};
+    use serde::de::{self, Deserialize, Deserializer};
+    use serde::ser::Serializer;
+
+    pub fn serialize<S>(dt: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&dt.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        DateTime::parse_from_rfc3339(&s)
+            .map(|dt| dt.with_timezone(&Utc))
+            .map_err(de::Error::custom)
+    }
+}
+
+/// Serialize a `DateTime<Utc>` as an integer count of seconds since the Unix epoch. Anything
+/// finer than a second is truncated.
+pub mod ts_seconds {
+    use ::chrono::{DateTime, Utc};
+    use serde::de::{self, Deserialize, Deserializer};
+    use serde::ser::Serializer;
+
+    pub fn serialize<S>(dt: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_i64(dt.timestamp())
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let secs = i64::deserialize(deserializer)?;
+        DateTime::from_timestamp(secs, 0)
+            .ok_or_else(|| de::Error::custom(format_args!("timestamp {} is out of range", secs)))
+    }
+}
@@ -0,0 +1,1364 @@
+//! Deserialize UBJSON data to a Rust data structure.
+
+use std::cmp;
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::iter::{self, FusedIterator};
+use std::marker::PhantomData;
+use std::str;
+
+use byteorder::{BigEndian, ByteOrder};
+use serde::de::value::MapDeserializer;
+use serde::de::{self, Deserialize, DeserializeSeed, Unexpected, Visitor};
+
+use crate::error::{Error, Result};
+use crate::marker;
+use crate::raw::RAW_VALUE_TOKEN;
+use crate::ser::integer_marker;
+use crate::value::HIGH_PRECISION_TOKEN;
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// Deserialize an instance of type `T` from a slice of UBJSON bytes.
+///
+/// The slice must hold exactly one value, optionally followed by no-ops. Anything else after the
+/// value fails with `Error::TrailingBytes`; use `from_slice_partial` to read a value off the front
+/// of a longer input.
+pub fn from_slice<'a, T>(v: &'a [u8]) -> Result<T>
+where
+    T: Deserialize<'a>,
+{
+    let mut deserializer = Deserializer::from_slice(v);
+    let value = T::deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    Ok(value)
+}
+
+/// Deserialize an instance of type `T` from the start of a slice of UBJSON bytes, returning it
+/// along with the bytes that follow it.
+pub fn from_slice_partial<'a, T>(v: &'a [u8]) -> Result<(T, &'a [u8])>
+where
+    T: Deserialize<'a>,
+{
+    let mut deserializer = Deserializer::from_slice(v);
+    let value = T::deserialize(&mut deserializer)?;
+    Ok((value, &v[deserializer.index..]))
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// Maximum container nesting accepted by a newly created `Deserializer`.
+pub const DEFAULT_MAX_DEPTH: usize = 128;
+
+/// Largest count of a strongly-typed array of `Z`, `T` or `F` accepted by a newly created
+/// `Deserializer`.
+pub const DEFAULT_MAX_ZERO_WIDTH_COUNT: usize = 1 << 20;
+
+/// Structure for deserializing UBJSON into Rust values.
+pub struct Deserializer<'de> {
+    input: &'de [u8],
+    index: usize,
+    /// Marker implied by the enclosing strongly-typed container, if any.
+    pending: Option<u8>,
+    depth: usize,
+    max_depth: usize,
+    max_length: usize,
+    max_zero_width_count: usize,
+    deny_duplicate_keys: bool,
+    deny_unknown_fields: bool,
+    human_readable: bool,
+    faithful_options: bool,
+    require_canonical_integers: bool,
+    require_exact_f32: bool,
+    /// Where the value of the innermost `Some` began, to tell whether that value is itself an
+    /// option. Only kept with `faithful_options`.
+    some_start: Option<(usize, Option<u8>)>,
+}
+
+impl<'de> Deserializer<'de> {
+    /// Creates a new UBJSON deserializer reading from a byte slice.
+    pub fn from_slice(input: &'de [u8]) -> Self {
+        Deserializer {
+            input,
+            index: 0,
+            pending: None,
+            depth: 0,
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_length: usize::MAX,
+            max_zero_width_count: DEFAULT_MAX_ZERO_WIDTH_COUNT,
+            deny_duplicate_keys: false,
+            deny_unknown_fields: false,
+            human_readable: false,
+            faithful_options: false,
+            require_canonical_integers: false,
+            require_exact_f32: false,
+            some_start: None,
+        }
+    }
+
+    /// Sets how deeply arrays and objects may nest before decoding fails with
+    /// `Error::DepthLimitExceeded`. Defaults to `DEFAULT_MAX_DEPTH`.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Sets the largest element count or string length that will be accepted before decoding
+    /// fails with `Error::LengthLimitExceeded`. Unlimited by default.
+    pub fn max_length(mut self, length: usize) -> Self {
+        self.max_length = length;
+        self
+    }
+
+    /// Sets the largest count a strongly-typed array of `Z`, `T` or `F`, such as `[$T#<count>`,
+    /// may declare before decoding fails with `Error::LengthLimitExceeded`. Its elements take up
+    /// no input, so unlike other counts, the size of the input does not bound it. Defaults to
+    /// `DEFAULT_MAX_ZERO_WIDTH_COUNT`.
+    pub fn max_zero_width_count(mut self, count: usize) -> Self {
+        self.max_zero_width_count = count;
+        self
+    }
+
+    /// Sets whether an object repeating a key fails with `Error::DuplicateKey`, rather than
+    /// leaving it to the visitor. Off by default.
+    pub fn deny_duplicate_keys(mut self, enabled: bool) -> Self {
+        self.deny_duplicate_keys = enabled;
+        self
+    }
+
+    /// Sets whether a struct written as an object fails with `Error::UnknownField` on a key that
+    /// is not one of its fields, whatever the struct's own serde attributes say. Off by default,
+    /// which leaves unknown keys to the struct, and derived structs skip them.
+    pub fn deny_unknown_fields(mut self, enabled: bool) -> Self {
+        self.deny_unknown_fields = enabled;
+        self
+    }
+
+    /// Sets what `is_human_readable` reports to the types being deserialized. This should match
+    /// the setting the data was serialized with. Off by default.
+    pub fn human_readable(mut self, enabled: bool) -> Self {
+        self.human_readable = enabled;
+        self
+    }
+
+    /// Sets whether an option that is the value of a `Some` is expected to be wrapped in a
+    /// single-element array, as the serializer's `faithful_options` writes it. Off by default.
+    pub fn faithful_options(mut self, enabled: bool) -> Self {
+        self.faithful_options = enabled;
+        self
+    }
+
+    /// Sets whether an integer written with a wider marker than it needs, such as 5 as
+    /// `l\x00\x00\x00\x05` rather than `i\x05`, fails with `Error::NonCanonical`. This also
+    /// applies to lengths, but not to the values of a strongly-typed container, whose marker is
+    /// shared. Off by default.
+    pub fn require_canonical_integers(mut self, enabled: bool) -> Self {
+        self.require_canonical_integers = enabled;
+        self
+    }
+
+    /// Sets whether a float64 read into an `f32` must be exactly representable as one, failing
+    /// with `Error::InexactFloat` if it is not. When off, it is rounded to the nearest `f32`. NaN
+    /// and infinities always pass. Off by default.
+    pub fn require_exact_f32(mut self, enabled: bool) -> Self {
+        self.require_exact_f32 = enabled;
+        self
+    }
+
+    /// Turns the deserializer into an iterator over values of type `T` written one after another,
+    /// as in a log of messages. No-ops between values are skipped.
+    pub fn into_stream<T>(self) -> StreamDeserializer<'de, T>
+    where
+        T: Deserialize<'de>,
+    {
+        StreamDeserializer {
+            de: self,
+            failed: false,
+            output: PhantomData,
+        }
+    }
+
+    /// Checks that nothing but no-ops is left of the input, which should be called once the last
+    /// value has been deserialized. Fails with `Error::TrailingBytes` otherwise.
+    pub fn end(&mut self) -> Result<()> {
+        while self.peek_byte() == Some(marker::NOOP) {
+            self.index += 1;
+        }
+        match self.remaining() {
+            0 => Ok(()),
+            remaining => Err(Error::TrailingBytes { remaining }),
+        }
+    }
+
+    pub(crate) fn remaining(&self) -> usize {
+        self.input.len() - self.index
+    }
+
+    pub(crate) fn peek_byte(&self) -> Option<u8> {
+        self.input.get(self.index).cloned()
+    }
+
+    pub(crate) fn next_byte(&mut self) -> Result<u8> {
+        let byte = self.peek_byte().ok_or(Error::UnexpectedEof)?;
+        self.index += 1;
+        Ok(byte)
+    }
+
+    pub(crate) fn read_slice(&mut self, len: usize) -> Result<&'de [u8]> {
+        if len > self.remaining() {
+            return Err(Error::UnexpectedEof);
+        }
+        let slice = &self.input[self.index..self.index + len];
+        self.index += len;
+        Ok(slice)
+    }
+
+    /// Consumes and returns the marker of the next value, skipping no-ops.
+    fn parse_marker(&mut self) -> Result<u8> {
+        if let Some(m) = self.pending.take() {
+            return Ok(m);
+        }
+        loop {
+            let m = self.next_byte()?;
+            if m != marker::NOOP {
+                return Ok(m);
+            }
+        }
+    }
+
+    /// Returns the marker of the next value without consuming it, skipping no-ops.
+    fn peek_marker(&mut self) -> Result<u8> {
+        if let Some(m) = self.pending {
+            return Ok(m);
+        }
+        loop {
+            match self.peek_byte() {
+                Some(marker::NOOP) => self.index += 1,
+                Some(m) => return Ok(m),
+                None => return Err(Error::UnexpectedEof),
+            }
+        }
+    }
+
+    /// Reads the payload of an integer whose marker has already been consumed.
+    pub(crate) fn parse_integer(&mut self, m: u8) -> Result<i64> {
+        match m {
+            marker::I8 => Ok(i64::from(self.next_byte()? as i8)),
+            marker::U8 => Ok(i64::from(self.next_byte()?)),
+            marker::I16 => Ok(i64::from(BigEndian::read_i16(self.read_slice(2)?))),
+            marker::I32 => Ok(i64::from(BigEndian::read_i32(self.read_slice(4)?))),
+            marker::I64 => Ok(BigEndian::read_i64(self.read_slice(8)?)),
+            _ => Err(Error::InvalidMarker(m)),
+        }
+    }
+
+    /// Under `require_canonical_integers`, checks that the integer whose marker `m` has just been
+    /// consumed is not written wider than the serializer would write it. Nothing is consumed.
+    fn check_canonical(&self, m: u8) -> Result<()> {
+        if !self.require_canonical_integers {
+            return Ok(());
+        }
+        let payload = &self.input[self.index..];
+        let v = match m {
+            marker::I16 if payload.len() >= 2 => i64::from(BigEndian::read_i16(payload)),
+            marker::I32 if payload.len() >= 4 => i64::from(BigEndian::read_i32(payload)),
+            marker::I64 if payload.len() >= 8 => BigEndian::read_i64(payload),
+            // One-byte integers are always canonical, and truncated ones fail when read.
+            _ => return Ok(()),
+        };
+        if integer_marker(v) != m {
+            return Err(Error::NonCanonical);
+        }
+        Ok(())
+    }
+
+    /// Reads the payload of a `C` value, which must be ASCII.
+    pub(crate) fn parse_ascii_char(&mut self) -> Result<char> {
+        match self.next_byte()? {
+            c if c <= 127 => Ok(char::from(c)),
+            _ => Err(Error::InvalidChar),
+        }
+    }
+
+    /// Reads a length, as found in strings and container counts.
+    fn parse_length(&mut self) -> Result<usize> {
+        let m = self.next_byte()?;
+        self.check_canonical(m)?;
+        let len = self.parse_integer(m)?;
+        self.check_length(len)
+    }
+
+    /// Validates a length that has been read as an integer.
+    pub(crate) fn check_length(&self, len: i64) -> Result<usize> {
+        if len < 0 {
+            return Err(de::Error::invalid_value(
+                Unexpected::Signed(len),
+                &"a non-negative length",
+            ));
+        }
+        // On 32-bit targets a 64-bit length may not fit, and must not be truncated.
+        let len = usize::try_from(len).map_err(|_| Error::LengthOverflow)?;
+        if len > self.max_length {
+            return Err(Error::LengthLimitExceeded);
+        }
+        Ok(len)
+    }
+
+    /// Reads a length-prefixed string whose marker (if any) has already been consumed.
+    fn parse_str(&mut self) -> Result<&'de str> {
+        let len = self.parse_length()?;
+        let bytes = self.read_slice(len)?;
+        str::from_utf8(bytes).map_err(|_| Error::InvalidUtf8)
+    }
+
+    /// Reads the optional `$type` and `#count` that follow a container's opening marker.
+    fn parse_container_header(&mut self) -> Result<(Option<u8>, Option<usize>)> {
+        let ty = if self.peek_byte() == Some(marker::TYPE) {
+            self.index += 1;
+            Some(container_type(self.next_byte()?)?)
+        } else {
+            None
+        };
+        match self.peek_byte() {
+            Some(marker::LENGTH) => {
+                self.index += 1;
+                Ok((ty, Some(self.parse_length()?)))
+            }
+            Some(m) if ty.is_some() => Err(Error::InvalidMarker(m)),
+            None if ty.is_some() => Err(Error::UnexpectedEof),
+            _ => Ok((ty, None)),
+        }
+    }
+
+    /// Fails early if `count` entries of at least `size` bytes each cannot fit in the remaining
+    /// input, so an adversarial count is rejected before anything is allocated for it. Entries
+    /// of no size are held to `max_zero_width_count` instead.
+    pub(crate) fn check_count(&self, count: Option<usize>, size: usize) -> Result<()> {
+        match count {
+            Some(count) if size == 0 && count > self.max_zero_width_count => {
+                Err(Error::LengthLimitExceeded)
+            }
+            Some(count) if count.saturating_mul(size) > self.remaining() => {
+                Err(Error::UnexpectedEof)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Caps a container's declared count by what the remaining input could possibly hold.
+    fn cautious_len(&self, count: usize, size: usize) -> usize {
+        cmp::min(count, self.remaining() / cmp::max(size, 1))
+    }
+
+    pub(crate) fn enter(&mut self) -> Result<()> {
+        if self.depth >= self.max_depth {
+            return Err(Error::DepthLimitExceeded);
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    pub(crate) fn leave(&mut self) {
+        self.depth -= 1;
+    }
+
+    /// Checks that the container just visited was read to its end, consuming the closing marker
+    /// of an unsized container.
+    fn end_container(&mut self, len: Option<usize>, read: usize, end: u8) -> Result<()> {
+        match len {
+            Some(len) if read == len => Ok(()),
+            Some(len) => Err(de::Error::invalid_length(
+                len,
+                &"fewer elements in container",
+            )),
+            None => match self.peek_marker()? {
+                m if m == end => {
+                    self.index += 1;
+                    Ok(())
+                }
+                _ => Err(de::Error::custom("trailing elements in container")),
+            },
+        }
+    }
+
+    /// Reads an array whose `[` has already been consumed. If `arity` is given, a counted array
+    /// must declare exactly that many elements, or else decoding fails with
+    /// `Error::LengthMismatch`.
+    fn parse_array<V>(&mut self, arity: Option<usize>, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let (ty, len) = self.parse_container_header()?;
+        if let (Some(arity), Some(len)) = (arity, len) {
+            if arity != len {
+                return Err(Error::LengthMismatch {
+                    expected: arity,
+                    found: len,
+                });
+            }
+        }
+        self.check_count(len, min_value_size(ty))?;
+        self.enter()?;
+        let mut read = 0;
+        let value = visitor.visit_seq(SeqAccess {
+            de: self,
+            ty,
+            len,
+            read: &mut read,
+        })?;
+        self.end_container(len, read, marker::ARR_END)?;
+        self.leave();
+        Ok(value)
+    }
+
+    /// Reads an object whose `{` has already been consumed. `fields` are the names a struct
+    /// expects, which are checked against when unknown fields are denied.
+    fn parse_object<V>(
+        &mut self,
+        fields: Option<&'static [&'static str]>,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let (ty, len) = self.parse_container_header()?;
+        self.check_count(len, MIN_KEY_SIZE + min_value_size(ty))?;
+        self.enter()?;
+        let mut read = 0;
+        let seen = if self.deny_duplicate_keys {
+            Some(HashSet::new())
+        } else {
+            None
+        };
+        let fields = fields.filter(|_| self.deny_unknown_fields);
+        let value = visitor.visit_map(MapAccess {
+            de: self,
+            ty,
+            len,
+            read: &mut read,
+            seen,
+            fields,
+        })?;
+        self.end_container(len, read, marker::OBJ_END)?;
+        self.leave();
+        Ok(value)
+    }
+
+    /// Consumes the value introduced by `m` without decoding it, other than checking that strings
+    /// and keys are valid UTF-8.
+    fn skip_value(&mut self, m: u8) -> Result<()> {
+        if let Some(size) = fixed_value_size(m) {
+            self.read_slice(size)?;
+            return Ok(());
+        }
+        match m {
+            marker::STRING | marker::HI_PRECISION => {
+                self.parse_str()?;
+                Ok(())
+            }
+            marker::ARR_START => self.skip_container(false),
+            marker::OBJ_START => self.skip_container(true),
+            _ => Err(Error::InvalidMarker(m)),
+        }
+    }
+
+    /// Consumes the rest of a container whose opening marker has already been read. A counted
+    /// array of fixed-size values is skipped in one step.
+    fn skip_container(&mut self, keyed: bool) -> Result<()> {
+        let (ty, len) = self.parse_container_header()?;
+        let min_size = min_value_size(ty) + if keyed { MIN_KEY_SIZE } else { 0 };
+        self.check_count(len, min_size)?;
+        if let (false, Some(size), Some(len)) = (keyed, ty.and_then(fixed_value_size), len) {
+            // `check_count` has already made sure this fits in the input.
+            self.read_slice(size * len)?;
+            return Ok(());
+        }
+
+        self.enter()?;
+        let end = if keyed {
+            marker::OBJ_END
+        } else {
+            marker::ARR_END
+        };
+        let mut read = 0;
+        loop {
+            match len {
+                Some(len) if read == len => break,
+                Some(_) => {}
+                None => {
+                    if self.peek_marker()? == end {
+                        self.index += 1;
+                        break;
+                    }
+                    if read >= self.max_length {
+                        return Err(Error::LengthLimitExceeded);
+                    }
+                }
+            }
+            if keyed {
+                self.parse_str()?;
+            }
+            let m = match ty {
+                Some(t) => t,
+                None => {
+                    let m = self.parse_marker()?;
+                    self.check_canonical(m)?;
+                    m
+                }
+            };
+            self.skip_value(m)?;
+            read += 1;
+        }
+        self.leave();
+        Ok(())
+    }
+}
+
+/// Smallest possible encoding of an object key: a one-byte length followed by no data.
+pub(crate) const MIN_KEY_SIZE: usize = 2;
+
+/// Returns the fewest bytes a single value can occupy in a container of the given type.
+pub(crate) fn min_value_size(ty: Option<u8>) -> usize {
+    match ty {
+        Some(marker::NULL) | Some(marker::TRUE) | Some(marker::FALSE) => 0,
+        Some(marker::I16) => 2,
+        Some(marker::I32) | Some(marker::F32) => 4,
+        Some(marker::I64) | Some(marker::F64) => 8,
+        Some(marker::STRING) | Some(marker::HI_PRECISION) => 2,
+        _ => 1,
+    }
+}
+
+/// Checks that `m` can follow `$` as the type of a strongly-typed container.
+pub(crate) fn container_type(m: u8) -> Result<u8> {
+    match m {
+        marker::NOOP | marker::ARR_END | marker::OBJ_END | marker::TYPE | marker::LENGTH => {
+            Err(Error::InvalidMarker(m))
+        }
+        m => Ok(m),
+    }
+}
+
+/// Returns the size of the payload that follows `m`, if it is always the same.
+pub(crate) fn fixed_value_size(m: u8) -> Option<usize> {
+    match m {
+        marker::NULL | marker::TRUE | marker::FALSE => Some(0),
+        marker::I8 | marker::U8 | marker::CHAR => Some(1),
+        marker::I16 => Some(2),
+        marker::I32 | marker::F32 => Some(4),
+        marker::I64 | marker::F64 => Some(8),
+        _ => None,
+    }
+}
+
+/// Parses a high-precision number into the requested numeric type, failing if it is out of range
+/// or, for integers, has a fractional part. Any other value is left to `deserialize_any`.
+macro_rules! deserialize_hi_precision {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value>
+            where
+                V: Visitor<'de>,
+            {
+                if self.peek_marker()? != marker::HI_PRECISION {
+                    return self.deserialize_any(visitor);
+                }
+                self.parse_marker()?;
+                let digits = self.parse_str()?;
+                match digits.parse() {
+                    Ok(v) => visitor.$visit(v),
+                    Err(_) => Err(de::Error::invalid_value(Unexpected::Other(digits), &visitor)),
+                }
+            }
+        )*
+    };
+}
+
+/// Reads an integer of any width into the requested integer type, failing with
+/// `Error::IntegerOutOfRange` if it does not fit. High-precision numbers are parsed as with
+/// `deserialize_hi_precision!`, and anything else is left to `deserialize_any`.
+macro_rules! deserialize_integer {
+    ($($method:ident => $ty:ty, $visit:ident,)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value>
+            where
+                V: Visitor<'de>,
+            {
+                let typed = self.pending.is_some();
+                let m = self.peek_marker()?;
+                match m {
+                    marker::I8 | marker::U8 | marker::I16 | marker::I32 | marker::I64 => {
+                        self.parse_marker()?;
+                        if !typed {
+                            self.check_canonical(m)?;
+                        }
+                        let v = self.parse_integer(m)?;
+                        match <$ty>::try_from(v) {
+                            Ok(v) => visitor.$visit(v),
+                            Err(_) => Err(Error::IntegerOutOfRange {
+                                target: stringify!($ty),
+                                value: i128::from(v),
+                            }),
+                        }
+                    }
+                    marker::HI_PRECISION => {
+                        self.parse_marker()?;
+                        let digits = self.parse_str()?;
+                        match (digits.parse(), digits.parse()) {
+                            (Ok(v), _) => visitor.$visit(v),
+                            (Err(_), Ok(value)) => Err(Error::IntegerOutOfRange {
+                                target: stringify!($ty),
+                                value,
+                            }),
+                            (Err(_), Err(_)) => {
+                                Err(de::Error::invalid_value(Unexpected::Other(digits), &visitor))
+                            }
+                        }
+                    }
+                    _ => self.deserialize_any(visitor),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
+    type Error = Error;
+
+    fn is_human_readable(&self) -> bool {
+        self.human_readable
+    }
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let typed = self.pending.is_some();
+        let m = self.parse_marker()?;
+        if !typed {
+            self.check_canonical(m)?;
+        }
+        match m {
+            marker::NULL => visitor.visit_unit(),
+            marker::TRUE => visitor.visit_bool(true),
+            marker::FALSE => visitor.visit_bool(false),
+            marker::I8 => visitor.visit_i8(self.next_byte()? as i8),
+            marker::U8 => visitor.visit_u8(self.next_byte()?),
+            marker::I16 => visitor.visit_i16(BigEndian::read_i16(self.read_slice(2)?)),
+            marker::I32 => visitor.visit_i32(BigEndian::read_i32(self.read_slice(4)?)),
+            marker::I64 => visitor.visit_i64(BigEndian::read_i64(self.read_slice(8)?)),
+            marker::F32 => visitor.visit_f32(BigEndian::read_f32(self.read_slice(4)?)),
+            marker::F64 => visitor.visit_f64(BigEndian::read_f64(self.read_slice(8)?)),
+            marker::CHAR => visitor.visit_char(self.parse_ascii_char()?),
+            marker::STRING => visitor.visit_borrowed_str(self.parse_str()?),
+            // Handed over as a single-entry map under a private key, which `Value` recognizes
+            // and keeps as `Value::HighPrecision`.
+            marker::HI_PRECISION => {
+                let digits = self.parse_str()?;
+                let mut map =
+                    MapDeserializer::<_, Error>::new(iter::once((HIGH_PRECISION_TOKEN, digits)));
+                let value = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(value)
+            }
+            marker::ARR_START => self.parse_array(None, visitor),
+            marker::OBJ_START => self.parse_object(None, visitor),
+            _ => Err(Error::InvalidMarker(m)),
+        }
+    }
+
+    deserialize_integer! {
+        deserialize_i8 => i8, visit_i8,
+        deserialize_i16 => i16, visit_i16,
+        deserialize_i32 => i32, visit_i32,
+        deserialize_i64 => i64, visit_i64,
+        deserialize_i128 => i128, visit_i128,
+        deserialize_u8 => u8, visit_u8,
+        deserialize_u16 => u16, visit_u16,
+        deserialize_u32 => u32, visit_u32,
+        deserialize_u64 => u64, visit_u64,
+        deserialize_u128 => u128, visit_u128,
+    }
+
+    deserialize_hi_precision! {
+        deserialize_f64 => visit_f64,
+    }
+
+    /// Accepts a float64 as well as a float32, narrowing it under `require_exact_f32`.
+    /// High-precision numbers are parsed as with `deserialize_hi_precision!`.
+    fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.peek_marker()? {
+            marker::F64 => {
+                self.parse_marker()?;
+                let v = BigEndian::read_f64(self.read_slice(8)?);
+                let narrowed = v as f32;
+                if self.require_exact_f32 && f64::from(narrowed) != v && !v.is_nan() {
+                    return Err(Error::InexactFloat(v));
+                }
+                visitor.visit_f32(narrowed)
+            }
+            marker::HI_PRECISION => {
+                self.parse_marker()?;
+                let digits = self.parse_str()?;
+                match digits.parse() {
+                    Ok(v) => visitor.visit_f32(v),
+                    Err(_) => Err(de::Error::invalid_value(
+                        Unexpected::Other(digits),
+                        &visitor,
+                    )),
+                }
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    /// A high-precision number is visited as its string of digits.
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if self.peek_marker()? == marker::HI_PRECISION {
+            self.parse_marker()?;
+            return visitor.visit_borrowed_str(self.parse_str()?);
+        }
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    /// A counted array of `U` is visited as the bytes it holds, borrowed from the input rather than
+    /// decoded one element at a time. Anything else is deserialized as usual.
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if self.peek_marker()? != marker::ARR_START {
+            return self.deserialize_any(visitor);
+        }
+        let position = (self.index, self.pending);
+        self.parse_marker()?;
+        match self.parse_container_header()? {
+            (Some(marker::U8), Some(len)) => visitor.visit_borrowed_bytes(self.read_slice(len)?),
+            _ => {
+                let (index, pending) = position;
+                self.index = index;
+                self.pending = pending;
+                self.deserialize_any(visitor)
+            }
+        }
+    }
+
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_bytes(visitor)
+    }
+
+    /// Only `T` and `F` are booleans; integers such as 0 and 1 are rejected.
+    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.parse_marker()? {
+            marker::TRUE => visitor.visit_bool(true),
+            marker::FALSE => visitor.visit_bool(false),
+            m => Err(Error::InvalidMarker(m)),
+        }
+    }
+
+    /// Accepts an ASCII `C` value, a string of exactly one character, or an integer code point as
+    /// the serializer writes for non-ASCII characters.
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let typed = self.pending.is_some();
+        let m = self.parse_marker()?;
+        let c = match m {
+            marker::CHAR => self.parse_ascii_char()?,
+            marker::STRING => {
+                let mut chars = self.parse_str()?.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => c,
+                    _ => return Err(Error::InvalidChar),
+                }
+            }
+            marker::I8 | marker::U8 | marker::I16 | marker::I32 | marker::I64 => {
+                if !typed {
+                    self.check_canonical(m)?;
+                }
+                let v = self.parse_integer(m)?;
+                u32::try_from(v)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or(Error::InvalidChar)?
+            }
+            _ => return Err(Error::InvalidMarker(m)),
+        };
+        visitor.visit_char(c)
+    }
+
+    /// A null is `None`, anything else is `Some`. Since `()` is also written as a null by default,
+    /// `Option<()>` can only ever decode as `Some(())` if it was written with
+    /// `unit_as_empty_array`.
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let m = self.peek_marker()?;
+        let position = (self.index, self.pending);
+        if !self.faithful_options || self.some_start.take() != Some(position) {
+            if m == marker::NULL {
+                self.parse_marker()?;
+                return visitor.visit_none();
+            }
+            if !self.faithful_options {
+                return visitor.visit_some(self);
+            }
+            // Elements of a strongly-typed array of `Z`, `T` or `F` all start at the same
+            // position, so the mark must not outlive this `Some`.
+            self.some_start = Some(position);
+            let value = visitor.visit_some(&mut *self);
+            self.some_start = None;
+            return value;
+        }
+        // The value of a `Some` is itself an option, wrapped in a single-element array.
+        if m != marker::ARR_START {
+            return Err(Error::InvalidMarker(m));
+        }
+        self.parse_marker()?;
+        let (ty, len) = self.parse_container_header()?;
+        if let Some(len) = len {
+            if len != 1 {
+                return Err(Error::LengthMismatch {
+                    expected: 1,
+                    found: len,
+                });
+            }
+        }
+        self.pending = ty;
+        let value = if self.peek_marker()? == marker::NULL {
+            self.parse_marker()?;
+            visitor.visit_none::<Error>()?
+        } else {
+            self.some_start = Some((self.index, self.pending));
+            let value = visitor.visit_some(&mut *self);
+            self.some_start = None;
+            value?
+        };
+        self.end_container(len, 1, marker::ARR_END)?;
+        Ok(value)
+    }
+
+    /// Accepts an empty array as well as a null, since that is how `unit_as_empty_array` writes
+    /// units. Under `faithful_options`, the value of a `Some` may also be a null wrapped in a
+    /// single-element array, as the serializer writes any `Some` whose value is a null.
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let m = self.peek_marker()?;
+        let position = (self.index, self.pending);
+        let in_some = self.faithful_options && self.some_start.take() == Some(position);
+        if m != marker::ARR_START {
+            return self.deserialize_any(visitor);
+        }
+        self.parse_marker()?;
+        let (ty, len) = self.parse_container_header()?;
+        let wrapped = match len {
+            Some(len) => len == 1,
+            None => self.peek_marker()? != marker::ARR_END,
+        };
+        if in_some && wrapped {
+            self.pending = ty;
+            match self.parse_marker()? {
+                marker::NULL => {}
+                m => return Err(Error::InvalidMarker(m)),
+            }
+            self.end_container(len, 1, marker::ARR_END)?;
+        } else {
+            self.end_container(len, 0, marker::ARR_END)?;
+        }
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_unit(visitor)
+    }
+
+    /// Tuples are arrays holding exactly `len` elements; a count that says otherwise is rejected
+    /// with `Error::LengthMismatch` before any element is read.
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if self.peek_marker()? != marker::ARR_START {
+            return self.deserialize_any(visitor);
+        }
+        self.parse_marker()?;
+        self.parse_array(Some(len), visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    /// Accepts either `StructRepresentation`, so data written in both can be read by the same
+    /// code. An array is decoded positionally and must hold exactly one element per field, as with
+    /// tuples, while an object's keys are matched against the field names.
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.peek_marker()? {
+            marker::ARR_START => {
+                self.parse_marker()?;
+                self.parse_array(Some(fields.len()), visitor)
+            }
+            marker::OBJ_START => {
+                self.parse_marker()?;
+                self.parse_object(Some(fields), visitor)
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    /// Unit variants are a bare variant index, while other variants are an array holding the
+    /// index followed by their fields.
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if self.peek_marker()? != marker::ARR_START {
+            return visitor.visit_enum(UnitVariantAccess { de: self });
+        }
+        self.parse_marker()?;
+        let (ty, len) = self.parse_container_header()?;
+        self.check_count(len, min_value_size(ty))?;
+        self.enter()?;
+        let mut read = 0;
+        let value = visitor.visit_enum(VariantAccess {
+            seq: SeqAccess {
+                de: self,
+                ty,
+                len,
+                read: &mut read,
+            },
+        })?;
+        self.end_container(len, read, marker::ARR_END)?;
+        self.leave();
+        Ok(value)
+    }
+
+    /// A `RawValue` is handed the bytes of the next value, including its marker even when that is
+    /// implied by a strongly-typed container.
+    fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if name != RAW_VALUE_TOKEN {
+            return visitor.visit_newtype_struct(self);
+        }
+        let implied = self.pending;
+        self.peek_marker()?;
+        let start = self.index;
+        de::Deserializer::deserialize_ignored_any(&mut *self, de::IgnoredAny)?;
+        let mut bytes = Vec::with_capacity(self.index - start + 1);
+        bytes.extend(implied);
+        bytes.extend_from_slice(&self.input[start..self.index]);
+        visitor.visit_byte_buf(bytes)
+    }
+
+    /// Skips over the next value, however deeply nested, without building anything from it.
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let typed = self.pending.is_some();
+        let m = self.parse_marker()?;
+        if !typed {
+            self.check_canonical(m)?;
+        }
+        self.skip_value(m)?;
+        visitor.visit_unit()
+    }
+
+    serde::forward_to_deserialize_any! {
+        seq map identifier
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+struct SeqAccess<'a, 'de: 'a> {
+    de: &'a mut Deserializer<'de>,
+    ty: Option<u8>,
+    len: Option<usize>,
+    read: &'a mut usize,
+}
+
+impl<'de, 'a> de::SeqAccess<'de> for SeqAccess<'a, 'de> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self.len {
+            Some(len) if *self.read == len => return Ok(None),
+            Some(_) => {}
+            None => {
+                if self.de.peek_marker()? == marker::ARR_END {
+                    return Ok(None);
+                }
+                if *self.read >= self.de.max_length {
+                    return Err(Error::LengthLimitExceeded);
+                }
+            }
+        }
+        *self.read += 1;
+        self.de.pending = self.ty;
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.len.map(|len| {
+            self.de
+                .cautious_len(len - *self.read, min_value_size(self.ty))
+        })
+    }
+}
+
+struct MapAccess<'a, 'de: 'a> {
+    de: &'a mut Deserializer<'de>,
+    ty: Option<u8>,
+    len: Option<usize>,
+    read: &'a mut usize,
+    /// Keys read so far, when duplicates are denied.
+    seen: Option<HashSet<&'de str>>,
+    /// The only keys allowed, when unknown fields are denied.
+    fields: Option<&'static [&'static str]>,
+}
+
+impl<'de, 'a> de::MapAccess<'de> for MapAccess<'a, 'de> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        match self.len {
+            Some(len) if *self.read == len => return Ok(None),
+            Some(_) => {}
+            None => {
+                if self.de.peek_marker()? == marker::OBJ_END {
+                    return Ok(None);
+                }
+                if *self.read >= self.de.max_length {
+                    return Err(Error::LengthLimitExceeded);
+                }
+            }
+        }
+        *self.read += 1;
+        if self.seen.is_some() || self.fields.is_some() {
+            let start = self.de.index;
+            let key = self.de.parse_str()?;
+            if let Some(ref mut seen) = self.seen {
+                if !seen.insert(key) {
+                    return Err(Error::DuplicateKey(key.to_owned()));
+                }
+            }
+            if let Some(fields) = self.fields {
+                if !fields.contains(&key) {
+                    return Err(Error::UnknownField(key.to_owned()));
+                }
+            }
+            self.de.index = start;
+        }
+        seed.deserialize(MapKey { de: &mut *self.de }).map(Some)
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        self.de.pending = self.ty;
+        seed.deserialize(&mut *self.de)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.len.map(|len| {
+            self.de
+                .cautious_len(len - *self.read, MIN_KEY_SIZE + min_value_size(self.ty))
+        })
+    }
+}
+
+/// Access to a variant written as an array of its index and fields.
+struct VariantAccess<'a, 'de: 'a> {
+    seq: SeqAccess<'a, 'de>,
+}
+
+impl<'de, 'a> de::EnumAccess<'de> for VariantAccess<'a, 'de> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V>(mut self, seed: V) -> Result<(V::Value, Self)>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        match de::SeqAccess::next_element_seed(&mut self.seq, seed)? {
+            Some(variant) => Ok((variant, self)),
+            None => Err(de::Error::invalid_length(0, &"a variant index")),
+        }
+    }
+}
+
+impl<'de, 'a> de::VariantAccess<'de> for VariantAccess<'a, 'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T>(mut self, seed: T) -> Result<T::Value>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match de::SeqAccess::next_element_seed(&mut self.seq, seed)? {
+            Some(value) => Ok(value),
+            None => Err(de::Error::invalid_length(1, &"a variant index and a value")),
+        }
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_seq(self.seq)
+    }
+
+    /// Fields are read positionally, unless the tag is followed by nothing but an object, which
+    /// is how `StructRepresentation::Object` writes them. A single field holding a map, written
+    /// positionally, looks the same and is read as an object as well.
+    fn struct_variant<V>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let last = match self.seq.len {
+            Some(len) => len - *self.seq.read == 1,
+            None => true,
+        };
+        if self.seq.ty.is_none() && last && self.seq.de.peek_marker()? == marker::OBJ_START {
+            self.seq.de.parse_marker()?;
+            let value = self.seq.de.parse_object(Some(fields), visitor)?;
+            *self.seq.read += 1;
+            return Ok(value);
+        }
+        visitor.visit_seq(self.seq)
+    }
+}
+
+/// Access to a unit variant, written as just its index.
+struct UnitVariantAccess<'a, 'de: 'a> {
+    de: &'a mut Deserializer<'de>,
+}
+
+impl<'de, 'a> de::EnumAccess<'de> for UnitVariantAccess<'a, 'de> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self)>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let variant = seed.deserialize(&mut *self.de)?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de, 'a> de::VariantAccess<'de> for UnitVariantAccess<'a, 'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T>(self, _seed: T) -> Result<T::Value>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        Err(de::Error::invalid_type(
+            Unexpected::UnitVariant,
+            &"newtype variant",
+        ))
+    }
+
+    fn tuple_variant<V>(self, _len: usize, _visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        Err(de::Error::invalid_type(
+            Unexpected::UnitVariant,
+            &"tuple variant",
+        ))
+    }
+
+    fn struct_variant<V>(self, _fields: &'static [&'static str], _visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        Err(de::Error::invalid_type(
+            Unexpected::UnitVariant,
+            &"struct variant",
+        ))
+    }
+}
+
+/// Reads keys written by `Serializer::stringify_non_string_keys` back into scalars. A key that
+/// does not parse is handed to the visitor as a string, so the error comes from the visitor.
+macro_rules! deserialize_parsed_key {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value>
+            where
+                V: Visitor<'de>,
+            {
+                let s = self.de.parse_str()?;
+                match s.parse() {
+                    Ok(v) => visitor.$visit(v),
+                    Err(_) => visitor.visit_borrowed_str(s),
+                }
+            }
+        )*
+    };
+}
+
+/// Deserializer for object keys, which are strings without a leading `S` marker.
+struct MapKey<'a, 'de: 'a> {
+    de: &'a mut Deserializer<'de>,
+}
+
+impl<'de, 'a> de::Deserializer<'de> for MapKey<'a, 'de> {
+    type Error = Error;
+
+    fn is_human_readable(&self) -> bool {
+        self.de.human_readable
+    }
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_borrowed_str(self.de.parse_str()?)
+    }
+
+    deserialize_parsed_key! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_i128 => visit_i128,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_u128 => visit_u128,
+    }
+
+    serde::forward_to_deserialize_any! {
+        f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// Iterator over the values in a sequence of UBJSON values, created by
+/// `Deserializer::into_stream`.
+///
+/// Once the input runs out or a value fails to decode, every later call to `next` returns `None`.
+pub struct StreamDeserializer<'de, T> {
+    de: Deserializer<'de>,
+    failed: bool,
+    output: PhantomData<T>,
+}
+
+impl<'de, T> StreamDeserializer<'de, T> {
+    /// Returns how many bytes of input have been consumed by the values yielded so far. After an
+    /// error, this is where the value that failed begins.
+    pub fn byte_offset(&self) -> usize {
+        self.de.index
+    }
+}
+
+impl<'de, T> Iterator for StreamDeserializer<'de, T>
+where
+    T: Deserialize<'de>,
+{
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Result<T>> {
+        if self.failed {
+            return None;
+        }
+        while self.de.peek_byte() == Some(marker::NOOP) {
+            self.de.index += 1;
+        }
+        if self.de.remaining() == 0 {
+            return None;
+        }
+        let start = self.de.index;
+        match T::deserialize(&mut self.de) {
+            Ok(value) => Some(Ok(value)),
+            Err(err) => {
+                self.failed = true;
+                self.de.index = start;
+                Some(Err(err))
+            }
+        }
+    }
+
+    /// Every value takes at least a byte, but the rest of the input may be nothing but no-ops.
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.failed {
+            (0, Some(0))
+        } else {
+            (0, Some(self.de.remaining()))
+        }
+    }
+}
+
+impl<'de, T> FusedIterator for StreamDeserializer<'de, T> where T: Deserialize<'de> {}
@@ -0,0 +1,174 @@
+//! Render UBJSON data in the block notation used by the specification.
+
+use std::fmt::{Display, Write};
+use std::str;
+
+use byteorder::{BigEndian, ByteOrder};
+
+use crate::de::{self, Deserializer};
+use crate::error::{Error, Result};
+use crate::marker;
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// Render UBJSON bytes in block notation, such as `[S][U][5][hello]`.
+///
+/// Each value in `v` goes on its own line, as does each element of a container, indented by its
+/// depth. Elements of strongly-typed containers are shown without their implied markers, except
+/// for nulls, booleans and containers, which have nothing else to show them by. No-ops are shown
+/// wherever they appear.
+pub fn to_block_notation(v: &[u8]) -> Result<String> {
+    let mut renderer = Renderer {
+        de: Deserializer::from_slice(v),
+        out: String::new(),
+    };
+    while renderer.de.remaining() > 0 {
+        if !renderer.out.is_empty() {
+            renderer.out.push('\n');
+        }
+        let m = renderer.de.next_byte()?;
+        renderer.value(m, false, 0)?;
+    }
+    Ok(renderer.out)
+}
+
+struct Renderer<'a> {
+    de: Deserializer<'a>,
+    out: String,
+}
+
+impl<'a> Renderer<'a> {
+    fn block<T: Display>(&mut self, content: T) {
+        // Writing into a `String` cannot fail.
+        let _ = write!(self.out, "[{}]", content);
+    }
+
+    fn marker(&mut self, m: u8) {
+        self.block(char::from(m));
+    }
+
+    fn line(&mut self, indent: usize) {
+        self.out.push('\n');
+        for _ in 0..indent {
+            self.out.push_str("    ");
+        }
+    }
+
+    /// Renders a length along with its integer marker.
+    fn length(&mut self) -> Result<usize> {
+        let m = self.de.next_byte()?;
+        let len = self.de.parse_integer(m)?;
+        self.marker(m);
+        self.block(len);
+        self.de.check_length(len)
+    }
+
+    /// Renders a length-prefixed string without its `S` marker.
+    fn string(&mut self) -> Result<()> {
+        let len = self.length()?;
+        let bytes = self.de.read_slice(len)?;
+        let s = str::from_utf8(bytes).map_err(|_| Error::InvalidUtf8)?;
+        self.block(s);
+        Ok(())
+    }
+
+    /// Renders a value whose marker has already been read, leaving the marker out if it is implied
+    /// by a strongly-typed container and followed by a payload that can stand for it.
+    fn value(&mut self, m: u8, implied: bool, indent: usize) -> Result<()> {
+        let bare =
+            de::fixed_value_size(m) == Some(0) || m == marker::ARR_START || m == marker::OBJ_START;
+        if !implied || bare {
+            self.marker(m);
+        }
+        match m {
+            marker::NULL | marker::NOOP | marker::TRUE | marker::FALSE => {}
+            marker::I8 | marker::U8 | marker::I16 | marker::I32 | marker::I64 => {
+                let v = self.de.parse_integer(m)?;
+                self.block(v);
+            }
+            marker::F32 => {
+                let v = BigEndian::read_f32(self.de.read_slice(4)?);
+                self.block(v);
+            }
+            marker::F64 => {
+                let v = BigEndian::read_f64(self.de.read_slice(8)?);
+                self.block(v);
+            }
+            marker::CHAR => {
+                let c = self.de.parse_ascii_char()?;
+                self.block(c);
+            }
+            marker::HI_PRECISION | marker::STRING => self.string()?,
+            marker::ARR_START => self.container(marker::ARR_END, indent)?,
+            marker::OBJ_START => self.container(marker::OBJ_END, indent)?,
+            _ => return Err(Error::InvalidMarker(m)),
+        }
+        Ok(())
+    }
+
+    fn container(&mut self, end: u8, indent: usize) -> Result<()> {
+        let keyed = end == marker::OBJ_END;
+        let mut ty = None;
+        if self.de.peek_byte() == Some(marker::TYPE) {
+            self.de.next_byte()?;
+            let t = de::container_type(self.de.next_byte()?)?;
+            self.marker(marker::TYPE);
+            self.marker(t);
+            ty = Some(t);
+        }
+        let len = if self.de.peek_byte() == Some(marker::LENGTH) {
+            self.de.next_byte()?;
+            self.marker(marker::LENGTH);
+            Some(self.length()?)
+        } else if ty.is_some() {
+            return Err(self
+                .de
+                .peek_byte()
+                .map_or(Error::UnexpectedEof, Error::InvalidMarker));
+        } else {
+            None
+        };
+        let min_size = de::min_value_size(ty) + if keyed { de::MIN_KEY_SIZE } else { 0 };
+        self.de.check_count(len, min_size)?;
+
+        self.de.enter()?;
+        let mut read = 0;
+        loop {
+            if len == Some(read) {
+                break;
+            }
+            // No-ops may come before the marker of an element, but are not elements themselves.
+            if ty.is_none() && (!keyed || len.is_none()) {
+                while self.de.peek_byte() == Some(marker::NOOP) {
+                    self.de.next_byte()?;
+                    self.line(indent + 1);
+                    self.marker(marker::NOOP);
+                }
+            }
+            if len.is_none() && self.de.peek_byte().ok_or(Error::UnexpectedEof)? == end {
+                self.de.next_byte()?;
+                self.line(indent);
+                self.marker(end);
+                break;
+            }
+            self.line(indent + 1);
+            if keyed {
+                self.string()?;
+            }
+            match ty {
+                Some(t) => self.value(t, true, indent + 1)?,
+                None => {
+                    let mut m = self.de.next_byte()?;
+                    while m == marker::NOOP {
+                        self.marker(m);
+                        m = self.de.next_byte()?;
+                    }
+                    self.value(m, false, indent + 1)?;
+                }
+            }
+            read += 1;
+        }
+        self.de.leave();
+        Ok(())
+    }
+}
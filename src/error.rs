@@ -4,27 +4,140 @@ use std::io;
 
 use serde::{de, ser};
 
+use crate::marker;
+
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// Everything that can go wrong while serializing or deserializing.
+///
+/// It is `Send + Sync + 'static`, and will stay so, so errors can be passed between threads or
+/// boxed as a `Box<dyn std::error::Error + Send + Sync>`.
 #[derive(Debug)]
 pub enum Error {
     Message(String),
     Io(io::Error),
-    KeyMustBeAString,
+    KeyMustBeAString(&'static str),
+    UnexpectedEof,
+    InvalidMarker(u8),
+    InvalidUtf8,
+    InvalidChar,
+    DepthLimitExceeded,
+    LengthLimitExceeded,
+    LengthOverflow,
+    NonFiniteFloat,
+    DuplicateKey(String),
+    BufferFull,
+    LengthMismatch {
+        expected: usize,
+        found: usize,
+    },
+    UnknownField(String),
+    NonCanonical,
+    IntegerOutOfRange {
+        target: &'static str,
+        value: i128,
+    },
+    OutputLimitExceeded,
+    TrailingBytes {
+        remaining: usize,
+    },
+    InexactFloat(f64),
+    /// An error raised while serializing the inside of a container, along with where, such as
+    /// `endpoints[2].headers`. Only produced with the `error_path` feature.
+    WithPath {
+        path: String,
+        error: Box<Error>,
+    },
+    /// An error from outside this crate, kept whole so that it can be recovered from `source` with
+    /// `downcast_ref`. Made by `Error::custom_error`; `ser::Error::custom` and `de::Error::custom`
+    /// are only given something to display, so they make a `Message` instead.
+    Custom(Box<dyn std::error::Error + Send + Sync>),
 }
 
 impl Display for Error {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-        formatter.write_str(std::error::Error::description(self))
+        match *self {
+            Error::Message(ref msg) => formatter.write_str(msg),
+            Error::Io(ref err) => Display::fmt(err, formatter),
+            Error::KeyMustBeAString(found) => {
+                write!(formatter, "key must be a string, found {}", found)
+            }
+            Error::UnexpectedEof => formatter.write_str("unexpected end of input"),
+            Error::InvalidMarker(m) => match marker::marker_name(m) {
+                Some(name) => write!(formatter, "invalid marker {:?} ({})", m as char, name),
+                None => write!(formatter, "invalid marker {:?}", m as char),
+            },
+            Error::InvalidUtf8 => formatter.write_str("string is not valid UTF-8"),
+            Error::InvalidChar => formatter.write_str("invalid character"),
+            Error::DepthLimitExceeded => formatter.write_str("nesting depth limit exceeded"),
+            Error::LengthLimitExceeded => formatter.write_str("length limit exceeded"),
+            Error::LengthOverflow => formatter.write_str("length does not fit in usize"),
+            Error::NonFiniteFloat => formatter.write_str("float must be finite"),
+            Error::DuplicateKey(ref key) => write!(formatter, "duplicate key `{}`", key),
+            Error::BufferFull => formatter.write_str("output buffer is full"),
+            Error::LengthMismatch { expected, found } => write!(
+                formatter,
+                "expected a container of {} elements, found {}",
+                expected, found
+            ),
+            Error::UnknownField(ref key) => write!(formatter, "unknown field `{}`", key),
+            Error::NonCanonical => formatter.write_str("integer is wider than it needs to be"),
+            Error::IntegerOutOfRange { target, value } => {
+                write!(formatter, "integer {} does not fit in {}", value, target)
+            }
+            Error::OutputLimitExceeded => formatter.write_str("output size limit exceeded"),
+            Error::TrailingBytes { remaining } => {
+                write!(formatter, "{} trailing bytes after the value", remaining)
+            }
+            Error::InexactFloat(v) => write!(formatter, "float {} does not fit in f32 exactly", v),
+            Error::WithPath {
+                ref path,
+                ref error,
+            } => write!(formatter, "error at `{}`: {}", path, error),
+            Error::Custom(ref err) => Display::fmt(err, formatter),
+        }
     }
 }
 
 impl std::error::Error for Error {
-    fn description(&self) -> &str {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match *self {
-            Error::Message(ref msg) => msg,
-            Error::Io(ref err) => err.description(),
-            Error::KeyMustBeAString => "key must be a string",
+            Error::Io(ref err) => Some(err),
+            Error::WithPath { ref error, .. } => error.source(),
+            Error::Custom(ref err) => Some(&**err),
+            _ => None,
+        }
+    }
+}
+
+impl Error {
+    /// Wraps an error of the caller's own, such as one raised by a hand-written serialization
+    /// routine, in `Error::Custom`.
+    pub fn custom_error<E>(error: E) -> Error
+    where
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        Error::Custom(error.into())
+    }
+
+    /// Adds `segment`, a field name or key or an index such as `[2]`, to the front of the path
+    /// where the error happened. Errors from the writer rather than the value, such as `Io`, are
+    /// left as they are.
+    #[cfg(feature = "error_path")]
+    pub(crate) fn within(self, segment: &str) -> Error {
+        match self {
+            Error::Io(_) | Error::OutputLimitExceeded => self,
+            Error::WithPath { path, error } => {
+                let separator = if path.starts_with('[') { "" } else { "." };
+                Error::WithPath {
+                    path: format!("{}{}{}", segment, separator, path),
+                    error,
+                }
+            }
+            error => Error::WithPath {
+                path: segment.to_owned(),
+                error: Box::new(error),
+            },
         }
     }
 }
@@ -47,8 +160,28 @@ impl de::Error for Error {
     }
 }
 
+/// Wraps the error in `Error::Io`, so `?` works on IO results in code returning this crate's
+/// `Result`.
 impl From<io::Error> for Error {
     fn from(e: io::Error) -> Self {
+        if let Some(inner) = e.get_ref() {
+            if inner.is::<OutputLimit>() {
+                return Error::OutputLimitExceeded;
+            }
+        }
         Error::Io(e)
     }
 }
+
+/// Raised as an IO error by the serializer's writer when `max_output_bytes` would be exceeded,
+/// and turned into `Error::OutputLimitExceeded` on the way out.
+#[derive(Debug)]
+pub(crate) struct OutputLimit;
+
+impl Display for OutputLimit {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("output size limit exceeded")
+    }
+}
+
+impl std::error::Error for OutputLimit {}
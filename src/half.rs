@@ -0,0 +1,93 @@
+//! Helpers for serializing a `half::f16`, which UBJSON has no marker for, for use with
+//! `#[serde(with = "...")]`.
+//!
+//! ```ignore
+//! #[derive(Serialize, Deserialize)]
+//! struct Vertex {
+//!     #[serde(with = "serde_ubjson::half::as_f32")]
+//!     x: f16,
+//! }
+//! ```
+
+/// Serialize an `f16` as a float32, which holds every `f16` exactly.
+pub mod as_f32 {
+    use ::half::f16;
+    use serde::de::{Deserialize, Deserializer};
+    use serde::ser::Serializer;
+
+    pub fn serialize<S>(v: &f16, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_f32(v.to_f32())
+    }
+
+    /// Reads any float, rounding it to the nearest `f16`.
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<f16, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        f32::deserialize(deserializer).map(f16::from_f32)
+    }
+}
+
+/// Serialize an `f16` as a high-precision number holding the shortest decimal that reads back as
+/// the same `f16`. NaN and infinities cannot be written this way.
+pub mod as_hi_precision {
+    use std::fmt;
+
+    use ::half::f16;
+    use serde::de::{self, Deserializer, Visitor};
+    use serde::ser::{self, Serializer};
+
+    use crate::value::HIGH_PRECISION_TOKEN;
+
+    pub fn serialize<S>(v: &f16, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if !v.is_finite() {
+            return Err(ser::Error::custom("high-precision number must be finite"));
+        }
+        // Widening is exact, so the shortest `f32` decimal also identifies the `f16`.
+        serializer.serialize_newtype_struct(HIGH_PRECISION_TOKEN, &v.to_f32().to_string())
+    }
+
+    /// Reads a high-precision number, or any other number, rounding it to the nearest `f16`.
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<f16, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_str(F16Visitor)
+    }
+
+    struct F16Visitor;
+
+    impl<'de> Visitor<'de> for F16Visitor {
+        type Value = f16;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a number")
+        }
+
+        fn visit_i64<E>(self, v: i64) -> Result<f16, E> {
+            Ok(f16::from_f64(v as f64))
+        }
+
+        fn visit_u64<E>(self, v: u64) -> Result<f16, E> {
+            Ok(f16::from_f64(v as f64))
+        }
+
+        fn visit_f64<E>(self, v: f64) -> Result<f16, E> {
+            Ok(f16::from_f64(v))
+        }
+
+        fn visit_str<E>(self, v: &str) -> Result<f16, E>
+        where
+            E: de::Error,
+        {
+            v.parse()
+                .map_err(|_| E::invalid_value(de::Unexpected::Str(v), &self))
+        }
+    }
+}
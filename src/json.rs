@@ -0,0 +1,273 @@
+//! Convert between UBJSON and JSON text as a stream, without building a `Value`.
+
+use std::fmt;
+use std::io::{Read, Write};
+
+use serde::de::{self, DeserializeSeed, Visitor};
+use serde::ser::Error as _;
+
+use crate::error::{Error, Result};
+use crate::reader::UbjsonReader;
+use crate::token::Token;
+use crate::writer::UbjsonWriter;
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// Convert the UBJSON values read from `reader` into JSON text written to `writer`, putting each
+/// top-level value on its own line.
+///
+/// High-precision numbers become JSON numbers with the same digits, characters become strings,
+/// and NaN and infinite floats become `null`. The input is read with the default limits of
+/// `UbjsonReader`, so a strongly-typed array of `Z`, `T` or `F` cannot declare more than
+/// `DEFAULT_MAX_ZERO_WIDTH_COUNT` elements.
+pub fn ubjson_to_json<R, W>(reader: R, mut writer: W) -> Result<()>
+where
+    R: Read,
+    W: Write,
+{
+    let mut reader = UbjsonReader::new(reader);
+    // For each open container, whether it is an object and whether nothing has been written in
+    // it yet.
+    let mut stack: Vec<(bool, bool)> = Vec::new();
+    let mut started = false;
+    while let Some(token) = reader.next_token()? {
+        match stack.last_mut() {
+            None => {
+                if started {
+                    writer.write_all(b"\n")?;
+                }
+                started = true;
+            }
+            Some(&mut (keyed, ref mut first)) => {
+                // In an object, the comma goes before the key rather than the value.
+                let entry = match token {
+                    Token::Key(_) => true,
+                    Token::ArrayEnd | Token::ObjectEnd => false,
+                    _ => !keyed,
+                };
+                if entry && !*first {
+                    writer.write_all(b",")?;
+                }
+                *first &= !entry;
+            }
+        }
+        match token {
+            Token::Null => writer.write_all(b"null")?,
+            Token::Bool(v) => write!(writer, "{}", v)?,
+            Token::Int(v) => write!(writer, "{}", v)?,
+            Token::Float(v) => serde_json::to_writer(&mut writer, &v).map_err(json_error)?,
+            Token::HighPrecision(digits) => {
+                if !is_json_number(&digits) {
+                    return Err(Error::custom(format_args!(
+                        "invalid high-precision number `{}`",
+                        digits
+                    )));
+                }
+                writer.write_all(digits.as_bytes())?;
+            }
+            Token::Char(c) => serde_json::to_writer(&mut writer, &c).map_err(json_error)?,
+            Token::Str(s) => serde_json::to_writer(&mut writer, &s).map_err(json_error)?,
+            Token::Key(key) => {
+                serde_json::to_writer(&mut writer, &key).map_err(json_error)?;
+                writer.write_all(b":")?;
+            }
+            Token::ArrayStart { .. } => {
+                stack.push((false, true));
+                writer.write_all(b"[")?;
+            }
+            Token::ObjectStart { .. } => {
+                stack.push((true, true));
+                writer.write_all(b"{")?;
+            }
+            Token::ArrayEnd => {
+                stack.pop();
+                writer.write_all(b"]")?;
+            }
+            Token::ObjectEnd => {
+                stack.pop();
+                writer.write_all(b"}")?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Convert the JSON values read from `reader`, separated by whitespace, into UBJSON written to
+/// `writer`.
+///
+/// Integers take the smallest marker that holds them, and those beyond `i64::MAX` become
+/// high-precision numbers. Arrays and objects are written unsized, since JSON gives no counts.
+pub fn json_to_ubjson<R, W>(reader: R, writer: W) -> Result<()>
+where
+    R: Read,
+    W: Write,
+{
+    let mut de = serde_json::Deserializer::from_reader(reader);
+    let mut writer = UbjsonWriter::new(writer);
+    let mut error = None;
+    while de.end().is_err() {
+        let result = Transcode {
+            writer: &mut writer,
+            error: &mut error,
+        }
+        .deserialize(&mut de);
+        // An error from the writer is kept aside rather than passed through serde_json, which
+        // could only carry its message.
+        if let Some(err) = error.take() {
+            return Err(err);
+        }
+        result.map_err(json_error)?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+fn json_error(err: serde_json::Error) -> Error {
+    if err.is_io() {
+        Error::Io(err.into())
+    } else {
+        Error::custom(err)
+    }
+}
+
+/// Checks `s` against the JSON number grammar.
+fn is_json_number(s: &str) -> bool {
+    let s = s.strip_prefix('-').unwrap_or(s);
+    let digits = |s: &str| s.len() - s.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let int = digits(s);
+    if int == 0 || (int > 1 && s.starts_with('0')) {
+        return false;
+    }
+    let mut rest = &s[int..];
+    if let Some(frac) = rest.strip_prefix('.') {
+        let n = digits(frac);
+        if n == 0 {
+            return false;
+        }
+        rest = &frac[n..];
+    }
+    if let Some(exp) = rest.strip_prefix(|c| c == 'e' || c == 'E') {
+        let exp = exp.strip_prefix(|c| c == '+' || c == '-').unwrap_or(exp);
+        let n = digits(exp);
+        if n == 0 {
+            return false;
+        }
+        rest = &exp[n..];
+    }
+    rest.is_empty()
+}
+
+/// Writes each value it visits to the UBJSON writer as it goes.
+struct Transcode<'a, W> {
+    writer: &'a mut UbjsonWriter<W>,
+    /// The first error from `writer`, which aborts deserialization.
+    error: &'a mut Option<Error>,
+}
+
+impl<'a, W> Transcode<'a, W>
+where
+    W: Write,
+{
+    fn write<E>(&mut self, token: Token) -> std::result::Result<(), E>
+    where
+        E: de::Error,
+    {
+        self.writer.write_token(&token).map_err(|err| {
+            let message = E::custom(&err);
+            *self.error = Some(err);
+            message
+        })
+    }
+
+    /// Borrows the writer for a nested value.
+    fn nested(&mut self) -> Transcode<'_, W> {
+        Transcode {
+            writer: &mut *self.writer,
+            error: &mut *self.error,
+        }
+    }
+}
+
+impl<'de, 'a, W> DeserializeSeed<'de> for Transcode<'a, W>
+where
+    W: Write,
+{
+    type Value = ();
+
+    fn deserialize<D>(self, deserializer: D) -> std::result::Result<(), D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de, 'a, W> Visitor<'de> for Transcode<'a, W>
+where
+    W: Write,
+{
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("any JSON value")
+    }
+
+    fn visit_unit<E: de::Error>(mut self) -> std::result::Result<(), E> {
+        self.write(Token::Null)
+    }
+
+    fn visit_bool<E: de::Error>(mut self, v: bool) -> std::result::Result<(), E> {
+        self.write(Token::Bool(v))
+    }
+
+    fn visit_i64<E: de::Error>(mut self, v: i64) -> std::result::Result<(), E> {
+        self.write(Token::Int(v))
+    }
+
+    fn visit_u64<E: de::Error>(mut self, v: u64) -> std::result::Result<(), E> {
+        if v > i64::MAX as u64 {
+            self.write(Token::HighPrecision(v.to_string()))
+        } else {
+            self.write(Token::Int(v as i64))
+        }
+    }
+
+    fn visit_f64<E: de::Error>(mut self, v: f64) -> std::result::Result<(), E> {
+        self.write(Token::Float(v))
+    }
+
+    fn visit_str<E: de::Error>(mut self, v: &str) -> std::result::Result<(), E> {
+        self.write(Token::Str(v.to_owned()))
+    }
+
+    fn visit_string<E: de::Error>(mut self, v: String) -> std::result::Result<(), E> {
+        self.write(Token::Str(v))
+    }
+
+    fn visit_seq<A>(mut self, mut seq: A) -> std::result::Result<(), A::Error>
+    where
+        A: de::SeqAccess<'de>,
+    {
+        self.write(Token::ArrayStart {
+            len: None,
+            ty: None,
+        })?;
+        while let Some(()) = seq.next_element_seed(self.nested())? {}
+        self.write(Token::ArrayEnd)
+    }
+
+    fn visit_map<A>(mut self, mut map: A) -> std::result::Result<(), A::Error>
+    where
+        A: de::MapAccess<'de>,
+    {
+        self.write(Token::ObjectStart {
+            len: None,
+            ty: None,
+        })?;
+        while let Some(key) = map.next_key::<String>()? {
+            self.write(Token::Key(key))?;
+            map.next_value_seed(self.nested())?;
+        }
+        self.write(Token::ObjectEnd)
+    }
+}
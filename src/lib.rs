@@ -1,6 +1,39 @@
+pub mod bool_enum;
+pub mod byte_array;
+#[cfg(feature = "chrono")]
+pub mod chrono;
+pub mod de;
+pub mod debug;
 pub mod error;
-mod marker;
+#[cfg(feature = "half")]
+pub mod half;
+#[cfg(feature = "serde_json")]
+pub mod json;
+pub mod marker;
+#[cfg(feature = "num-bigint")]
+pub mod num_bigint;
+pub mod raw;
+pub mod reader;
+#[cfg(feature = "rust_decimal")]
+pub mod rust_decimal;
 pub mod ser;
+pub mod time;
+pub mod token;
+#[cfg(feature = "tokio")]
+pub mod tokio;
+#[cfg(feature = "uuid")]
+pub mod uuid;
+pub mod value;
+pub mod writer;
 
+pub use de::{from_slice, from_slice_partial, Deserializer, StreamDeserializer};
 pub use error::{Error, Result};
-pub use ser::{to_vec, to_writer, Serializer};
+pub use raw::{to_raw_value, RawValue};
+pub use reader::{validate, UbjsonReader};
+pub use ser::{
+    serialized_size, to_buf, to_slice, to_vec, to_vec_with_capacity, to_writer, to_writer_framed,
+    Serializer, TypeTag, TypedElement,
+};
+pub use token::Token;
+pub use value::{from_value, Value};
+pub use writer::UbjsonWriter;
@@ -1,30 +1,62 @@
+//! Type markers, the single bytes that introduce each UBJSON value and container.
+
 macro_rules! make_consts {
-    ( $($name:ident = $val:expr);* $(;)? ) => {
+    ( $($(#[$attr:meta])* $name:ident = $val:expr => $desc:expr);* $(;)? ) => {
         $(
-            pub(crate) const $name: u8 = $val;
+            $(#[$attr])*
+            pub const $name: u8 = $val;
         )*
+
+        /// Returns a short name for a marker, such as `"int16"` for `I16`, or `None` if `byte` is
+        /// not a marker.
+        pub fn marker_name(byte: u8) -> Option<&'static str> {
+            match byte {
+                $( $name => Some($desc), )*
+                _ => None,
+            }
+        }
     };
 }
 
 make_consts! {
-    NULL = b'Z';
-    NOOP = b'N';
-    TRUE = b'T';
-    FALSE = b'F';
-    I8 = b'i';
-    U8 = b'U';
-    I16 = b'I';
-    I32 = b'l';
-    I64 = b'L';
-    F32 = b'd';
-    F64 = b'D';
-    HI_PRECISION = b'H';
-    CHAR = b'C';
-    STRING = b'S';
-    ARR_START = b'[';
-    ARR_END = b']';
-    OBJ_START = b'{';
-    OBJ_END = b'}';
-    TYPE = b'$';
-    LENGTH = b'#';
+    /// Null value.
+    NULL = b'Z' => "null";
+    /// No-op, which may appear anywhere a value marker can and carries no value.
+    NOOP = b'N' => "no-op";
+    /// Boolean true.
+    TRUE = b'T' => "true";
+    /// Boolean false.
+    FALSE = b'F' => "false";
+    /// Signed 8-bit integer.
+    I8 = b'i' => "int8";
+    /// Unsigned 8-bit integer.
+    U8 = b'U' => "uint8";
+    /// Signed 16-bit integer.
+    I16 = b'I' => "int16";
+    /// Signed 32-bit integer.
+    I32 = b'l' => "int32";
+    /// Signed 64-bit integer.
+    I64 = b'L' => "int64";
+    /// 32-bit float.
+    F32 = b'd' => "float32";
+    /// 64-bit float.
+    F64 = b'D' => "float64";
+    /// High-precision number, written as a string of decimal digits.
+    HI_PRECISION = b'H' => "high-precision number";
+    /// ASCII character.
+    CHAR = b'C' => "char";
+    /// UTF-8 string.
+    STRING = b'S' => "string";
+    /// Start of an array.
+    ARR_START = b'[' => "array start";
+    /// End of an array.
+    ARR_END = b']' => "array end";
+    /// Start of an object.
+    OBJ_START = b'{' => "object start";
+    /// End of an object.
+    OBJ_END = b'}' => "object end";
+    /// Introduces the element type of a strongly-typed container.
+    TYPE = b'$' => "container type";
+    /// Introduces the element count of a container.
+    LENGTH = b'#' => "container count";
 }
@@ -0,0 +1,178 @@
+//! Helpers for serializing `num_bigint` integers, for use with `#[serde(with = "...")]`.
+//!
+//! A value that fits in an `i64` or `u64` is written as a plain integer, and a larger one as a
+//! high-precision number holding its digits, the same way `serialize_u128` writes one. Either
+//! reads back, as does a string of digits, but a number with a fraction or an exponent is
+//! rejected.
+//!
+//! ```ignore
+//! #[derive(Serialize, Deserialize)]
+//! struct Account {
+//!     #[serde(with = "serde_ubjson::num_bigint::bigint")]
+//!     balance: BigInt,
+//! }
+//! ```
+
+use serde::de::{self, Unexpected};
+
+/// Whether `v` is written as an integer in the JSON number grammar, an optional minus sign and
+/// then digits alone.
+fn is_integer(v: &str) -> bool {
+    let digits = v.strip_prefix('-').unwrap_or(v);
+    !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Rejects the digits of a high-precision number, or a string, unless they form an integer.
+fn check_integer<E>(v: &str, exp: &dyn de::Expected) -> Result<(), E>
+where
+    E: de::Error,
+{
+    if is_integer(v) {
+        Ok(())
+    } else {
+        Err(E::invalid_value(Unexpected::Str(v), exp))
+    }
+}
+
+/// Serialize a `BigInt`.
+pub mod bigint {
+    use std::convert::TryFrom;
+    use std::fmt;
+
+    use ::num_bigint::BigInt;
+    use serde::de::{self, Deserializer, Visitor};
+    use serde::ser::Serializer;
+
+    use super::check_integer;
+    use crate::value::HIGH_PRECISION_TOKEN;
+
+    pub fn serialize<S>(v: &BigInt, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match i64::try_from(v) {
+            Ok(v) => serializer.serialize_i64(v),
+            Err(_) => serializer.serialize_newtype_struct(HIGH_PRECISION_TOKEN, &v.to_string()),
+        }
+    }
+
+    /// Reads any integer, including a high-precision one.
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<BigInt, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_str(BigIntVisitor)
+    }
+
+    struct BigIntVisitor;
+
+    impl<'de> Visitor<'de> for BigIntVisitor {
+        type Value = BigInt;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("an integer")
+        }
+
+        fn visit_i64<E>(self, v: i64) -> Result<BigInt, E> {
+            Ok(BigInt::from(v))
+        }
+
+        fn visit_u64<E>(self, v: u64) -> Result<BigInt, E> {
+            Ok(BigInt::from(v))
+        }
+
+        fn visit_i128<E>(self, v: i128) -> Result<BigInt, E> {
+            Ok(BigInt::from(v))
+        }
+
+        fn visit_u128<E>(self, v: u128) -> Result<BigInt, E> {
+            Ok(BigInt::from(v))
+        }
+
+        fn visit_str<E>(self, v: &str) -> Result<BigInt, E>
+        where
+            E: de::Error,
+        {
+            check_integer(v, &self)?;
+            v.parse().map_err(de::Error::custom)
+        }
+    }
+}
+
+/// Serialize a `BigUint`.
+pub mod biguint {
+    use std::convert::TryFrom;
+    use std::fmt;
+
+    use ::num_bigint::BigUint;
+    use serde::de::{self, Deserializer, Unexpected, Visitor};
+    use serde::ser::Serializer;
+
+    use super::check_integer;
+    use crate::value::HIGH_PRECISION_TOKEN;
+
+    pub fn serialize<S>(v: &BigUint, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match u64::try_from(v) {
+            Ok(v) => serializer.serialize_u64(v),
+            Err(_) => serializer.serialize_newtype_struct(HIGH_PRECISION_TOKEN, &v.to_string()),
+        }
+    }
+
+    /// Reads any non-negative integer, including a high-precision one.
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<BigUint, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_str(BigUintVisitor)
+    }
+
+    struct BigUintVisitor;
+
+    impl<'de> Visitor<'de> for BigUintVisitor {
+        type Value = BigUint;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a non-negative integer")
+        }
+
+        fn visit_i64<E>(self, v: i64) -> Result<BigUint, E>
+        where
+            E: de::Error,
+        {
+            u64::try_from(v)
+                .map(BigUint::from)
+                .map_err(|_| E::invalid_value(Unexpected::Signed(v), &self))
+        }
+
+        fn visit_u64<E>(self, v: u64) -> Result<BigUint, E> {
+            Ok(BigUint::from(v))
+        }
+
+        fn visit_i128<E>(self, v: i128) -> Result<BigUint, E>
+        where
+            E: de::Error,
+        {
+            u128::try_from(v)
+                .map(BigUint::from)
+                .map_err(|_| E::invalid_value(Unexpected::Other("negative integer"), &self))
+        }
+
+        fn visit_u128<E>(self, v: u128) -> Result<BigUint, E> {
+            Ok(BigUint::from(v))
+        }
+
+        fn visit_str<E>(self, v: &str) -> Result<BigUint, E>
+        where
+            E: de::Error,
+        {
+            check_integer(v, &self)?;
+            if v.starts_with('-') {
+                return Err(E::invalid_value(Unexpected::Str(v), &self));
+            }
+            v.parse().map_err(de::Error::custom)
+        }
+    }
+}
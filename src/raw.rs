@@ -0,0 +1,135 @@
+//! Already-encoded UBJSON values, carried through serialization and deserialization untouched.
+//!
+//! A `RawValue` is written out byte for byte, and reading one captures the bytes of the next
+//! value without decoding it, so sub-documents can be cached or forwarded without being parsed
+//! and re-encoded.
+//!
+//! ```ignore
+//! #[derive(Serialize, Deserialize)]
+//! struct Envelope {
+//!     id: u64,
+//!     payload: RawValue,
+//! }
+//! ```
+
+use std::fmt;
+
+use serde::de::{self, Deserialize, IgnoredAny, Visitor};
+use serde::ser::{self, Serialize};
+
+use crate::de::{from_slice, Deserializer};
+use crate::error::{Error, Result};
+use crate::ser::to_vec;
+use crate::value::Value;
+
+/// Name of the newtype struct through which a `RawValue` is handed to the serializer and
+/// requested from the deserializer.
+pub(crate) const RAW_VALUE_TOKEN: &str = "$serde_ubjson::private::RawValue";
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// The encoding of exactly one UBJSON value.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RawValue {
+    bytes: Vec<u8>,
+}
+
+/// Serialize the given value into a `RawValue`.
+pub fn to_raw_value<T>(value: &T) -> Result<RawValue>
+where
+    T: Serialize,
+{
+    Ok(RawValue {
+        bytes: to_vec(value)?,
+    })
+}
+
+impl RawValue {
+    /// Wraps bytes holding an encoded value, failing if they do not hold exactly one well-formed
+    /// value. Anything after the value, even a no-op, fails with `Error::TrailingBytes`.
+    pub fn from_vec(bytes: Vec<u8>) -> Result<Self> {
+        let mut de = Deserializer::from_slice(&bytes);
+        IgnoredAny::deserialize(&mut de)?;
+        let remaining = de.remaining();
+        if remaining != 0 {
+            return Err(Error::TrailingBytes { remaining });
+        }
+        Ok(RawValue { bytes })
+    }
+
+    /// Returns the encoded value.
+    pub fn get(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Consumes the raw value and returns its bytes.
+    pub fn into_vec(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    /// Deserializes the encoded value as an instance of type `T`.
+    pub fn parse<'a, T>(&'a self) -> Result<T>
+    where
+        T: Deserialize<'a>,
+    {
+        from_slice(&self.bytes)
+    }
+}
+
+/// Handed over as a byte array, which this crate's serializer writes without its header.
+impl Serialize for RawValue {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        serializer.serialize_newtype_struct(RAW_VALUE_TOKEN, &RawBytes(&self.bytes))
+    }
+}
+
+struct RawBytes<'a>(&'a [u8]);
+
+impl<'a> Serialize for RawBytes<'a> {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+/// This crate's deserializer hands over the bytes of the next value. Any other deserializer gets
+/// the value decoded into a `Value` and encoded again.
+impl<'de> Deserialize<'de> for RawValue {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<RawValue, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_newtype_struct(RAW_VALUE_TOKEN, RawValueVisitor)
+    }
+}
+
+struct RawValueVisitor;
+
+impl<'de> Visitor<'de> for RawValueVisitor {
+    type Value = RawValue;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("any UBJSON value")
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> std::result::Result<RawValue, E> {
+        Ok(RawValue { bytes: v.to_vec() })
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> std::result::Result<RawValue, E> {
+        Ok(RawValue { bytes: v })
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> std::result::Result<RawValue, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        let value = Value::deserialize(deserializer)?;
+        to_raw_value(&value).map_err(|e: Error| de::Error::custom(e))
+    }
+}
@@ -0,0 +1,312 @@
+//! Read UBJSON as a stream of tokens, without serde.
+
+use std::convert::TryFrom;
+use std::io::{self, Read};
+
+use byteorder::{BigEndian, ByteOrder};
+use serde::de::{self, Deserialize, IgnoredAny, Unexpected};
+
+use crate::de::{fixed_value_size, Deserializer, DEFAULT_MAX_DEPTH, DEFAULT_MAX_ZERO_WIDTH_COUNT};
+use crate::error::{Error, Result};
+use crate::marker;
+use crate::token::Token;
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// Checks that `input` holds exactly one well-formed UBJSON value, optionally followed by no-ops,
+/// without building it in memory.
+///
+/// The value is walked by a `Deserializer` with its default limits, so everything it checks is
+/// checked here. Anything after the value fails with `Error::TrailingBytes`.
+pub fn validate(input: &[u8]) -> Result<()> {
+    let mut de = Deserializer::from_slice(input);
+    IgnoredAny::deserialize(&mut de)?;
+    de.end()
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// Pull parser that reads UBJSON from an IO stream one `Token` at a time, so a document of any
+/// size can be processed without holding it in memory.
+///
+/// Values at the top level are read one after another until the stream ends. No-ops are skipped.
+pub struct UbjsonReader<R> {
+    reader: R,
+    /// A byte read ahead of time to look for the end of the input or of a container.
+    peeked: Option<u8>,
+    /// Containers that have been started but not ended, innermost last.
+    stack: Vec<Frame>,
+    max_depth: usize,
+    max_zero_width_count: usize,
+}
+
+/// Where the reader is in an open container.
+struct Frame {
+    keyed: bool,
+    ty: Option<u8>,
+    /// Entries left to read, if the container is counted.
+    remaining: Option<usize>,
+    /// Whether an object expects a key next, rather than a value.
+    at_key: bool,
+}
+
+impl<R> UbjsonReader<R>
+where
+    R: Read,
+{
+    /// Creates a new reader over an IO stream.
+    pub fn new(reader: R) -> Self {
+        UbjsonReader {
+            reader,
+            peeked: None,
+            stack: Vec::new(),
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_zero_width_count: DEFAULT_MAX_ZERO_WIDTH_COUNT,
+        }
+    }
+
+    /// Sets how deeply arrays and objects may nest before reading fails with
+    /// `Error::DepthLimitExceeded`. Defaults to `DEFAULT_MAX_DEPTH`.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Sets the largest count a strongly-typed array of `Z`, `T` or `F` may declare before
+    /// reading fails with `Error::LengthLimitExceeded`, as `Deserializer::max_zero_width_count`
+    /// does. Defaults to `DEFAULT_MAX_ZERO_WIDTH_COUNT`.
+    pub fn max_zero_width_count(mut self, count: usize) -> Self {
+        self.max_zero_width_count = count;
+        self
+    }
+
+    /// Returns how many containers are currently open.
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// Consumes the reader and returns the stream it wrapped.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Reads the next token, or returns `None` if the stream ends between top-level values.
+    ///
+    /// Once this returns an error, the reader's position in the stream is unspecified.
+    pub fn next_token(&mut self) -> Result<Option<Token>> {
+        let m = match self.stack.last_mut() {
+            None => match self.peek_marker()? {
+                None => return Ok(None),
+                Some(_) => self.next_marker()?,
+            },
+            Some(frame) => {
+                let ty = frame.ty;
+                let keyed = frame.keyed;
+                let counted = frame.remaining.is_some();
+                if frame.remaining == Some(0) && (!keyed || frame.at_key) {
+                    return Ok(Some(self.end_container()));
+                }
+                if !counted && (!keyed || frame.at_key) {
+                    let end = if keyed {
+                        marker::OBJ_END
+                    } else {
+                        marker::ARR_END
+                    };
+                    if self.peek_marker()?.ok_or(Error::UnexpectedEof)? == end {
+                        self.peeked = None;
+                        return Ok(Some(self.end_container()));
+                    }
+                }
+                // Borrowed again since `peek_marker` needed all of `self`.
+                let frame = self.stack.last_mut().unwrap();
+                if keyed && frame.at_key {
+                    frame.at_key = false;
+                    if let Some(ref mut remaining) = frame.remaining {
+                        *remaining -= 1;
+                    }
+                    let key = self.read_string()?;
+                    return Ok(Some(Token::Key(key)));
+                }
+                if keyed {
+                    frame.at_key = true;
+                } else if let Some(ref mut remaining) = frame.remaining {
+                    *remaining -= 1;
+                }
+                match ty {
+                    Some(ty) => ty,
+                    None => self.next_marker()?,
+                }
+            }
+        };
+        self.read_value(m).map(Some)
+    }
+
+    /// Reads the rest of the value introduced by `m`.
+    fn read_value(&mut self, m: u8) -> Result<Token> {
+        let token = match m {
+            marker::NULL => Token::Null,
+            marker::TRUE => Token::Bool(true),
+            marker::FALSE => Token::Bool(false),
+            marker::I8 | marker::U8 | marker::I16 | marker::I32 | marker::I64 => {
+                Token::Int(self.read_integer(m)?)
+            }
+            marker::F32 => Token::Float(f64::from(BigEndian::read_f32(&self.read_array::<4>()?))),
+            marker::F64 => Token::Float(BigEndian::read_f64(&self.read_array::<8>()?)),
+            marker::HI_PRECISION => Token::HighPrecision(self.read_string()?),
+            marker::CHAR => match self.read_byte()? {
+                c if c <= 127 => Token::Char(char::from(c)),
+                _ => return Err(Error::InvalidChar),
+            },
+            marker::STRING => Token::Str(self.read_string()?),
+            marker::ARR_START | marker::OBJ_START => {
+                if self.stack.len() >= self.max_depth {
+                    return Err(Error::DepthLimitExceeded);
+                }
+                let keyed = m == marker::OBJ_START;
+                let (ty, len) = self.read_container_header()?;
+                // Elements of `Z`, `T` or `F` take up no input, so their count alone bounds how
+                // many tokens follow.
+                if let (false, Some(0), Some(len)) = (keyed, ty.and_then(fixed_value_size), len) {
+                    if len > self.max_zero_width_count {
+                        return Err(Error::LengthLimitExceeded);
+                    }
+                }
+                self.stack.push(Frame {
+                    keyed,
+                    ty,
+                    remaining: len,
+                    at_key: true,
+                });
+                if keyed {
+                    Token::ObjectStart { len, ty }
+                } else {
+                    Token::ArrayStart { len, ty }
+                }
+            }
+            _ => return Err(Error::InvalidMarker(m)),
+        };
+        Ok(token)
+    }
+
+    /// Closes the innermost container, whose end has been reached.
+    fn end_container(&mut self) -> Token {
+        match self.stack.pop() {
+            Some(Frame { keyed: true, .. }) => Token::ObjectEnd,
+            _ => Token::ArrayEnd,
+        }
+    }
+
+    /// Reads the optional `$type` and `#count` that follow a container's opening marker.
+    fn read_container_header(&mut self) -> Result<(Option<u8>, Option<usize>)> {
+        let ty = if self.peek_byte()? == Some(marker::TYPE) {
+            self.peeked = None;
+            match self.read_byte()? {
+                m @ marker::NOOP
+                | m @ marker::ARR_END
+                | m @ marker::OBJ_END
+                | m @ marker::TYPE
+                | m @ marker::LENGTH => return Err(Error::InvalidMarker(m)),
+                m => Some(m),
+            }
+        } else {
+            None
+        };
+        match self.peek_byte()? {
+            Some(marker::LENGTH) => {
+                self.peeked = None;
+                Ok((ty, Some(self.read_length()?)))
+            }
+            Some(m) if ty.is_some() => Err(Error::InvalidMarker(m)),
+            None if ty.is_some() => Err(Error::UnexpectedEof),
+            _ => Ok((ty, None)),
+        }
+    }
+
+    fn read_integer(&mut self, m: u8) -> Result<i64> {
+        match m {
+            marker::I8 => Ok(i64::from(self.read_byte()? as i8)),
+            marker::U8 => Ok(i64::from(self.read_byte()?)),
+            marker::I16 => Ok(i64::from(BigEndian::read_i16(&self.read_array::<2>()?))),
+            marker::I32 => Ok(i64::from(BigEndian::read_i32(&self.read_array::<4>()?))),
+            marker::I64 => Ok(BigEndian::read_i64(&self.read_array::<8>()?)),
+            _ => Err(Error::InvalidMarker(m)),
+        }
+    }
+
+    fn read_length(&mut self) -> Result<usize> {
+        let m = self.read_byte()?;
+        let len = self.read_integer(m)?;
+        if len < 0 {
+            return Err(de::Error::invalid_value(
+                Unexpected::Signed(len),
+                &"a non-negative length",
+            ));
+        }
+        usize::try_from(len).map_err(|_| Error::LengthOverflow)
+    }
+
+    /// Reads a length-prefixed string whose marker (if any) has already been consumed.
+    fn read_string(&mut self) -> Result<String> {
+        let len = self.read_length()?;
+        // Read through `take` so that a bogus length cannot make us allocate it all up front.
+        let mut bytes = Vec::new();
+        let read = (&mut self.reader)
+            .take(len as u64)
+            .read_to_end(&mut bytes)?;
+        if read < len {
+            return Err(Error::UnexpectedEof);
+        }
+        String::from_utf8(bytes).map_err(|_| Error::InvalidUtf8)
+    }
+
+    /// Returns the marker of the next value without consuming it, skipping no-ops, or `None` at
+    /// the end of the stream.
+    fn peek_marker(&mut self) -> Result<Option<u8>> {
+        loop {
+            match self.peek_byte()? {
+                Some(marker::NOOP) => self.peeked = None,
+                other => return Ok(other),
+            }
+        }
+    }
+
+    /// Consumes and returns the marker of the next value, skipping no-ops.
+    fn next_marker(&mut self) -> Result<u8> {
+        loop {
+            let m = self.read_byte()?;
+            if m != marker::NOOP {
+                return Ok(m);
+            }
+        }
+    }
+
+    fn peek_byte(&mut self) -> Result<Option<u8>> {
+        if self.peeked.is_none() {
+            let mut buf = [0];
+            loop {
+                match self.reader.read(&mut buf) {
+                    Ok(0) => return Ok(None),
+                    Ok(_) => break,
+                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    Err(e) => return Err(Error::Io(e)),
+                }
+            }
+            self.peeked = Some(buf[0]);
+        }
+        Ok(self.peeked)
+    }
+
+    fn read_byte(&mut self) -> Result<u8> {
+        self.peek_byte()?.ok_or(Error::UnexpectedEof)?;
+        Ok(self.peeked.take().unwrap_or_default())
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let mut buf = [0; N];
+        match self.reader.read_exact(&mut buf) {
+            Ok(()) => Ok(buf),
+            Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => Err(Error::UnexpectedEof),
+            Err(e) => Err(Error::Io(e)),
+        }
+    }
+}
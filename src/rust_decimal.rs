@@ -0,0 +1,72 @@
+//! Helpers for serializing a `rust_decimal::Decimal` as a high-precision number, for use with
+//! `#[serde(with = "serde_ubjson::rust_decimal")]`.
+//!
+//! The decimal is written as `H` followed by its decimal string, scale included, so `1.50` reads
+//! back as `1.50` rather than going through a float and losing digits.
+//!
+//! ```ignore
+//! #[derive(Serialize, Deserialize)]
+//! struct Trade {
+//!     #[serde(with = "serde_ubjson::rust_decimal")]
+//!     price: Decimal,
+//! }
+//! ```
+
+use std::convert::TryFrom;
+use std::fmt;
+
+use ::rust_decimal::Decimal;
+use serde::de::{self, Deserializer, Visitor};
+use serde::ser::Serializer;
+
+use crate::value::HIGH_PRECISION_TOKEN;
+
+pub fn serialize<S>(v: &Decimal, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_newtype_struct(HIGH_PRECISION_TOKEN, &v.to_string())
+}
+
+/// Reads a high-precision number in plain or scientific notation. Integers are read exactly, and
+/// floats as the decimal nearest to them.
+pub fn deserialize<'de, D>(deserializer: D) -> Result<Decimal, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_str(DecimalVisitor)
+}
+
+struct DecimalVisitor;
+
+impl<'de> Visitor<'de> for DecimalVisitor {
+    type Value = Decimal;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a decimal number")
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Decimal, E> {
+        Ok(Decimal::from(v))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Decimal, E> {
+        Ok(Decimal::from(v))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Decimal, E>
+    where
+        E: de::Error,
+    {
+        Decimal::try_from(v).map_err(|_| E::invalid_value(de::Unexpected::Float(v), &self))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Decimal, E>
+    where
+        E: de::Error,
+    {
+        v.parse()
+            .or_else(|_| Decimal::from_scientific(v))
+            .map_err(|_| E::invalid_value(de::Unexpected::Str(v), &self))
+    }
+}
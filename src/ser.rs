@@ -1,16 +1,25 @@
 //! Serialize a Rust data structure into UBJSON data.
 
-use std::io::Write;
+use std::cmp;
+use std::fmt::Display;
+use std::io::{self, Read, Write};
+use std::mem;
 
-use byteorder::{BigEndian, WriteBytesExt};
+use byteorder::{BigEndian, ByteOrder, WriteBytesExt};
 use serde::ser::{self, Impossible, Serialize};
 
-use crate::error::{Error, Result};
+use crate::error::{Error, OutputLimit, Result};
 use crate::marker;
+use crate::raw::RAW_VALUE_TOKEN;
+use crate::value::HIGH_PRECISION_TOKEN;
 
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 
 /// Serialize the given value as a UBJSON byte vector.
+///
+/// Writing to a `Vec` cannot fail, so this never returns `Error::Io`; the only errors come from
+/// the value itself, such as a map key that is not a string. This makes it a good fit where IO
+/// errors have no meaning, such as in WebAssembly.
 pub fn to_vec<T>(value: &T) -> Result<Vec<u8>>
 where
     T: Serialize,
@@ -20,6 +29,46 @@ where
     Ok(serializer.into_inner())
 }
 
+/// Serialize the given value as a UBJSON byte vector, starting from a buffer that can hold `cap`
+/// bytes without reallocating. Like `to_vec`, this never returns `Error::Io`.
+pub fn to_vec_with_capacity<T>(value: &T, cap: usize) -> Result<Vec<u8>>
+where
+    T: Serialize,
+{
+    let mut serializer = Serializer::new(Vec::with_capacity(cap));
+    value.serialize(&mut serializer)?;
+    Ok(serializer.into_inner())
+}
+
+/// Serialize the given value as UBJSON, appending it to the end of `buf`.
+///
+/// Nothing already in `buf` is touched, so the same buffer can be reused across calls to avoid
+/// allocating; call `buf.clear()` first to start over. If serialization fails, `buf` may be left
+/// holding part of the value. Like `to_vec`, this never returns `Error::Io`.
+pub fn to_buf<T>(value: &T, buf: &mut Vec<u8>) -> Result<()>
+where
+    T: Serialize,
+{
+    value.serialize(&mut Serializer::new(buf))
+}
+
+/// Serialize the given value as UBJSON into the start of `buf`, returning how many bytes were
+/// written.
+///
+/// Fails with `Error::BufferFull` if the value does not fit, in which case `buf` holds as much of
+/// it as did.
+pub fn to_slice<T>(value: &T, buf: &mut [u8]) -> Result<usize>
+where
+    T: Serialize,
+{
+    let mut serializer = Serializer::new(io::Cursor::new(buf));
+    match value.serialize(&mut serializer) {
+        Ok(()) => Ok(serializer.into_inner().position() as usize),
+        Err(Error::Io(ref e)) if e.kind() == io::ErrorKind::WriteZero => Err(Error::BufferFull),
+        Err(e) => Err(e),
+    }
+}
+
 /// Serialize the given value as UBJSON into the IO stream.
 pub fn to_writer<T, W>(writer: W, value: &T) -> Result<()>
 where
@@ -31,11 +80,383 @@ where
     Ok(())
 }
 
+/// Serialize the given value as UBJSON into the IO stream, preceded by its length in bytes as a
+/// big-endian `u32`, so that messages written one after another can be told apart.
+///
+/// The value is serialized into a buffer first, and nothing is written if that fails. Fails if
+/// the value takes more than `u32::MAX` bytes.
+pub fn to_writer_framed<T, W>(mut writer: W, value: &T) -> Result<()>
+where
+    W: Write,
+    T: Serialize,
+{
+    let payload = to_vec(value)?;
+    if payload.len() > u32::MAX as usize {
+        return Err(ser::Error::custom(
+            "framed message is longer than u32::MAX bytes",
+        ));
+    }
+    writer.write_u32::<BigEndian>(payload.len() as u32)?;
+    writer.write_all(&payload)?;
+    Ok(())
+}
+
+/// Write a signed integer using the smallest marker that can hold it, following the same rules
+/// as the serializer.
+pub fn encode_integer<W>(writer: &mut W, v: i64) -> Result<()>
+where
+    W: ?Sized + Write,
+{
+    ser::Serializer::serialize_i64(&mut Serializer::new(writer), v)
+}
+
+/// Write an unsigned integer using the smallest marker that can hold it, following the same rules
+/// as the serializer. Values beyond `i64::MAX` become high-precision numbers.
+pub fn encode_unsigned_integer<W>(writer: &mut W, v: u64) -> Result<()>
+where
+    W: ?Sized + Write,
+{
+    ser::Serializer::serialize_u64(&mut Serializer::new(writer), v)
+}
+
+/// Write a null.
+pub fn encode_null<W>(writer: &mut W) -> Result<()>
+where
+    W: ?Sized + Write,
+{
+    ser::Serializer::serialize_none(&mut Serializer::new(writer))
+}
+
+/// Write a boolean as `T` or `F`.
+pub fn encode_bool<W>(writer: &mut W, v: bool) -> Result<()>
+where
+    W: ?Sized + Write,
+{
+    ser::Serializer::serialize_bool(&mut Serializer::new(writer), v)
+}
+
+/// Write a float32.
+pub fn encode_f32<W>(writer: &mut W, v: f32) -> Result<()>
+where
+    W: ?Sized + Write,
+{
+    ser::Serializer::serialize_f32(&mut Serializer::new(writer), v)
+}
+
+/// Write a float64.
+pub fn encode_f64<W>(writer: &mut W, v: f64) -> Result<()>
+where
+    W: ?Sized + Write,
+{
+    ser::Serializer::serialize_f64(&mut Serializer::new(writer), v)
+}
+
+/// Write a string, marker and length included.
+pub fn encode_str<W>(writer: &mut W, v: &str) -> Result<()>
+where
+    W: ?Sized + Write,
+{
+    ser::Serializer::serialize_str(&mut Serializer::new(writer), v)
+}
+
+/// Write an object key, which is a string without the leading `S` marker.
+pub fn encode_key<W>(writer: &mut W, key: &str) -> Result<()>
+where
+    W: ?Sized + Write,
+{
+    Serializer::new(writer).write_key(key)
+}
+
+/// Compute how many bytes `to_vec` would produce for the given value, without writing them.
+pub fn serialized_size<T>(value: &T) -> Result<usize>
+where
+    T: Serialize,
+{
+    let mut serializer = Serializer::new(io::sink());
+    value.serialize(&mut serializer)?;
+    Ok(serializer.bytes_written() as usize)
+}
+
+/// Writer that passes everything on to the one it wraps, keeping a count of the bytes.
+#[derive(Clone)]
+struct CountingWriter<W> {
+    writer: W,
+    count: u64,
+    /// How many bytes may be written in all. A write that would go past this fails with
+    /// `OutputLimit` and writes nothing.
+    limit: u64,
+}
+
+impl<W> CountingWriter<W> {
+    fn new(writer: W) -> Self {
+        CountingWriter::with_limit(writer, u64::MAX)
+    }
+
+    fn with_limit(writer: W, limit: u64) -> Self {
+        CountingWriter {
+            writer,
+            count: 0,
+            limit,
+        }
+    }
+
+    /// Returns how many more bytes may be written.
+    fn remaining(&self) -> u64 {
+        self.limit.saturating_sub(self.count)
+    }
+}
+
+impl<W> Write for CountingWriter<W>
+where
+    W: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.len() as u64 > self.remaining() {
+            return Err(io::Error::other(OutputLimit));
+        }
+        let n = self.writer.write(buf)?;
+        self.count += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 
+/// How NaN and infinite floats are serialized.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NonFiniteFloat {
+    /// Write the float's bit pattern like any other. This is the default.
+    Raw,
+    /// Write a null in place of the float.
+    Null,
+    /// Fail with `Error::NonFiniteFloat`.
+    Error,
+}
+
+/// How enum variants are identified in the output.
+///
+/// A unit variant is written as its bare tag. Every other variant is written as a counted array
+/// holding the tag followed by the variant's fields, so `E::Newtype(x)` becomes `[#2 tag x]` and
+/// `E::Tuple(x, y)` becomes `[#3 tag x y]`. Struct variants under `StructRepresentation::Object`
+/// become `[#2 tag {fields}]`.
+///
+/// No discriminator marks an array as a variant; a newtype variant is byte-for-byte the same as
+/// a 2-tuple holding the tag and the value. Decoding is driven by the target type, which already
+/// says whether an enum is expected, so the layout is never ambiguous to `deserialize_enum`. It
+/// does mean that untyped readers, such as `Value`, see variants as plain arrays.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EnumRepresentation {
+    /// Write the variant's index as an integer. This is the default.
+    Index,
+    /// Write the variant's name as a string.
+    Name,
+}
+
+/// How structs are laid out in the output.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StructRepresentation {
+    /// Write fields positionally in an array, dropping their names. This is the default.
+    ///
+    /// Fields left out by `#[serde(skip_serializing_if)]` shift every later field down a
+    /// position, so such structs should use `Object` if they are to be read back.
+    Array,
+    /// Write fields in an object keyed by their names.
+    ///
+    /// Keys are written in the order the struct declares its fields, even with `sort_map_keys`
+    /// set, so a struct's layout stays the same from one run to the next.
+    ///
+    /// Skipped fields are simply absent from the object. Structs with `#[serde(flatten)]` fields
+    /// are written by serde as a single unsized object with the flattened keys merged in, in
+    /// either representation.
+    Object,
+}
+
+/// Which integer type the count of a counted container is written as, the `n` in `#n`.
+///
+/// This covers arrays, objects and byte arrays. String and key lengths are always written with
+/// the smallest marker that holds them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CountEncoding {
+    /// Use the smallest marker that can hold the count, as for any other integer. This is the
+    /// default.
+    Narrow,
+    /// Always use `I`.
+    FixedI16,
+    /// Always use `l`.
+    FixedI32,
+    /// Always use `L`.
+    FixedI64,
+}
+
+impl CountEncoding {
+    /// Returns the marker every count is written with, or `None` for `Narrow`.
+    fn fixed_marker(self) -> Option<u8> {
+        match self {
+            CountEncoding::Narrow => None,
+            CountEncoding::FixedI16 => Some(marker::I16),
+            CountEncoding::FixedI32 => Some(marker::I32),
+            CountEncoding::FixedI64 => Some(marker::I64),
+        }
+    }
+}
+
+/// Element type of an array written with `Serializer::serialize_typed_array`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TypeTag {
+    /// `i`.
+    I8,
+    /// `U`.
+    U8,
+    /// `I`.
+    I16,
+    /// `l`.
+    I32,
+    /// `L`.
+    I64,
+    /// `d`.
+    F32,
+    /// `D`.
+    F64,
+}
+
+impl TypeTag {
+    /// Returns the marker written after the array's `$`.
+    pub fn marker(self) -> u8 {
+        match self {
+            TypeTag::I8 => marker::I8,
+            TypeTag::U8 => marker::U8,
+            TypeTag::I16 => marker::I16,
+            TypeTag::I32 => marker::I32,
+            TypeTag::I64 => marker::I64,
+            TypeTag::F32 => marker::F32,
+            TypeTag::F64 => marker::F64,
+        }
+    }
+}
+
+/// A number that can be an element of an array written with `Serializer::serialize_typed_array`.
+///
+/// Integers can go in an array of any integer type that holds them, and floats in a float array,
+/// which must be `TypeTag::F64` for an `f64` unless it converts to `f32` exactly.
+pub trait TypedElement: Copy {
+    /// Writes the value's payload, without a marker, as a value of type `ty`.
+    fn write_as<W: ?Sized + Write>(self, ty: TypeTag, writer: &mut W) -> Result<()>;
+}
+
+macro_rules! impl_typed_element {
+    ($($ty:ty => $write:ident,)*) => {
+        $(
+            impl TypedElement for $ty {
+                fn write_as<W: ?Sized + Write>(self, ty: TypeTag, writer: &mut W) -> Result<()> {
+                    $write(self.into(), ty, writer)
+                }
+            }
+        )*
+    };
+}
+
+impl_typed_element! {
+    i8 => write_typed_integer,
+    u8 => write_typed_integer,
+    i16 => write_typed_integer,
+    u16 => write_typed_integer,
+    i32 => write_typed_integer,
+    u32 => write_typed_integer,
+    i64 => write_typed_integer,
+    f32 => write_typed_float,
+    f64 => write_typed_float,
+}
+
+fn write_typed_integer<W: ?Sized + Write>(v: i64, ty: TypeTag, writer: &mut W) -> Result<()> {
+    if !integer_fits(v, ty.marker()) {
+        return Err(ser::Error::custom(format_args!(
+            "{} does not fit in a typed array of {:?}",
+            v, ty
+        )));
+    }
+    match ty {
+        TypeTag::I8 => writer.write_i8(v as i8)?,
+        TypeTag::U8 => writer.write_u8(v as u8)?,
+        TypeTag::I16 => writer.write_i16::<BigEndian>(v as i16)?,
+        TypeTag::I32 => writer.write_i32::<BigEndian>(v as i32)?,
+        _ => writer.write_i64::<BigEndian>(v)?,
+    }
+    Ok(())
+}
+
+fn write_typed_float<W: ?Sized + Write>(v: f64, ty: TypeTag, writer: &mut W) -> Result<()> {
+    match ty {
+        TypeTag::F64 => writer.write_f64::<BigEndian>(v)?,
+        TypeTag::F32 if f64::from(v as f32) == v || v.is_nan() => {
+            writer.write_f32::<BigEndian>(v as f32)?
+        }
+        _ => {
+            return Err(ser::Error::custom(format_args!(
+                "{} does not fit in a typed array of {:?}",
+                v, ty
+            )))
+        }
+    }
+    Ok(())
+}
+
+/// Options shared by a serializer and everything it spawns.
+#[derive(Clone, Copy, Debug)]
+struct Config {
+    non_finite_float: NonFiniteFloat,
+    compact_floats: bool,
+    normalize_negative_zero: bool,
+    enum_representation: EnumRepresentation,
+    struct_representation: StructRepresentation,
+    sort_map_keys: bool,
+    always_unsized_containers: bool,
+    stringify_non_string_keys: bool,
+    human_readable: bool,
+    optimize_unsized_seqs: bool,
+    optimize_seqs: bool,
+    optimize_maps: bool,
+    unit_as_empty_array: bool,
+    faithful_options: bool,
+    count_encoding: CountEncoding,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            non_finite_float: NonFiniteFloat::Raw,
+            compact_floats: false,
+            normalize_negative_zero: false,
+            enum_representation: EnumRepresentation::Index,
+            struct_representation: StructRepresentation::Array,
+            sort_map_keys: false,
+            always_unsized_containers: false,
+            stringify_non_string_keys: false,
+            human_readable: false,
+            optimize_unsized_seqs: false,
+            optimize_seqs: false,
+            optimize_maps: false,
+            unit_as_empty_array: false,
+            faithful_options: false,
+            count_encoding: CountEncoding::Narrow,
+        }
+    }
+}
+
 /// Structure for serializing Rust values into UBJSON.
+///
+/// It can be cloned when its writer can, such as to serialize a shared prefix into a `Vec<u8>`
+/// once and then carry on separately from each clone.
+#[derive(Clone)]
 pub struct Serializer<W> {
-    inner: W,
+    inner: CountingWriter<W>,
+    config: Config,
+    /// Where the value of the innermost `Some` began, to tell whether that value is itself an
+    /// option. Only kept with `faithful_options`.
+    some_start: Option<u64>,
 }
 
 impl<W> Serializer<W>
@@ -44,12 +465,339 @@ where
 {
     /// Creates a new UBJSON serializer.
     pub fn new(writer: W) -> Self {
-        Serializer { inner: writer }
+        Serializer {
+            inner: CountingWriter::new(writer),
+            config: Config::default(),
+            some_start: None,
+        }
+    }
+
+    /// Sets how NaN and infinite floats are serialized. Defaults to `NonFiniteFloat::Raw`.
+    pub fn non_finite_float(mut self, mode: NonFiniteFloat) -> Self {
+        self.config.non_finite_float = mode;
+        self
+    }
+
+    /// Sets whether an `f64` that converts to `f32` and back without loss is written as a float32
+    /// instead of a float64, much like integers are narrowed. Off by default.
+    pub fn compact_floats(mut self, enabled: bool) -> Self {
+        self.config.compact_floats = enabled;
+        self
+    }
+
+    /// Sets whether `-0.0` is written with the bits of `0.0`, so that equal floats always encode
+    /// to equal bytes. Off by default, which keeps the sign.
+    pub fn normalize_negative_zero(mut self, enabled: bool) -> Self {
+        self.config.normalize_negative_zero = enabled;
+        self
+    }
+
+    /// Sets how enum variants are identified. Defaults to `EnumRepresentation::Index`.
+    pub fn enum_representation(mut self, repr: EnumRepresentation) -> Self {
+        self.config.enum_representation = repr;
+        self
+    }
+
+    /// Sets how structs and struct variants are laid out. Defaults to
+    /// `StructRepresentation::Array`.
+    pub fn struct_representation(mut self, repr: StructRepresentation) -> Self {
+        self.config.struct_representation = repr;
+        self
+    }
+
+    /// Sets whether map entries are written sorted by the UTF-8 bytes of their keys, making the
+    /// output independent of iteration order, at the cost of buffering each map in memory. Struct
+    /// fields are unaffected. Off by default.
+    ///
+    /// A `BTreeMap` with string keys already iterates in this order, so it gives the same
+    /// deterministic output with or without this option, and leaving it off avoids the buffering.
+    /// With it on, the serializer cannot tell a `BTreeMap` from any other map and still buffers
+    /// it, though sorting entries that arrive in order takes only a single pass. Maps that keep
+    /// the order they were read in, such as `IndexMap`, are written in their own order only with
+    /// this off.
+    pub fn sort_map_keys(mut self, enabled: bool) -> Self {
+        self.config.sort_map_keys = enabled;
+        self
+    }
+
+    /// Sets whether every array and object is written in the unsized form, closed by `]` or `}`,
+    /// even when its length is known up front. Byte arrays are still written as counted `U`
+    /// arrays, since a strongly-typed container must have a count. Off by default.
+    pub fn always_unsized_containers(mut self, enabled: bool) -> Self {
+        self.config.always_unsized_containers = enabled;
+        self
+    }
+
+    /// Sets whether integer, bool and char map keys are written as strings, such as `"42"` or
+    /// `"true"`, instead of failing with `Error::KeyMustBeAString`. Off by default.
+    pub fn stringify_non_string_keys(mut self, enabled: bool) -> Self {
+        self.config.stringify_non_string_keys = enabled;
+        self
+    }
+
+    /// Sets what `is_human_readable` reports to the types being serialized, letting types such
+    /// as `Uuid` choose between a readable string and a compact binary form. Off by default.
+    ///
+    /// Adapters such as `serde_with::IfIsHumanReadable` follow this setting too, while
+    /// `serde_with::DisplayFromStr` always writes a string.
+    pub fn human_readable(mut self, enabled: bool) -> Self {
+        self.config.human_readable = enabled;
+        self
+    }
+
+    /// Sets whether the elements of a sequence of unknown length are held back until its end, so
+    /// that it can be written as a strongly-typed container, `[$` with the shared marker and a
+    /// count, whenever that comes out smaller. Integers of different widths count as sharing a
+    /// type, and are all written at the width the largest of them needs. A sequence of units,
+    /// nulls, or bools that are all equal becomes just a count, such as `[$T#<count>`.
+    ///
+    /// Only the first `OPTIMIZE_BUFFER_LIMIT` bytes of elements are held back; a longer sequence
+    /// is written unsized as usual. Has no effect with `always_unsized_containers`. Off by
+    /// default.
+    pub fn optimize_unsized_seqs(mut self, enabled: bool) -> Self {
+        self.config.optimize_unsized_seqs = enabled;
+        self
+    }
+
+    /// Sets whether every sequence, including those of known length, gets the treatment described
+    /// under `optimize_unsized_seqs`. This lets a `Vec<char>` of ASCII characters become a `C`
+    /// array, for example, or a `Vec<String>` an `S` array of bare length-prefixed strings. Off by
+    /// default.
+    pub fn optimize_seqs(mut self, enabled: bool) -> Self {
+        self.config.optimize_seqs = enabled;
+        self
+    }
+
+    /// Sets whether the entries of a map are held back until its end, so that it can be written
+    /// as a strongly-typed object, `{$` with the marker its values share and a count, whenever
+    /// that comes out smaller. Values are matched up as under `optimize_seqs`; keys are written
+    /// as usual.
+    ///
+    /// UBJSON has no way to share keys between objects, so every key costs its length and bytes
+    /// each time it is written, and an array of maps with the same keys repeats them all. Structs
+    /// do not need to pay this: `StructRepresentation::Array`, the default, leaves field names out
+    /// altogether.
+    ///
+    /// Only the first `OPTIMIZE_BUFFER_LIMIT` bytes of entries are held back, unless
+    /// `sort_map_keys` holds the whole map anyway. Has no effect with `always_unsized_containers`.
+    /// Off by default.
+    pub fn optimize_maps(mut self, enabled: bool) -> Self {
+        self.config.optimize_maps = enabled;
+        self
+    }
+
+    /// Sets whether `()` and unit structs are written as an empty array `[]` rather than as a null,
+    /// which keeps them apart from `None`. The deserializer accepts either form for unit types.
+    /// Off by default.
+    pub fn unit_as_empty_array(mut self, enabled: bool) -> Self {
+        self.config.unit_as_empty_array = enabled;
+        self
+    }
+
+    /// Sets whether an option that is the value of a `Some` is wrapped in a single-element array,
+    /// so that `Some(None)` stays distinct from `None`, as in an `Option<Option<T>>`. The
+    /// deserializer must be given the same setting. Off by default.
+    pub fn faithful_options(mut self, enabled: bool) -> Self {
+        self.config.faithful_options = enabled;
+        self
+    }
+
+    /// Sets which integer type container counts are written as, for readers that only accept
+    /// one. With a fixed type, a count too large for it fails serialization. Defaults to
+    /// `CountEncoding::Narrow`.
+    pub fn count_encoding(mut self, encoding: CountEncoding) -> Self {
+        self.config.count_encoding = encoding;
+        self
+    }
+
+    /// Sets how many bytes may be written in all, counting every value serialized with this
+    /// serializer, before serialization fails with `Error::OutputLimitExceeded`. A write that
+    /// would cross the limit is not made, so the output stops short of it. Unlimited by default.
+    ///
+    /// Bytes held back in memory, such as maps buffered by `sort_map_keys`, count against the
+    /// limit as well, so it also bounds how much memory they take.
+    pub fn max_output_bytes(mut self, limit: u64) -> Self {
+        self.inner.limit = limit;
+        self
+    }
+
+    /// Writes a single marker byte, such as `marker::NOOP` or part of a container header.
+    ///
+    /// Nothing is checked, so it is up to the caller to keep the output well-formed.
+    pub fn write_raw_marker(&mut self, m: u8) -> Result<()> {
+        self.inner.write_u8(m)?;
+        Ok(())
+    }
+
+    /// Writes `bytes` as they are, taking them to be one complete, already-encoded UBJSON value,
+    /// such as a cached result of `to_vec`.
+    ///
+    /// This is unchecked: nothing verifies that `bytes` hold exactly one well-formed value, and if
+    /// they do not, the rest of the output will be misread.
+    pub fn write_fragment(&mut self, bytes: &[u8]) -> Result<()> {
+        self.inner.write_all(bytes)?;
+        Ok(())
+    }
+
+    /// Returns how many bytes have been written so far, counting every value serialized with this
+    /// serializer.
+    pub fn bytes_written(&self) -> u64 {
+        self.inner.count
+    }
+
+    /// Swaps in a new writer so the serializer can be reused for another document, keeping its
+    /// options, and returns the old writer. `bytes_written` starts over from zero, and with it
+    /// the count against `max_output_bytes`.
+    pub fn reset(&mut self, writer: W) -> W {
+        self.some_start = None;
+        let inner = CountingWriter::with_limit(writer, self.inner.limit);
+        mem::replace(&mut self.inner, inner).writer
     }
 
     /// Consumes the serializer and returns the writer it wrapped.
     fn into_inner(self) -> W {
-        self.inner
+        self.inner.writer
+    }
+
+    /// Serializes `len` bytes copied from `reader` as a byte array, the same way
+    /// `serialize_bytes` would, without holding them all in memory at once.
+    ///
+    /// Fails with `Error::UnexpectedEof` if `reader` runs out before `len` bytes were copied, in
+    /// which case the output has been left incomplete.
+    pub fn serialize_byte_stream<R>(&mut self, len: u64, reader: R) -> Result<()>
+    where
+        R: Read,
+    {
+        let header = [marker::ARR_START, marker::TYPE, marker::U8, marker::LENGTH];
+        write_count(&mut self.inner, &header, len, self.config.count_encoding)?;
+        let copied = io::copy(&mut reader.take(len), &mut self.inner)?;
+        if copied < len {
+            return Err(Error::UnexpectedEof);
+        }
+        Ok(())
+    }
+
+    /// Writes `items` as a strongly-typed array of `ty`, such as `[$d#<count>` followed by the raw
+    /// payloads, without collecting them first. The count is always written, whatever
+    /// `always_unsized_containers` says.
+    ///
+    /// Fails if an item cannot be written as `ty`, in which case the output has been left
+    /// incomplete, or with `Error::LengthMismatch` if the iterator yields a different number of
+    /// items than its length said. Items beyond that length are not written, and no more than one
+    /// of them is taken from the iterator, so `found` is then one more than the length.
+    pub fn serialize_typed_array<I, T>(&mut self, ty: TypeTag, items: I) -> Result<()>
+    where
+        I: IntoIterator<Item = T>,
+        I::IntoIter: ExactSizeIterator,
+        T: TypedElement,
+    {
+        let mut items = items.into_iter();
+        let len = items.len();
+        let header = [marker::ARR_START, marker::TYPE, ty.marker(), marker::LENGTH];
+        write_count(
+            &mut self.inner,
+            &header,
+            len as u64,
+            self.config.count_encoding,
+        )?;
+        let mut written = 0;
+        for item in items.by_ref().take(len) {
+            item.write_as(ty, &mut self.inner)?;
+            written += 1;
+        }
+        // Only one item past the length is asked for, since the rest may never end.
+        let found = if written < len {
+            written
+        } else if items.next().is_some() {
+            len + 1
+        } else {
+            return Ok(());
+        };
+        Err(Error::LengthMismatch {
+            expected: len,
+            found,
+        })
+    }
+
+    /// Creates a serializer with the same options that writes into a fresh buffer. `held` is how
+    /// many bytes are already being held back to be written later, which the buffer must leave
+    /// room for under `max_output_bytes`.
+    fn fork(&self, held: usize) -> Serializer<Vec<u8>> {
+        let limit = self.inner.remaining().saturating_sub(held as u64);
+        Serializer {
+            inner: CountingWriter::with_limit(Vec::new(), limit),
+            config: self.config,
+            some_start: None,
+        }
+    }
+
+    /// Serializes a NaN or infinite float according to `Config::non_finite_float`, which must not
+    /// be `Raw`.
+    fn serialize_non_finite(&mut self) -> Result<()> {
+        match self.config.non_finite_float {
+            NonFiniteFloat::Null => ser::Serializer::serialize_none(self),
+            _ => Err(Error::NonFiniteFloat),
+        }
+    }
+
+    /// Serializes whatever identifies a variant under `Config::enum_representation`.
+    fn serialize_variant_tag(&mut self, variant_index: u32, variant: &'static str) -> Result<()> {
+        match self.config.enum_representation {
+            EnumRepresentation::Index => ser::Serializer::serialize_u32(self, variant_index),
+            EnumRepresentation::Name => ser::Serializer::serialize_str(self, variant),
+        }
+    }
+
+    /// Writes the start of a container, followed by its count unless the count is unknown or
+    /// `always_unsized_containers` is set. Returns whether the count was written.
+    fn write_container_header(&mut self, start: u8, len: Option<usize>) -> Result<bool> {
+        match len {
+            Some(len) if !self.config.always_unsized_containers => {
+                let header = [start, marker::LENGTH];
+                write_count(
+                    &mut self.inner,
+                    &header,
+                    len as u64,
+                    self.config.count_encoding,
+                )?;
+                Ok(true)
+            }
+            _ => {
+                self.inner.write_u8(start)?;
+                Ok(false)
+            }
+        }
+    }
+
+    /// Whether the option about to be written is the value of a `Some`, and so must be wrapped
+    /// under `faithful_options`. Since every value writes something, nothing has been written
+    /// since the `Some` began exactly when this is the case.
+    fn in_some(&self) -> bool {
+        self.config.faithful_options && self.some_start == Some(self.inner.count)
+    }
+
+    /// Writes an object key, which is a string without the leading `S` marker.
+    fn write_key(&mut self, key: &str) -> Result<()> {
+        write_header_with_length(&mut self.inner, &[], key.len() as u64)?;
+        self.inner.write_all(key.as_bytes())?;
+        Ok(())
+    }
+
+    /// Writes a marker and its fixed-size payload of at most 8 bytes with a single call to the
+    /// writer, rather than one call for each.
+    fn write_marked(&mut self, m: u8, payload: &[u8]) -> Result<()> {
+        let mut buf = [0; 9];
+        buf[0] = m;
+        buf[1..=payload.len()].copy_from_slice(payload);
+        self.inner.write_all(&buf[..=payload.len()])?;
+        Ok(())
+    }
+
+    /// Writes a high-precision number given its decimal representation.
+    fn write_hi_precision(&mut self, v: &str) -> Result<()> {
+        write_header_with_length(&mut self.inner, &[marker::HI_PRECISION], v.len() as u64)?;
+        self.inner.write_all(v.as_bytes())?;
+        Ok(())
     }
 }
 
@@ -65,60 +813,54 @@ impl<'a, W: Write> ser::Serializer for &'a mut Serializer<W> {
     type SerializeStruct = Static<'a, W>;
     type SerializeStructVariant = Static<'a, W>;
 
+    fn is_human_readable(&self) -> bool {
+        self.config.human_readable
+    }
+
     fn serialize_bool(self, v: bool) -> Result<()> {
         self.inner
-            .write_u8(if v { marker::TRUE } else { marker::FALSE })
-            .map_err(Error::Io)
+            .write_u8(if v { marker::TRUE } else { marker::FALSE })?;
+        Ok(())
     }
 
     fn serialize_i8(self, v: i8) -> Result<()> {
-        self.inner.write_u8(marker::I8)?;
-        self.inner.write_i8(v)?;
-        Ok(())
+        self.write_marked(marker::I8, &v.to_be_bytes())
     }
 
     fn serialize_i16(self, v: i16) -> Result<()> {
-        if (i16::from(i8::min_value()) <= v) && (v <= i16::from(i8::max_value())) {
+        if (i16::from(i8::MIN) <= v) && (v <= i16::from(i8::MAX)) {
             self.serialize_i8(v as i8)
-        } else if (i16::from(u8::min_value()) <= v) && (v <= i16::from(u8::max_value())) {
+        } else if (i16::from(u8::MIN) <= v) && (v <= i16::from(u8::MAX)) {
             self.serialize_u8(v as u8)
         } else {
-            self.inner.write_u8(marker::I16)?;
-            self.inner.write_i16::<BigEndian>(v)?;
-            Ok(())
+            self.write_marked(marker::I16, &v.to_be_bytes())
         }
     }
 
     fn serialize_i32(self, v: i32) -> Result<()> {
-        if (i32::from(i16::min_value()) <= v) && (v <= i32::from(i16::max_value())) {
+        if (i32::from(i16::MIN) <= v) && (v <= i32::from(i16::MAX)) {
             self.serialize_i16(v as i16)
         } else {
-            self.inner.write_u8(marker::I32)?;
-            self.inner.write_i32::<BigEndian>(v)?;
-            Ok(())
+            self.write_marked(marker::I32, &v.to_be_bytes())
         }
     }
 
     fn serialize_i64(self, v: i64) -> Result<()> {
-        if (i64::from(i32::min_value()) <= v) && (v <= i64::from(i32::max_value())) {
+        if (i64::from(i32::MIN) <= v) && (v <= i64::from(i32::MAX)) {
             self.serialize_i32(v as i32)
         } else {
-            self.inner.write_u8(marker::I64)?;
-            self.inner.write_i64::<BigEndian>(v)?;
-            Ok(())
+            self.write_marked(marker::I64, &v.to_be_bytes())
         }
     }
 
     fn serialize_u8(self, v: u8) -> Result<()> {
-        self.inner.write_u8(marker::U8)?;
-        self.inner.write_u8(v)?;
-        Ok(())
+        self.write_marked(marker::U8, &[v])
     }
 
     fn serialize_u16(self, v: u16) -> Result<()> {
-        if v <= u16::from(u8::max_value()) {
+        if v <= u16::from(u8::MAX) {
             self.serialize_u8(v as u8)
-        } else if v <= i16::max_value() as u16 {
+        } else if v <= i16::MAX as u16 {
             self.serialize_i16(v as i16)
         } else {
             self.serialize_i32(i32::from(v))
@@ -126,9 +868,9 @@ impl<'a, W: Write> ser::Serializer for &'a mut Serializer<W> {
     }
 
     fn serialize_u32(self, v: u32) -> Result<()> {
-        if v <= u32::from(u16::max_value()) {
+        if v <= u32::from(u16::MAX) {
             self.serialize_u16(v as u16)
-        } else if v <= i32::max_value() as u32 {
+        } else if v <= i32::MAX as u32 {
             self.serialize_i32(v as i32)
         } else {
             self.serialize_i64(i64::from(v))
@@ -136,130 +878,230 @@ impl<'a, W: Write> ser::Serializer for &'a mut Serializer<W> {
     }
 
     fn serialize_u64(self, v: u64) -> Result<()> {
-        if v <= u64::from(u32::max_value()) {
+        if v <= u64::from(u32::MAX) {
             self.serialize_u32(v as u32)
-        } else if v <= i64::max_value() as u64 {
+        } else if v <= i64::MAX as u64 {
+            self.serialize_i64(v as i64)
+        } else {
+            self.write_hi_precision(&v.to_string())
+        }
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<()> {
+        if (i128::from(i64::MIN) <= v) && (v <= i128::from(i64::MAX)) {
             self.serialize_i64(v as i64)
         } else {
-            let v = v.to_string();
-            self.inner.write_u8(marker::HI_PRECISION)?;
-            self.serialize_u64(v.len() as u64)?;
-            self.inner.write_all(v.as_bytes())?;
-            Ok(())
+            self.write_hi_precision(&v.to_string())
+        }
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<()> {
+        if v <= u128::from(u64::MAX) {
+            self.serialize_u64(v as u64)
+        } else {
+            self.write_hi_precision(&v.to_string())
         }
     }
 
     fn serialize_f32(self, v: f32) -> Result<()> {
-        self.inner.write_u8(marker::F32)?;
-        self.inner.write_f32::<BigEndian>(v)?;
-        Ok(())
+        if !v.is_finite() && self.config.non_finite_float != NonFiniteFloat::Raw {
+            return self.serialize_non_finite();
+        }
+        let v = if v == 0.0 && self.config.normalize_negative_zero {
+            0.0
+        } else {
+            v
+        };
+        self.write_marked(marker::F32, &v.to_be_bytes())
     }
 
     fn serialize_f64(self, v: f64) -> Result<()> {
-        self.inner.write_u8(marker::F64)?;
-        self.inner.write_f64::<BigEndian>(v)?;
-        Ok(())
+        if !v.is_finite() && self.config.non_finite_float != NonFiniteFloat::Raw {
+            return self.serialize_non_finite();
+        }
+        let v = if v == 0.0 && self.config.normalize_negative_zero {
+            0.0
+        } else {
+            v
+        };
+        if self.config.compact_floats && f64::from(v as f32).to_bits() == v.to_bits() {
+            return self.serialize_f32(v as f32);
+        }
+        self.write_marked(marker::F64, &v.to_be_bytes())
     }
 
     fn serialize_char(self, v: char) -> Result<()> {
         let v: u32 = v.into();
         if v <= 127 {
-            self.inner.write_u8(marker::CHAR)?;
-            self.inner.write_u8(v as u8)?;
-            Ok(())
+            self.write_marked(marker::CHAR, &[v as u8])
         } else {
             self.serialize_u32(v)
         }
     }
 
     fn serialize_str(self, v: &str) -> Result<()> {
-        self.inner.write_u8(marker::STRING)?;
-        self.serialize_u64(v.len() as u64)?;
+        write_header_with_length(&mut self.inner, &[marker::STRING], v.len() as u64)?;
         self.inner.write_all(v.as_bytes())?;
         Ok(())
     }
 
     fn serialize_bytes(self, v: &[u8]) -> Result<()> {
         let header = [marker::ARR_START, marker::TYPE, marker::U8, marker::LENGTH];
-        self.inner.write_all(&header)?;
-        self.serialize_u64(v.len() as u64)?;
-        self.inner.write_all(v).map_err(Error::Io)?;
+        write_count(
+            &mut self.inner,
+            &header,
+            v.len() as u64,
+            self.config.count_encoding,
+        )?;
+        self.inner.write_all(v)?;
         Ok(())
     }
 
     fn serialize_none(self) -> Result<()> {
-        self.inner.write_u8(marker::NULL).map_err(Error::Io)
+        if self.in_some() {
+            let counted = self.write_container_header(marker::ARR_START, Some(1))?;
+            self.inner.write_u8(marker::NULL)?;
+            if !counted {
+                self.inner.write_u8(marker::ARR_END)?;
+            }
+            return Ok(());
+        }
+        self.inner.write_u8(marker::NULL)?;
+        Ok(())
     }
 
-    fn serialize_some<T: ?Sized>(self, value: &T) -> Result<()>
+    fn serialize_some<T>(self, value: &T) -> Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        value.serialize(self)
+        if !self.config.faithful_options {
+            return value.serialize(self);
+        }
+        let counted = if self.in_some() {
+            Some(self.write_container_header(marker::ARR_START, Some(1))?)
+        } else {
+            None
+        };
+        self.some_start = Some(self.inner.count);
+        value.serialize(&mut *self)?;
+        if counted == Some(false) {
+            self.inner.write_u8(marker::ARR_END)?;
+        }
+        Ok(())
     }
 
     fn serialize_unit(self) -> Result<()> {
+        if self.config.unit_as_empty_array {
+            self.inner
+                .write_all(&[marker::ARR_START, marker::ARR_END])?;
+            return Ok(());
+        }
         self.serialize_none()
     }
 
+    /// Unit structs, `PhantomData` among them, are written just like `()`.
     fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
-        self.serialize_none()
+        self.serialize_unit()
     }
 
     fn serialize_unit_variant(
         self,
         _name: &'static str,
         variant_index: u32,
-        _variant: &'static str,
+        variant: &'static str,
     ) -> Result<()> {
-        self.serialize_u32(variant_index)
+        self.serialize_variant_tag(variant_index, variant)
     }
 
-    fn serialize_newtype_struct<T: ?Sized>(self, _name: &'static str, value: &T) -> Result<()>
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
+        if name == HIGH_PRECISION_TOKEN {
+            // A high-precision number is laid out exactly like a string besides its marker.
+            let mut buf = self.fork(0);
+            value.serialize(&mut buf)?;
+            return match buf.into_inner().split_first() {
+                Some((&marker::STRING, rest)) => {
+                    self.inner.write_u8(marker::HI_PRECISION)?;
+                    self.inner.write_all(rest)?;
+                    Ok(())
+                }
+                _ => Err(ser::Error::custom("high-precision number must be a string")),
+            };
+        }
+        if name == RAW_VALUE_TOKEN {
+            // The bytes of a raw value come as a byte array, whose payload is written alone.
+            return value.serialize(RawValueEmitter { ser: self });
+        }
         value.serialize(self)
     }
 
-    fn serialize_newtype_variant<T: ?Sized>(
+    fn serialize_newtype_variant<T>(
         self,
         _name: &'static str,
         variant_index: u32,
-        _variant: &'static str,
+        variant: &'static str,
         value: &T,
     ) -> Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         let mut tup = self.serialize_tuple(2)?;
-        ser::SerializeTuple::serialize_element(&mut tup, &variant_index)?;
+        tup.ser.serialize_variant_tag(variant_index, variant)?;
         ser::SerializeTuple::serialize_element(&mut tup, value)?;
         ser::SerializeTuple::end(tup)
     }
 
+    /// A sequence of known length is written in the count-only form, `[#` and the count with no
+    /// closing `]`, exactly like a tuple. Otherwise it is left open and closed by `]`.
     fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq> {
-        self.inner.write_u8(marker::ARR_START)?;
-        if let Some(len) = len {
-            self.inner.write_u8(marker::LENGTH)?;
-            len.serialize(&mut *self)?;
-            Ok(Dynamic {
-                ser: self,
-                length_known: true,
-            })
-        } else {
-            Ok(Dynamic {
+        let config = self.config;
+        let optimize = match len {
+            Some(_) => config.optimize_seqs,
+            None => config.optimize_unsized_seqs || config.optimize_seqs,
+        };
+        if optimize && !config.always_unsized_containers {
+            return Ok(Dynamic {
                 ser: self,
                 length_known: false,
-            })
+                entries: None,
+                held: 0,
+                buffer: Some(SeqBuffer {
+                    len,
+                    ..SeqBuffer::default()
+                }),
+                map_len: None,
+                #[cfg(feature = "error_path")]
+                index: 0,
+                #[cfg(feature = "error_path")]
+                key: String::new(),
+            });
         }
+        let length_known = self.write_container_header(marker::ARR_START, len)?;
+        Ok(Dynamic {
+            ser: self,
+            length_known,
+            entries: None,
+            held: 0,
+            buffer: None,
+            map_len: None,
+            #[cfg(feature = "error_path")]
+            index: 0,
+            #[cfg(feature = "error_path")]
+            key: String::new(),
+        })
     }
 
     fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
-        let header = [marker::ARR_START, marker::LENGTH];
-        self.inner.write_all(&header)?;
-        self.serialize_u64(len as u64)?;
-        Ok(Static { ser: self })
+        let counted = self.write_container_header(marker::ARR_START, Some(len))?;
+        Ok(Static {
+            ser: self,
+            keyed: false,
+            end: if counted { &[] } else { &[marker::ARR_END] },
+            #[cfg(feature = "error_path")]
+            index: 0,
+        })
     }
 
     fn serialize_tuple_struct(
@@ -274,33 +1116,55 @@ impl<'a, W: Write> ser::Serializer for &'a mut Serializer<W> {
         self,
         _name: &'static str,
         variant_index: u32,
-        _variant: &'static str,
+        variant: &'static str,
         len: usize,
     ) -> Result<Self::SerializeTupleVariant> {
-        let mut tup = self.serialize_tuple(len + 1)?;
-        ser::SerializeTuple::serialize_element(&mut tup, &variant_index)?;
+        let tup = self.serialize_tuple(len + 1)?;
+        tup.ser.serialize_variant_tag(variant_index, variant)?;
         Ok(tup)
     }
 
     fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap> {
-        self.inner.write_u8(marker::OBJ_START)?;
-        if let Some(len) = len {
-            self.inner.write_u8(marker::LENGTH)?;
-            len.serialize(&mut *self)?;
-            Ok(Dynamic {
-                ser: self,
-                length_known: true,
-            })
+        let config = self.config;
+        let optimize = config.optimize_maps && !config.always_unsized_containers;
+        let length_known = if optimize {
+            false
         } else {
-            Ok(Dynamic {
-                ser: self,
-                length_known: false,
-            })
-        }
+            self.write_container_header(marker::OBJ_START, len)?
+        };
+        let entries = if config.sort_map_keys || optimize {
+            Some(Vec::new())
+        } else {
+            None
+        };
+        Ok(Dynamic {
+            ser: self,
+            length_known,
+            entries,
+            held: 0,
+            buffer: None,
+            map_len: if optimize { Some(len) } else { None },
+            #[cfg(feature = "error_path")]
+            index: 0,
+            #[cfg(feature = "error_path")]
+            key: String::new(),
+        })
     }
 
     fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Self::SerializeStruct> {
-        self.serialize_tuple(len)
+        match self.config.struct_representation {
+            StructRepresentation::Array => self.serialize_tuple(len),
+            StructRepresentation::Object => {
+                let counted = self.write_container_header(marker::OBJ_START, Some(len))?;
+                Ok(Static {
+                    ser: self,
+                    keyed: true,
+                    end: if counted { &[] } else { &[marker::OBJ_END] },
+                    #[cfg(feature = "error_path")]
+                    index: 0,
+                })
+            }
+        }
     }
 
     fn serialize_struct_variant(
@@ -310,7 +1174,21 @@ impl<'a, W: Write> ser::Serializer for &'a mut Serializer<W> {
         variant: &'static str,
         len: usize,
     ) -> Result<Self::SerializeStructVariant> {
-        self.serialize_tuple_variant(name, variant_index, variant, len)
+        match self.config.struct_representation {
+            StructRepresentation::Array => {
+                self.serialize_tuple_variant(name, variant_index, variant, len)
+            }
+            StructRepresentation::Object => {
+                let Static { ser, end, .. } = self.serialize_tuple(2)?;
+                ser.serialize_variant_tag(variant_index, variant)?;
+                let mut obj = ser.serialize_struct(name, len)?;
+                // The object is the last element of the array, so both are closed at once.
+                if !end.is_empty() {
+                    obj.end = &[marker::OBJ_END, marker::ARR_END];
+                }
+                Ok(obj)
+            }
+        }
     }
 }
 
@@ -320,6 +1198,13 @@ impl<'a, W: Write> ser::Serializer for &'a mut Serializer<W> {
 /// Serialization handler for compound types with non-optional length (i. e. len: usize).
 pub struct Static<'a, W: 'a> {
     ser: &'a mut Serializer<W>,
+    /// Whether struct fields are preceded by their names.
+    keyed: bool,
+    /// Markers that close the container, if it was written without a count.
+    end: &'static [u8],
+    /// Position of the next element, to say where an error happened.
+    #[cfg(feature = "error_path")]
+    index: usize,
 }
 
 impl<'a, W: 'a> ser::SerializeTuple for Static<'a, W>
@@ -329,14 +1214,21 @@ where
     type Ok = ();
     type Error = Error;
 
-    fn serialize_element<T: ?Sized>(&mut self, value: &T) -> Result<()>
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        value.serialize(&mut *self.ser)
+        let result = value.serialize(&mut *self.ser);
+        #[cfg(feature = "error_path")]
+        let result = {
+            self.index += 1;
+            result.map_err(|e| e.within(&format!("[{}]", self.index - 1)))
+        };
+        result
     }
 
     fn end(self) -> Result<()> {
+        self.ser.inner.write_all(self.end)?;
         Ok(())
     }
 }
@@ -348,9 +1240,9 @@ where
     type Ok = ();
     type Error = Error;
 
-    fn serialize_field<T: ?Sized>(&mut self, value: &T) -> Result<()>
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         ser::SerializeTuple::serialize_element(self, value)
     }
@@ -367,9 +1259,9 @@ where
     type Ok = ();
     type Error = Error;
 
-    fn serialize_field<T: ?Sized>(&mut self, value: &T) -> Result<()>
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         ser::SerializeTuple::serialize_element(self, value)
     }
@@ -386,11 +1278,17 @@ where
     type Ok = ();
     type Error = Error;
 
-    fn serialize_field<T: ?Sized>(&mut self, _key: &'static str, value: &T) -> Result<()>
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        ser::SerializeTuple::serialize_element(self, value)
+        if self.keyed {
+            self.ser.write_key(key)?;
+        }
+        let result = value.serialize(&mut *self.ser);
+        #[cfg(feature = "error_path")]
+        let result = result.map_err(|e| e.within(key));
+        result
     }
 
     fn end(self) -> Result<()> {
@@ -405,11 +1303,17 @@ where
     type Ok = ();
     type Error = Error;
 
-    fn serialize_field<T: ?Sized>(&mut self, _key: &'static str, value: &T) -> Result<()>
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        ser::SerializeTuple::serialize_element(self, value)
+        if self.keyed {
+            self.ser.write_key(key)?;
+        }
+        let result = value.serialize(&mut *self.ser);
+        #[cfg(feature = "error_path")]
+        let result = result.map_err(|e| e.within(key));
+        result
     }
 
     fn end(self) -> Result<()> {
@@ -424,6 +1328,374 @@ where
 pub struct Dynamic<'a, W: 'a> {
     ser: &'a mut Serializer<W>,
     length_known: bool,
+    /// Encoded keys and values of a map, held back to be sorted by key.
+    entries: Option<Vec<(Vec<u8>, Vec<u8>)>>,
+    /// Total size of `entries`.
+    held: usize,
+    /// Encoded elements of a sequence, held back to find out whether they share a type.
+    buffer: Option<SeqBuffer>,
+    /// Length of a map whose header `optimize_maps` is holding back along with its entries.
+    map_len: Option<Option<usize>>,
+    /// Position of the next element, to say where an error happened.
+    #[cfg(feature = "error_path")]
+    index: usize,
+    /// The key of the map entry being written, to say where an error happened.
+    #[cfg(feature = "error_path")]
+    key: String,
+}
+
+/// How many bytes of elements `optimize_unsized_seqs` and `optimize_seqs` hold back before giving
+/// up on a sequence.
+pub const OPTIMIZE_BUFFER_LIMIT: usize = 64 * 1024;
+
+// With the `smallvec` feature, short sequences are held back without allocating.
+#[cfg(feature = "smallvec")]
+type BufferBytes = smallvec::SmallVec<[u8; 64]>;
+#[cfg(feature = "smallvec")]
+type BufferStarts = smallvec::SmallVec<[usize; 8]>;
+#[cfg(not(feature = "smallvec"))]
+type BufferBytes = Vec<u8>;
+#[cfg(not(feature = "smallvec"))]
+type BufferStarts = Vec<usize>;
+
+#[derive(Default)]
+struct SeqBuffer {
+    /// Length of the sequence, if known up front.
+    len: Option<usize>,
+    /// Encoded elements, markers included, back to back.
+    bytes: BufferBytes,
+    /// Where each element starts in `bytes`.
+    starts: BufferStarts,
+}
+
+impl SeqBuffer {
+    fn elements(&self) -> impl Iterator<Item = &[u8]> + Clone {
+        let ends = self
+            .starts
+            .iter()
+            .skip(1)
+            .cloned()
+            .chain(Some(self.bytes.len()));
+        self.starts
+            .iter()
+            .zip(ends)
+            .map(move |(&start, end)| &self.bytes[start..end])
+    }
+}
+
+/// Picks the marker every one of the encoded `elements` could be written with, if there is one.
+fn common_type<'b, I>(elements: I) -> Option<u8>
+where
+    I: Iterator<Item = &'b [u8]> + Clone,
+{
+    let mut rest = elements.clone();
+    let first = rest.next()?[0];
+    if rest.all(|e| e[0] == first) {
+        return Some(first);
+    }
+    let (mut min, mut max) = (0, 0);
+    for e in elements {
+        let v = read_integer(e)?;
+        min = cmp::min(min, v);
+        max = cmp::max(max, v);
+    }
+    let ty = if min >= i64::from(i8::MIN) && max <= i64::from(i8::MAX) {
+        marker::I8
+    } else if min >= 0 && max <= i64::from(u8::MAX) {
+        marker::U8
+    } else if min >= i64::from(i16::MIN) && max <= i64::from(i16::MAX) {
+        marker::I16
+    } else if min >= i64::from(i32::MIN) && max <= i64::from(i32::MAX) {
+        marker::I32
+    } else {
+        marker::I64
+    };
+    Some(ty)
+}
+
+/// Returns how many bytes the encoded `elements` take up in a strongly-typed container of `ty`.
+fn typed_payload_size<'b, I>(elements: I, ty: u8) -> usize
+where
+    I: Iterator<Item = &'b [u8]> + Clone,
+{
+    match integer_width(ty) {
+        Some(width) if elements.clone().any(|e| e[0] != ty) => elements.count() * width,
+        _ => elements.map(|e| e.len() - 1).sum(),
+    }
+}
+
+/// Writes an encoded element into a strongly-typed container of `ty`, leaving off its marker.
+fn write_typed_payload<W>(writer: &mut W, e: &[u8], ty: u8) -> Result<()>
+where
+    W: ?Sized + Write,
+{
+    if e[0] == ty {
+        writer.write_all(&e[1..])?;
+        return Ok(());
+    }
+    // Only integers are mixed, and `ty` is wide enough for all of them.
+    let v = read_integer(e).unwrap_or_default();
+    match ty {
+        marker::I8 => writer.write_i8(v as i8)?,
+        marker::U8 => writer.write_u8(v as u8)?,
+        marker::I16 => writer.write_i16::<BigEndian>(v as i16)?,
+        marker::I32 => writer.write_i32::<BigEndian>(v as i32)?,
+        _ => writer.write_i64::<BigEndian>(v)?,
+    }
+    Ok(())
+}
+
+/// Picks the smallest integer marker that can hold `v`, as `serialize_i64` does.
+pub(crate) fn integer_marker(v: i64) -> u8 {
+    [marker::I8, marker::U8, marker::I16, marker::I32]
+        .iter()
+        .cloned()
+        .find(|&m| integer_fits(v, m))
+        .unwrap_or(marker::I64)
+}
+
+/// Whether `v` can be written with the integer marker `m`.
+pub(crate) fn integer_fits(v: i64, m: u8) -> bool {
+    match m {
+        marker::I8 => v >= i64::from(i8::MIN) && v <= i64::from(i8::MAX),
+        marker::U8 => v >= 0 && v <= i64::from(u8::MAX),
+        marker::I16 => v >= i64::from(i16::MIN) && v <= i64::from(i16::MAX),
+        marker::I32 => v >= i64::from(i32::MIN) && v <= i64::from(i32::MAX),
+        marker::I64 => true,
+        _ => false,
+    }
+}
+
+/// Copies the payload of `v`, written with the integer marker `m`, to the start of `buf`, and
+/// returns how many bytes it took.
+pub(crate) fn stage_integer(buf: &mut [u8], m: u8, v: i64) -> usize {
+    let width = integer_width(m).unwrap_or(8);
+    buf[..width].copy_from_slice(&v.to_be_bytes()[8 - width..]);
+    width
+}
+
+/// Writes `header` followed by `len` encoded as `serialize_u64` would, such as `S` and a string's
+/// length, with a single call to the writer. `header` may be at most 4 bytes long.
+pub(crate) fn write_header_with_length<W>(writer: &mut W, header: &[u8], len: u64) -> Result<()>
+where
+    W: ?Sized + Write,
+{
+    if len > i64::MAX as u64 {
+        // A high-precision number, whose handful of digits always has a `U` length.
+        let digits = len.to_string();
+        writer.write_all(header)?;
+        writer.write_all(&[marker::HI_PRECISION, marker::U8, digits.len() as u8])?;
+        writer.write_all(digits.as_bytes())?;
+        return Ok(());
+    }
+    let m = if len <= u64::from(u8::MAX) {
+        marker::U8
+    } else {
+        integer_marker(len as i64)
+    };
+    let mut buf = [0; 13];
+    buf[..header.len()].copy_from_slice(header);
+    buf[header.len()] = m;
+    let end = header.len() + 1;
+    let end = end + stage_integer(&mut buf[end..], m, len as i64);
+    writer.write_all(&buf[..end])?;
+    Ok(())
+}
+
+/// Writes `header` followed by the count `len` as `encoding` asks, all in one write.
+fn write_count<W>(writer: &mut W, header: &[u8], len: u64, encoding: CountEncoding) -> Result<()>
+where
+    W: ?Sized + Write,
+{
+    let m = match encoding.fixed_marker() {
+        Some(m) => m,
+        None => return write_header_with_length(writer, header, len),
+    };
+    if len > i64::MAX as u64 || !integer_fits(len as i64, m) {
+        return Err(ser::Error::custom(format_args!(
+            "count {} does not fit in {:?}",
+            len, encoding
+        )));
+    }
+    let mut buf = [0; 13];
+    buf[..header.len()].copy_from_slice(header);
+    buf[header.len()] = m;
+    let end = header.len() + 1;
+    let end = end + stage_integer(&mut buf[end..], m, len as i64);
+    writer.write_all(&buf[..end])?;
+    Ok(())
+}
+
+/// Returns how many bytes follow an integer marker.
+fn integer_width(m: u8) -> Option<usize> {
+    match m {
+        marker::I8 | marker::U8 => Some(1),
+        marker::I16 => Some(2),
+        marker::I32 => Some(4),
+        marker::I64 => Some(8),
+        _ => None,
+    }
+}
+
+/// Reads back an encoded integer, or returns `None` if the value is not one.
+fn read_integer(v: &[u8]) -> Option<i64> {
+    let payload = &v[1..];
+    match v[0] {
+        marker::I8 => Some(i64::from(payload[0] as i8)),
+        marker::U8 => Some(i64::from(payload[0])),
+        marker::I16 => Some(i64::from(BigEndian::read_i16(payload))),
+        marker::I32 => Some(i64::from(BigEndian::read_i32(payload))),
+        marker::I64 => Some(BigEndian::read_i64(payload)),
+        _ => None,
+    }
+}
+
+impl<'a, W: 'a> Dynamic<'a, W>
+where
+    W: Write,
+{
+    /// Writes out the held-back elements of a sequence, as a strongly-typed container if that is
+    /// smaller, or else as they are.
+    fn flush_buffer(&mut self, buffer: SeqBuffer, finished: bool) -> Result<()> {
+        let count = buffer.starts.len();
+        let encoding = self.ser.config.count_encoding;
+        let mut count_header = Vec::new();
+        let count_fits = write_count(&mut count_header, &[], count as u64, encoding).is_ok();
+        let ty = common_type(buffer.elements());
+        if let (true, Some(ty)) = (finished && count > 0 && count_fits, ty) {
+            let payload_size = typed_payload_size(buffer.elements(), ty);
+            let count_size = count_header.len();
+            let typed_size = 4 + count_size + payload_size;
+            let plain_size = buffer.bytes.len()
+                + match buffer.len {
+                    Some(_) => 2 + count_size,
+                    None => 2,
+                };
+            if typed_size < plain_size {
+                let header = [marker::ARR_START, marker::TYPE, ty, marker::LENGTH];
+                write_count(&mut self.ser.inner, &header, count as u64, encoding)?;
+                for e in buffer.elements() {
+                    write_typed_payload(&mut self.ser.inner, e, ty)?;
+                }
+                return Ok(());
+            }
+        }
+        self.length_known = self
+            .ser
+            .write_container_header(marker::ARR_START, buffer.len)?;
+        self.ser.inner.write_all(&buffer.bytes)?;
+        if finished && !self.length_known {
+            self.ser.inner.write_u8(marker::ARR_END)?;
+        }
+        Ok(())
+    }
+
+    /// Writes out the held-back entries of a map, sorted if `sort_map_keys` asks. If
+    /// `optimize_maps` held back the header too, the map is written as a strongly-typed object
+    /// when that is smaller.
+    fn flush_entries(&mut self, finished: bool) -> Result<()> {
+        let mut entries = self.entries.take().unwrap_or_default();
+        self.held = 0;
+        if self.ser.config.sort_map_keys {
+            entries.sort_by(|a, b| key_payload(&a.0).cmp(key_payload(&b.0)));
+        }
+        let mut typed = None;
+        if let Some(len) = self.map_len.take() {
+            let count = entries.len();
+            let encoding = self.ser.config.count_encoding;
+            let mut count_header = Vec::new();
+            let count_fits = write_count(&mut count_header, &[], count as u64, encoding).is_ok();
+            let values = entries.iter().map(|e| &e.1[..]);
+            if let (true, Some(ty)) = (
+                finished && count > 0 && count_fits,
+                common_type(values.clone()),
+            ) {
+                let count_size = count_header.len();
+                let typed_size = 4 + count_size + typed_payload_size(values.clone(), ty);
+                let plain_size = values.map(<[u8]>::len).sum::<usize>()
+                    + match len {
+                        Some(_) => 2 + count_size,
+                        None => 2,
+                    };
+                if typed_size < plain_size {
+                    typed = Some(ty);
+                }
+            }
+            match typed {
+                Some(ty) => {
+                    let header = [marker::OBJ_START, marker::TYPE, ty, marker::LENGTH];
+                    write_count(&mut self.ser.inner, &header, count as u64, encoding)?;
+                    self.length_known = true;
+                }
+                None => {
+                    self.length_known = self.ser.write_container_header(marker::OBJ_START, len)?;
+                }
+            }
+        }
+        for (key, value) in entries {
+            self.ser.inner.write_all(&key)?;
+            match typed {
+                Some(ty) => write_typed_payload(&mut self.ser.inner, &value, ty)?,
+                None => self.ser.inner.write_all(&value)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Serializes the next element of a sequence.
+    fn write_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        let buffer = match self.buffer {
+            Some(ref mut buffer) => buffer,
+            None => return value.serialize(&mut *self.ser),
+        };
+        buffer.starts.push(buffer.bytes.len());
+        // Writing the elements as a strongly-typed container drops at most a marker from each, so
+        // more than that beyond what may still be written would be too much either way.
+        let limit = self
+            .ser
+            .inner
+            .remaining()
+            .saturating_add(buffer.starts.len() as u64)
+            .saturating_sub(buffer.bytes.len() as u64);
+        value.serialize(&mut Serializer {
+            inner: CountingWriter::with_limit(&mut buffer.bytes, limit),
+            config: self.ser.config,
+            some_start: None,
+        })?;
+        if buffer.bytes.len() > OPTIMIZE_BUFFER_LIMIT {
+            // Too long to hold back any further, so the rest is written as it comes.
+            let buffer = self.buffer.take().unwrap_or_default();
+            self.flush_buffer(buffer, false)?;
+        }
+        Ok(())
+    }
+
+    /// Serializes the value of a map entry whose key has been written.
+    fn write_value<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        match self.entries {
+            Some(ref mut entries) => {
+                let mut buf = self.ser.fork(self.held);
+                value.serialize(&mut buf)?;
+                let value = buf.into_inner();
+                self.held += value.len();
+                if let Some(entry) = entries.last_mut() {
+                    entry.1 = value;
+                }
+                if self.held > OPTIMIZE_BUFFER_LIMIT && !self.ser.config.sort_map_keys {
+                    // Too much to hold back any further, so the rest is written as it comes.
+                    self.flush_entries(false)?;
+                }
+                Ok(())
+            }
+            None => value.serialize(&mut *self.ser),
+        }
+    }
 }
 
 impl<'a, W: 'a> ser::SerializeSeq for Dynamic<'a, W>
@@ -433,14 +1705,23 @@ where
     type Ok = ();
     type Error = Error;
 
-    fn serialize_element<T: ?Sized>(&mut self, value: &T) -> Result<()>
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        value.serialize(&mut *self.ser)
-    }
-
-    fn end(self) -> Result<()> {
+        let result = self.write_element(value);
+        #[cfg(feature = "error_path")]
+        let result = {
+            self.index += 1;
+            result.map_err(|e| e.within(&format!("[{}]", self.index - 1)))
+        };
+        result
+    }
+
+    fn end(mut self) -> Result<()> {
+        if let Some(buffer) = self.buffer.take() {
+            return self.flush_buffer(buffer, true);
+        }
         if !self.length_known {
             self.ser.inner.write_u8(marker::ARR_END)?;
         }
@@ -455,23 +1736,48 @@ where
     type Ok = ();
     type Error = Error;
 
-    fn serialize_key<T: ?Sized>(&mut self, key: &T) -> Result<()>
+    fn serialize_key<T>(&mut self, key: &T) -> Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        key.serialize(MapKeySerializer {
-            ser: &mut *self.ser,
-        })
+        // Keys are written straight through unless they must be held back for sorting or kept
+        // to say where an error happened.
+        if self.entries.is_none() && !cfg!(feature = "error_path") {
+            return key.serialize(MapKeySerializer {
+                ser: &mut *self.ser,
+            });
+        }
+        let mut buf = self.ser.fork(self.held);
+        key.serialize(MapKeySerializer { ser: &mut buf })?;
+        let key = buf.into_inner();
+        #[cfg(feature = "error_path")]
+        {
+            self.key = String::from_utf8_lossy(key_payload(&key)).into_owned();
+        }
+        match self.entries {
+            Some(ref mut entries) => {
+                self.held += key.len();
+                entries.push((key, Vec::new()));
+            }
+            None => self.ser.inner.write_all(&key)?,
+        }
+        Ok(())
     }
 
-    fn serialize_value<T: ?Sized>(&mut self, value: &T) -> Result<()>
+    fn serialize_value<T>(&mut self, value: &T) -> Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        value.serialize(&mut *self.ser)
+        let result = self.write_value(value);
+        #[cfg(feature = "error_path")]
+        let result = result.map_err(|e| e.within(&self.key));
+        result
     }
 
-    fn end(self) -> Result<()> {
+    fn end(mut self) -> Result<()> {
+        if self.entries.is_some() {
+            self.flush_entries(true)?;
+        }
         if !self.length_known {
             self.ser.inner.write_u8(marker::OBJ_END)?;
         }
@@ -479,10 +1785,35 @@ where
     }
 }
 
+/// Strips the length from an encoded object key, leaving the key's UTF-8 bytes.
+fn key_payload(key: &[u8]) -> &[u8] {
+    let len_size = match key[0] {
+        marker::I16 => 3,
+        marker::I32 => 5,
+        marker::I64 => 9,
+        _ => 2,
+    };
+    &key[len_size..]
+}
+
 struct MapKeySerializer<'a, W: 'a> {
     ser: &'a mut Serializer<W>,
 }
 
+impl<'a, W> MapKeySerializer<'a, W>
+where
+    W: Write,
+{
+    /// Writes a scalar key in its `Display` form, if `stringify_non_string_keys` allows it.
+    fn stringify<T: Display>(self, v: T, found: &'static str) -> Result<()> {
+        if self.ser.config.stringify_non_string_keys {
+            self.ser.write_key(&v.to_string())
+        } else {
+            Err(Error::KeyMustBeAString(found))
+        }
+    }
+}
+
 impl<'a, W> ser::Serializer for MapKeySerializer<'a, W>
 where
     W: Write,
@@ -498,81 +1829,272 @@ where
     type SerializeStruct = Impossible<(), Error>;
     type SerializeStructVariant = Impossible<(), Error>;
 
+    fn is_human_readable(&self) -> bool {
+        self.ser.config.human_readable
+    }
+
     fn serialize_str(self, v: &str) -> Result<()> {
-        v.len().serialize(&mut *self.ser)?;
-        self.ser.inner.write_all(v.as_bytes())?;
-        Ok(())
+        self.ser.write_key(v)
+    }
+
+    fn serialize_bool(self, v: bool) -> Result<()> {
+        self.stringify(v, "bool")
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<()> {
+        self.stringify(v, "i8")
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<()> {
+        self.stringify(v, "i16")
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<()> {
+        self.stringify(v, "i32")
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<()> {
+        self.stringify(v, "i64")
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<()> {
+        self.stringify(v, "i128")
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<()> {
+        self.stringify(v, "u8")
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<()> {
+        self.stringify(v, "u16")
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<()> {
+        self.stringify(v, "u32")
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<()> {
+        self.stringify(v, "u64")
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<()> {
+        self.stringify(v, "u128")
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<()> {
+        Err(Error::KeyMustBeAString("f32"))
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<()> {
+        Err(Error::KeyMustBeAString("f64"))
+    }
+
+    fn serialize_char(self, v: char) -> Result<()> {
+        self.stringify(v, "char")
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<()> {
+        Err(Error::KeyMustBeAString("bytes"))
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        Err(Error::KeyMustBeAString("option"))
+    }
+
+    fn serialize_some<T>(self, _v: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(Error::KeyMustBeAString("option"))
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        Err(Error::KeyMustBeAString("unit"))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        Err(Error::KeyMustBeAString("unit struct"))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<()> {
+        Err(Error::KeyMustBeAString("unit variant"))
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, _value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(Error::KeyMustBeAString("newtype struct"))
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(Error::KeyMustBeAString("newtype variant"))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Err(Error::KeyMustBeAString("sequence"))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
+        Err(Error::KeyMustBeAString("tuple"))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        Err(Error::KeyMustBeAString("tuple struct"))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Err(Error::KeyMustBeAString("tuple variant"))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Err(Error::KeyMustBeAString("map"))
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        Err(Error::KeyMustBeAString("struct"))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Err(Error::KeyMustBeAString("struct variant"))
+    }
+}
+
+fn raw_value_expected() -> Error {
+    ser::Error::custom("expected RawValue")
+}
+
+/// Writes the bytes a `RawValue` hands over as they are.
+struct RawValueEmitter<'a, W: 'a> {
+    ser: &'a mut Serializer<W>,
+}
+
+impl<'a, W> ser::Serializer for RawValueEmitter<'a, W>
+where
+    W: Write,
+{
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = Impossible<(), Error>;
+    type SerializeTuple = Impossible<(), Error>;
+    type SerializeTupleStruct = Impossible<(), Error>;
+    type SerializeTupleVariant = Impossible<(), Error>;
+    type SerializeMap = Impossible<(), Error>;
+    type SerializeStruct = Impossible<(), Error>;
+    type SerializeStructVariant = Impossible<(), Error>;
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<()> {
+        self.ser.write_fragment(v)
     }
 
     fn serialize_bool(self, _v: bool) -> Result<()> {
-        Err(Error::KeyMustBeAString)
+        Err(raw_value_expected())
     }
 
     fn serialize_i8(self, _v: i8) -> Result<()> {
-        Err(Error::KeyMustBeAString)
+        Err(raw_value_expected())
     }
 
     fn serialize_i16(self, _v: i16) -> Result<()> {
-        Err(Error::KeyMustBeAString)
+        Err(raw_value_expected())
     }
 
     fn serialize_i32(self, _v: i32) -> Result<()> {
-        Err(Error::KeyMustBeAString)
+        Err(raw_value_expected())
     }
 
     fn serialize_i64(self, _v: i64) -> Result<()> {
-        Err(Error::KeyMustBeAString)
+        Err(raw_value_expected())
+    }
+
+    fn serialize_i128(self, _v: i128) -> Result<()> {
+        Err(raw_value_expected())
     }
 
     fn serialize_u8(self, _v: u8) -> Result<()> {
-        Err(Error::KeyMustBeAString)
+        Err(raw_value_expected())
     }
 
     fn serialize_u16(self, _v: u16) -> Result<()> {
-        Err(Error::KeyMustBeAString)
+        Err(raw_value_expected())
     }
 
     fn serialize_u32(self, _v: u32) -> Result<()> {
-        Err(Error::KeyMustBeAString)
+        Err(raw_value_expected())
     }
 
     fn serialize_u64(self, _v: u64) -> Result<()> {
-        Err(Error::KeyMustBeAString)
+        Err(raw_value_expected())
+    }
+
+    fn serialize_u128(self, _v: u128) -> Result<()> {
+        Err(raw_value_expected())
     }
 
     fn serialize_f32(self, _v: f32) -> Result<()> {
-        Err(Error::KeyMustBeAString)
+        Err(raw_value_expected())
     }
 
     fn serialize_f64(self, _v: f64) -> Result<()> {
-        Err(Error::KeyMustBeAString)
+        Err(raw_value_expected())
     }
 
     fn serialize_char(self, _v: char) -> Result<()> {
-        Err(Error::KeyMustBeAString)
+        Err(raw_value_expected())
     }
 
-    fn serialize_bytes(self, _v: &[u8]) -> Result<()> {
-        Err(Error::KeyMustBeAString)
+    fn serialize_str(self, _v: &str) -> Result<()> {
+        Err(raw_value_expected())
     }
 
     fn serialize_none(self) -> Result<()> {
-        Err(Error::KeyMustBeAString)
+        Err(raw_value_expected())
     }
 
-    fn serialize_some<T: ?Sized>(self, _v: &T) -> Result<()>
+    fn serialize_some<T>(self, _v: &T) -> Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        Err(Error::KeyMustBeAString)
+        Err(raw_value_expected())
     }
 
     fn serialize_unit(self) -> Result<()> {
-        Err(Error::KeyMustBeAString)
+        Err(raw_value_expected())
     }
 
     fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
-        Err(Error::KeyMustBeAString)
+        Err(raw_value_expected())
     }
 
     fn serialize_unit_variant(
@@ -581,17 +2103,17 @@ where
         _variant_index: u32,
         _variant: &'static str,
     ) -> Result<()> {
-        Err(Error::KeyMustBeAString)
+        Err(raw_value_expected())
     }
 
-    fn serialize_newtype_struct<T: ?Sized>(self, _name: &'static str, _value: &T) -> Result<()>
+    fn serialize_newtype_struct<T>(self, _name: &'static str, _value: &T) -> Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        Err(Error::KeyMustBeAString)
+        Err(raw_value_expected())
     }
 
-    fn serialize_newtype_variant<T: ?Sized>(
+    fn serialize_newtype_variant<T>(
         self,
         _name: &'static str,
         _variant_index: u32,
@@ -599,17 +2121,17 @@ where
         _value: &T,
     ) -> Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        Err(Error::KeyMustBeAString)
+        Err(raw_value_expected())
     }
 
     fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
-        Err(Error::KeyMustBeAString)
+        Err(raw_value_expected())
     }
 
     fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
-        Err(Error::KeyMustBeAString)
+        Err(raw_value_expected())
     }
 
     fn serialize_tuple_struct(
@@ -617,7 +2139,7 @@ where
         _name: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeTupleStruct> {
-        Err(Error::KeyMustBeAString)
+        Err(raw_value_expected())
     }
 
     fn serialize_tuple_variant(
@@ -627,15 +2149,15 @@ where
         _variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeTupleVariant> {
-        Err(Error::KeyMustBeAString)
+        Err(raw_value_expected())
     }
 
     fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
-        Err(Error::KeyMustBeAString)
+        Err(raw_value_expected())
     }
 
     fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
-        Err(Error::KeyMustBeAString)
+        Err(raw_value_expected())
     }
 
     fn serialize_struct_variant(
@@ -645,6 +2167,6 @@ where
         _variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeStructVariant> {
-        Err(Error::KeyMustBeAString)
+        Err(raw_value_expected())
     }
 }
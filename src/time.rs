@@ -0,0 +1,82 @@
+//! Helpers for serializing `std::time` types, for use with `#[serde(with = "...")]`.
+//!
+//! ```ignore
+//! #[derive(Serialize, Deserialize)]
+//! struct Sample {
+//!     #[serde(with = "serde_ubjson::time::system_time")]
+//!     at: SystemTime,
+//!     #[serde(with = "serde_ubjson::time::duration")]
+//!     took: Duration,
+//! }
+//! ```
+
+/// Serialize a `Duration` as a two-element array of whole seconds and the remaining nanoseconds.
+pub mod duration {
+    use std::time::Duration;
+
+    use serde::de::{self, Deserialize, Deserializer, Unexpected};
+    use serde::ser::{SerializeTuple, Serializer};
+
+    pub fn serialize<S>(d: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut tup = serializer.serialize_tuple(2)?;
+        tup.serialize_element(&d.as_secs())?;
+        tup.serialize_element(&d.subsec_nanos())?;
+        tup.end()
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let (secs, nanos) = <(u64, u32)>::deserialize(deserializer)?;
+        if nanos >= 1_000_000_000 {
+            return Err(de::Error::invalid_value(
+                Unexpected::Unsigned(u64::from(nanos)),
+                &"fewer than 1000000000 nanoseconds",
+            ));
+        }
+        Ok(Duration::new(secs, nanos))
+    }
+}
+
+/// Serialize a `SystemTime` as a signed integer count of nanoseconds since the Unix epoch. Times
+/// too far from the epoch for an `i64` become high-precision numbers.
+pub mod system_time {
+    use std::convert::TryFrom;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use serde::de::{self, Deserialize, Deserializer};
+    use serde::ser::{self, Serializer};
+
+    pub fn serialize<S>(t: &SystemTime, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let nanos = match t.duration_since(UNIX_EPOCH) {
+            Ok(d) => i128::try_from(d.as_nanos()),
+            Err(e) => i128::try_from(e.duration().as_nanos()).map(|n| -n),
+        };
+        let nanos = nanos.map_err(|_| ser::Error::custom("time is out of range"))?;
+        serializer.serialize_i128(nanos)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<SystemTime, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let nanos = i128::deserialize(deserializer)?;
+        let abs = nanos.unsigned_abs();
+        let t = u64::try_from(abs / 1_000_000_000).ok().and_then(|secs| {
+            let offset = Duration::new(secs, (abs % 1_000_000_000) as u32);
+            if nanos < 0 {
+                UNIX_EPOCH.checked_sub(offset)
+            } else {
+                UNIX_EPOCH.checked_add(offset)
+            }
+        });
+        t.ok_or_else(|| de::Error::custom(format_args!("timestamp {} is out of range", nanos)))
+    }
+}
@@ -0,0 +1,32 @@
+//! Tokens of the UBJSON event model, read by `UbjsonReader` and written by `UbjsonWriter`.
+
+/// A single event in a UBJSON document: a scalar value, or the start or end of a container.
+///
+/// Integers and floats are widened to 64 bits, so the width they were written with is not kept.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Token {
+    /// `Z`.
+    Null,
+    /// `T` or `F`.
+    Bool(bool),
+    /// Any of `i`, `U`, `I`, `l` and `L`.
+    Int(i64),
+    /// `d` or `D`.
+    Float(f64),
+    /// `H`, as its string of decimal digits.
+    HighPrecision(String),
+    /// `C`, which is always ASCII.
+    Char(char),
+    /// `S`.
+    Str(String),
+    /// `[`, along with the element type and count if the header gave them.
+    ArrayStart { len: Option<usize>, ty: Option<u8> },
+    /// The end of an array, whether or not a `]` was written for it.
+    ArrayEnd,
+    /// `{`, along with the value type and count if the header gave them.
+    ObjectStart { len: Option<usize>, ty: Option<u8> },
+    /// The key of the object entry whose value comes next.
+    Key(String),
+    /// The end of an object, whether or not a `}` was written for it.
+    ObjectEnd,
+}
@@ -0,0 +1,330 @@
+//! Read UBJSON from a `tokio::io::AsyncRead` without blocking the thread.
+//!
+//! The bytes of a value are collected as they arrive, reading no further than its end, and then
+//! decoded all at once. A stream of several values can be read by calling `AsyncReader::read`
+//! once for each. The same limits as a `Deserializer` apply, and are checked while the bytes are
+//! still being collected.
+
+use std::convert::TryFrom;
+
+use ::tokio::io::{AsyncRead, AsyncReadExt};
+use byteorder::{BigEndian, ByteOrder};
+use serde::de::{self, DeserializeOwned, Unexpected};
+
+use crate::de::{fixed_value_size, Deserializer, DEFAULT_MAX_DEPTH, DEFAULT_MAX_ZERO_WIDTH_COUNT};
+use crate::error::{Error, Result};
+use crate::marker;
+
+/// Deserialize an instance of type `T` from the next value read off an async stream, with the
+/// default limits of `AsyncReader`.
+///
+/// Leading no-ops are skipped, and nothing after the value is read.
+pub async fn from_async_reader<R, T>(reader: R) -> Result<T>
+where
+    R: AsyncRead + Unpin,
+    T: DeserializeOwned,
+{
+    AsyncReader::new(reader).read().await
+}
+
+/// Reads values off an async stream one at a time.
+pub struct AsyncReader<R> {
+    reader: R,
+    max_depth: usize,
+    max_length: usize,
+    max_zero_width_count: usize,
+}
+
+impl<R> AsyncReader<R>
+where
+    R: AsyncRead + Unpin,
+{
+    /// Creates a new reader over an async stream.
+    pub fn new(reader: R) -> Self {
+        AsyncReader {
+            reader,
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_length: usize::MAX,
+            max_zero_width_count: DEFAULT_MAX_ZERO_WIDTH_COUNT,
+        }
+    }
+
+    /// Sets how deeply arrays and objects may nest before reading fails with
+    /// `Error::DepthLimitExceeded`. Defaults to `DEFAULT_MAX_DEPTH`.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Sets the largest element count or string length that will be accepted before reading
+    /// fails with `Error::LengthLimitExceeded`. Unlimited by default.
+    pub fn max_length(mut self, length: usize) -> Self {
+        self.max_length = length;
+        self
+    }
+
+    /// Sets the largest count a strongly-typed array of `Z`, `T` or `F` may declare, as
+    /// `Deserializer::max_zero_width_count` does. Defaults to `DEFAULT_MAX_ZERO_WIDTH_COUNT`.
+    pub fn max_zero_width_count(mut self, count: usize) -> Self {
+        self.max_zero_width_count = count;
+        self
+    }
+
+    /// Unwraps the stream, positioned just after the last value read.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Deserialize an instance of type `T` from the next value in the stream.
+    ///
+    /// Leading no-ops are skipped, and nothing after the value is read.
+    pub async fn read<T>(&mut self) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        let mut scanner = Scanner {
+            reader: &mut self.reader,
+            bytes: Vec::new(),
+            peeked: None,
+            max_depth: self.max_depth,
+            max_length: self.max_length,
+            max_zero_width_count: self.max_zero_width_count,
+        };
+        scanner.scan_value().await?;
+        let mut de = Deserializer::from_slice(&scanner.bytes)
+            .max_depth(self.max_depth)
+            .max_length(self.max_length)
+            .max_zero_width_count(self.max_zero_width_count);
+        let value = T::deserialize(&mut de)?;
+        de.end()?;
+        Ok(value)
+    }
+}
+
+/// Where the scanner is in an open container.
+struct Frame {
+    keyed: bool,
+    ty: Option<u8>,
+    /// Entries left to read, if the container is counted.
+    remaining: Option<usize>,
+    /// Entries read so far, if the container is not counted.
+    read: usize,
+    /// Whether an object expects a key next, rather than a value.
+    at_key: bool,
+}
+
+impl Frame {
+    /// Accounts for the entry about to be read.
+    fn take_entry(&mut self, max_length: usize) -> Result<()> {
+        match self.remaining {
+            Some(ref mut remaining) => *remaining -= 1,
+            None if self.read >= max_length => return Err(Error::LengthLimitExceeded),
+            None => self.read += 1,
+        }
+        Ok(())
+    }
+}
+
+/// Reads the bytes of a single value, walking just enough of its structure to find its end.
+struct Scanner<R> {
+    reader: R,
+    /// Everything read so far.
+    bytes: Vec<u8>,
+    /// A byte already read into `bytes` but not yet looked at.
+    peeked: Option<u8>,
+    max_depth: usize,
+    max_length: usize,
+    max_zero_width_count: usize,
+}
+
+impl<R> Scanner<R>
+where
+    R: AsyncRead + Unpin,
+{
+    async fn scan_value(&mut self) -> Result<()> {
+        let mut stack: Vec<Frame> = Vec::new();
+        loop {
+            let m = match stack.last_mut() {
+                None => self.next_marker().await?,
+                Some(frame) => {
+                    if frame.remaining.is_none() && (!frame.keyed || frame.at_key) {
+                        let end = if frame.keyed {
+                            marker::OBJ_END
+                        } else {
+                            marker::ARR_END
+                        };
+                        let m = self.next_marker().await?;
+                        self.peeked = Some(m);
+                        if m == end {
+                            self.peeked = None;
+                            frame.remaining = Some(0);
+                        }
+                    }
+                    if frame.remaining == Some(0) && (!frame.keyed || frame.at_key) {
+                        stack.pop();
+                        if stack.is_empty() {
+                            return Ok(());
+                        }
+                        continue;
+                    }
+                    if frame.keyed && frame.at_key {
+                        frame.at_key = false;
+                        frame.take_entry(self.max_length)?;
+                        let len = self.read_length().await?;
+                        self.read_bytes(len).await?;
+                        continue;
+                    }
+                    if frame.keyed {
+                        frame.at_key = true;
+                    } else {
+                        frame.take_entry(self.max_length)?;
+                    }
+                    match frame.ty {
+                        Some(ty) => ty,
+                        None => self.next_marker().await?,
+                    }
+                }
+            };
+            match m {
+                marker::ARR_START | marker::OBJ_START => {
+                    if stack.len() >= self.max_depth {
+                        return Err(Error::DepthLimitExceeded);
+                    }
+                    let keyed = m == marker::OBJ_START;
+                    let (ty, len) = self.read_container_header().await?;
+                    if let (false, Some(size), Some(len)) =
+                        (keyed, ty.and_then(fixed_value_size), len)
+                    {
+                        // A counted array of fixed-size values is read in one go, and one of
+                        // `Z`, `T` or `F` without reading anything.
+                        if size == 0 && len > self.max_zero_width_count {
+                            return Err(Error::LengthLimitExceeded);
+                        }
+                        self.read_bytes(len.checked_mul(size).ok_or(Error::LengthOverflow)?)
+                            .await?;
+                        if stack.is_empty() {
+                            return Ok(());
+                        }
+                        continue;
+                    }
+                    stack.push(Frame {
+                        keyed,
+                        ty,
+                        remaining: len,
+                        read: 0,
+                        at_key: true,
+                    });
+                }
+                m => {
+                    self.read_scalar(m).await?;
+                    if stack.is_empty() {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Reads the rest of a value that is not a container, introduced by `m`.
+    async fn read_scalar(&mut self, m: u8) -> Result<()> {
+        let width = match m {
+            marker::NULL | marker::TRUE | marker::FALSE => 0,
+            marker::I8 | marker::U8 | marker::CHAR => 1,
+            marker::I16 => 2,
+            marker::I32 | marker::F32 => 4,
+            marker::I64 | marker::F64 => 8,
+            marker::STRING | marker::HI_PRECISION => self.read_length().await?,
+            _ => return Err(Error::InvalidMarker(m)),
+        };
+        self.read_bytes(width).await
+    }
+
+    /// Reads the optional `$type` and `#count` that follow a container's opening marker.
+    async fn read_container_header(&mut self) -> Result<(Option<u8>, Option<usize>)> {
+        let mut next = self.next_byte().await?;
+        let ty = if next == marker::TYPE {
+            let ty = match self.next_byte().await? {
+                m @ marker::NOOP
+                | m @ marker::ARR_END
+                | m @ marker::OBJ_END
+                | m @ marker::TYPE
+                | m @ marker::LENGTH => return Err(Error::InvalidMarker(m)),
+                m => m,
+            };
+            next = self.next_byte().await?;
+            Some(ty)
+        } else {
+            None
+        };
+        match next {
+            marker::LENGTH => Ok((ty, Some(self.read_length().await?))),
+            m if ty.is_some() => Err(Error::InvalidMarker(m)),
+            m => {
+                self.peeked = Some(m);
+                Ok((ty, None))
+            }
+        }
+    }
+
+    async fn read_length(&mut self) -> Result<usize> {
+        let m = self.next_byte().await?;
+        let width = match m {
+            marker::I8 | marker::U8 => 1,
+            marker::I16 => 2,
+            marker::I32 => 4,
+            marker::I64 => 8,
+            _ => return Err(Error::InvalidMarker(m)),
+        };
+        self.read_bytes(width).await?;
+        let payload = &self.bytes[self.bytes.len() - width..];
+        let len = match m {
+            marker::I8 => i64::from(payload[0] as i8),
+            marker::U8 => i64::from(payload[0]),
+            marker::I16 => i64::from(BigEndian::read_i16(payload)),
+            marker::I32 => i64::from(BigEndian::read_i32(payload)),
+            _ => BigEndian::read_i64(payload),
+        };
+        if len < 0 {
+            return Err(de::Error::invalid_value(
+                Unexpected::Signed(len),
+                &"a non-negative length",
+            ));
+        }
+        let len = usize::try_from(len).map_err(|_| Error::LengthOverflow)?;
+        if len > self.max_length {
+            return Err(Error::LengthLimitExceeded);
+        }
+        Ok(len)
+    }
+
+    /// Consumes and returns the marker of the next value, skipping no-ops.
+    async fn next_marker(&mut self) -> Result<u8> {
+        loop {
+            let m = self.next_byte().await?;
+            if m != marker::NOOP {
+                return Ok(m);
+            }
+        }
+    }
+
+    async fn next_byte(&mut self) -> Result<u8> {
+        if let Some(b) = self.peeked.take() {
+            return Ok(b);
+        }
+        self.read_bytes(1).await?;
+        Ok(self.bytes[self.bytes.len() - 1])
+    }
+
+    /// Reads exactly `len` more bytes.
+    async fn read_bytes(&mut self, len: usize) -> Result<()> {
+        // Read through `take` so that a bogus length cannot make us allocate it all up front.
+        let read = (&mut self.reader)
+            .take(len as u64)
+            .read_to_end(&mut self.bytes)
+            .await?;
+        if read < len {
+            return Err(Error::UnexpectedEof);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,25 @@
+//! Helpers for serializing a `Uuid` as its 16 raw bytes, for use with
+//! `#[serde(with = "serde_ubjson::uuid")]`.
+//!
+//! The bytes are written as a strongly-typed `U` array, `[$U#U\x10` followed by the bytes, rather
+//! than as the 36-character hyphenated string.
+
+use ::uuid::Uuid;
+use serde::de::Deserializer;
+use serde::ser::Serializer;
+
+use crate::byte_array;
+
+pub fn serialize<S>(uuid: &Uuid, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    byte_array::serialize(uuid.as_bytes(), serializer)
+}
+
+pub fn deserialize<'de, D>(deserializer: D) -> Result<Uuid, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    byte_array::deserialize(deserializer).map(Uuid::from_bytes)
+}
@@ -0,0 +1,546 @@
+//! A dynamically typed representation of any UBJSON value.
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::iter;
+use std::str::FromStr;
+
+use serde::de::value::{MapDeserializer, SeqDeserializer};
+use serde::de::{
+    self, Deserialize, DeserializeOwned, DeserializeSeed, IntoDeserializer, Unexpected, Visitor,
+};
+use serde::ser::{self, Serialize, SerializeMap, SerializeSeq};
+
+use crate::error::{Error, Result};
+
+/// Name of the newtype struct through which `Value::HighPrecision` is handed to the serializer.
+pub(crate) const HIGH_PRECISION_TOKEN: &str = "$serde_ubjson::private::HighPrecision";
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// Any UBJSON value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    /// Any integer that fits an `i64`, regardless of how narrowly it was encoded.
+    Int(i64),
+    Float(f64),
+    /// A number that fits no other variant, kept as its decimal representation.
+    HighPrecision(String),
+    Char(char),
+    String(String),
+    Array(Vec<Value>),
+    Object(BTreeMap<String, Value>),
+}
+
+/// Interpret a `Value` as an instance of type `T`.
+pub fn from_value<T>(value: Value) -> Result<T>
+where
+    T: DeserializeOwned,
+{
+    T::deserialize(value)
+}
+
+impl Value {
+    /// Returns the value as an `i64` if it is an integer, including a high-precision number that
+    /// happens to be an integer in range.
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            Value::Int(v) => Some(v),
+            Value::HighPrecision(ref v) => v.parse().ok(),
+            _ => None,
+        }
+    }
+
+    /// Returns the value as a `u64` if it is a non-negative integer, including a high-precision
+    /// number that happens to be an integer in range.
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            Value::Int(v) if v >= 0 => Some(v as u64),
+            Value::HighPrecision(ref v) => v.parse().ok(),
+            _ => None,
+        }
+    }
+
+    /// Returns the value as an `f64` if it is any kind of number. Integers and high-precision
+    /// numbers are rounded to the nearest `f64`.
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Value::Int(v) => Some(v as f64),
+            Value::Float(v) => Some(v),
+            Value::HighPrecision(ref v) => v.parse().ok(),
+            _ => None,
+        }
+    }
+
+    /// Parses a number into any type implementing `FromStr`, such as an arbitrary-precision
+    /// decimal type, by way of its decimal representation. Returns `None` if the value is not a
+    /// number.
+    pub fn parse_number<T>(&self) -> Option<std::result::Result<T, T::Err>>
+    where
+        T: FromStr,
+    {
+        match *self {
+            Value::Int(v) => Some(v.to_string().parse()),
+            Value::Float(v) => Some(v.to_string().parse()),
+            Value::HighPrecision(ref v) => Some(v.parse()),
+            _ => None,
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+impl Serialize for Value {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        match *self {
+            Value::Null => serializer.serialize_none(),
+            Value::Bool(v) => serializer.serialize_bool(v),
+            Value::Int(v) => serializer.serialize_i64(v),
+            Value::Float(v) => serializer.serialize_f64(v),
+            Value::HighPrecision(ref v) => {
+                serializer.serialize_newtype_struct(HIGH_PRECISION_TOKEN, v)
+            }
+            Value::Char(v) => serializer.serialize_char(v),
+            Value::String(ref v) => serializer.serialize_str(v),
+            Value::Array(ref v) => {
+                let mut seq = serializer.serialize_seq(Some(v.len()))?;
+                for element in v {
+                    seq.serialize_element(element)?;
+                }
+                seq.end()
+            }
+            Value::Object(ref v) => {
+                let mut map = serializer.serialize_map(Some(v.len()))?;
+                for (key, value) in v {
+                    map.serialize_entry(key, value)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("any UBJSON value")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> std::result::Result<Value, E> {
+        Ok(Value::Bool(v))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> std::result::Result<Value, E> {
+        Ok(Value::Int(v))
+    }
+
+    fn visit_i128<E>(self, v: i128) -> std::result::Result<Value, E> {
+        if (i128::from(i64::MIN) <= v) && (v <= i128::from(i64::MAX)) {
+            Ok(Value::Int(v as i64))
+        } else {
+            Ok(Value::HighPrecision(v.to_string()))
+        }
+    }
+
+    fn visit_u64<E>(self, v: u64) -> std::result::Result<Value, E> {
+        if v <= i64::MAX as u64 {
+            Ok(Value::Int(v as i64))
+        } else {
+            Ok(Value::HighPrecision(v.to_string()))
+        }
+    }
+
+    fn visit_u128<E>(self, v: u128) -> std::result::Result<Value, E> {
+        if v <= i64::MAX as u128 {
+            Ok(Value::Int(v as i64))
+        } else {
+            Ok(Value::HighPrecision(v.to_string()))
+        }
+    }
+
+    fn visit_f64<E>(self, v: f64) -> std::result::Result<Value, E> {
+        Ok(Value::Float(v))
+    }
+
+    fn visit_char<E>(self, v: char) -> std::result::Result<Value, E> {
+        Ok(Value::Char(v))
+    }
+
+    fn visit_str<E>(self, v: &str) -> std::result::Result<Value, E> {
+        Ok(Value::String(v.to_owned()))
+    }
+
+    fn visit_string<E>(self, v: String) -> std::result::Result<Value, E> {
+        Ok(Value::String(v))
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> std::result::Result<Value, E> {
+        Ok(Value::Array(
+            v.iter().map(|&b| Value::Int(i64::from(b))).collect(),
+        ))
+    }
+
+    fn visit_unit<E>(self) -> std::result::Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_none<E>(self) -> std::result::Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> std::result::Result<Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        Value::deserialize(deserializer)
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> std::result::Result<Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        Value::deserialize(deserializer)
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> std::result::Result<Value, A::Error>
+    where
+        A: de::SeqAccess<'de>,
+    {
+        let mut values = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(value) = seq.next_element()? {
+            values.push(value);
+        }
+        Ok(Value::Array(values))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> std::result::Result<Value, A::Error>
+    where
+        A: de::MapAccess<'de>,
+    {
+        let mut values = BTreeMap::new();
+        let first = map.next_key::<String>()?;
+        if first.as_deref() == Some(HIGH_PRECISION_TOKEN) {
+            return Ok(Value::HighPrecision(map.next_value()?));
+        }
+        if let Some(key) = first {
+            values.insert(key, map.next_value()?);
+        }
+        while let Some((key, value)) = map.next_entry()? {
+            values.insert(key, value);
+        }
+        Ok(Value::Object(values))
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+impl<'de> IntoDeserializer<'de, Error> for Value {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+/// Parses `Value::HighPrecision` into the requested numeric type, failing if it is out of range
+/// or, for integers, has a fractional part.
+macro_rules! deserialize_hi_precision {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value>
+            where
+                V: Visitor<'de>,
+            {
+                match self {
+                    Value::HighPrecision(v) => match v.parse() {
+                        Ok(n) => visitor.$visit(n),
+                        Err(_) => Err(de::Error::invalid_value(Unexpected::Other(&v), &visitor)),
+                    },
+                    value => value.deserialize_any(visitor),
+                }
+            }
+        )*
+    };
+}
+
+/// Converts `Value::Int` and `Value::HighPrecision` into the requested integer type, failing with
+/// `Error::IntegerOutOfRange` if they do not fit.
+macro_rules! deserialize_integer {
+    ($($method:ident => $ty:ty, $visit:ident,)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value>
+            where
+                V: Visitor<'de>,
+            {
+                match self {
+                    Value::Int(v) => match <$ty>::try_from(v) {
+                        Ok(n) => visitor.$visit(n),
+                        Err(_) => Err(Error::IntegerOutOfRange {
+                            target: stringify!($ty),
+                            value: i128::from(v),
+                        }),
+                    },
+                    Value::HighPrecision(v) => match (v.parse(), v.parse()) {
+                        (Ok(n), _) => visitor.$visit(n),
+                        (Err(_), Ok(value)) => Err(Error::IntegerOutOfRange {
+                            target: stringify!($ty),
+                            value,
+                        }),
+                        (Err(_), Err(_)) => {
+                            Err(de::Error::invalid_value(Unexpected::Other(&v), &visitor))
+                        }
+                    },
+                    value => value.deserialize_any(visitor),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Value {
+    type Error = Error;
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Null => visitor.visit_unit(),
+            Value::Bool(v) => visitor.visit_bool(v),
+            Value::Int(v) if v >= 0 => visitor.visit_u64(v as u64),
+            Value::Int(v) => visitor.visit_i64(v),
+            Value::Float(v) => visitor.visit_f64(v),
+            Value::HighPrecision(v) => {
+                let mut map =
+                    MapDeserializer::<_, Error>::new(iter::once((HIGH_PRECISION_TOKEN, v)));
+                let value = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(value)
+            }
+            Value::Char(v) => visitor.visit_char(v),
+            Value::String(v) => visitor.visit_string(v),
+            Value::Array(v) => {
+                let mut seq = SeqDeserializer::new(v.into_iter());
+                let value = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(value)
+            }
+            Value::Object(v) => {
+                let mut map = MapDeserializer::new(v.into_iter());
+                let value = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(value)
+            }
+        }
+    }
+
+    deserialize_integer! {
+        deserialize_i8 => i8, visit_i8,
+        deserialize_i16 => i16, visit_i16,
+        deserialize_i32 => i32, visit_i32,
+        deserialize_i64 => i64, visit_i64,
+        deserialize_i128 => i128, visit_i128,
+        deserialize_u8 => u8, visit_u8,
+        deserialize_u16 => u16, visit_u16,
+        deserialize_u32 => u32, visit_u32,
+        deserialize_u64 => u64, visit_u64,
+        deserialize_u128 => u128, visit_u128,
+    }
+
+    deserialize_hi_precision! {
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::HighPrecision(v) => visitor.visit_string(v),
+            value => value.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Null => visitor.visit_none(),
+            value => visitor.visit_some(value),
+        }
+    }
+
+    /// Accepts an empty array as well as a null, like the UBJSON deserializer does.
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Array(ref v) if v.is_empty() => visitor.visit_unit(),
+            value => value.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    /// Accepts the same shapes the serializer writes: a bare variant tag for unit variants, or an
+    /// array of the tag followed by the variant's fields.
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Array(v) => {
+                let mut iter = v.into_iter();
+                match iter.next() {
+                    Some(variant) => visitor.visit_enum(EnumDeserializer {
+                        variant,
+                        fields: Some(iter.collect()),
+                    }),
+                    None => Err(de::Error::invalid_length(0, &"a variant tag")),
+                }
+            }
+            variant => visitor.visit_enum(EnumDeserializer {
+                variant,
+                fields: None,
+            }),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool char bytes byte_buf seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+struct EnumDeserializer {
+    variant: Value,
+    /// Everything after the tag, if the variant came as an array.
+    fields: Option<Vec<Value>>,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumDeserializer {
+    type Error = Error;
+    type Variant = VariantDeserializer;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, VariantDeserializer)>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let variant = seed.deserialize(self.variant)?;
+        Ok((
+            variant,
+            VariantDeserializer {
+                fields: self.fields,
+            },
+        ))
+    }
+}
+
+struct VariantDeserializer {
+    fields: Option<Vec<Value>>,
+}
+
+impl<'de> de::VariantAccess<'de> for VariantDeserializer {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        match self.fields {
+            None => Ok(()),
+            Some(ref fields) if fields.is_empty() => Ok(()),
+            Some(_) => Err(de::Error::invalid_type(Unexpected::Seq, &"unit variant")),
+        }
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self.fields {
+            Some(mut fields) if fields.len() == 1 => seed.deserialize(fields.remove(0)),
+            Some(fields) => Err(de::Error::invalid_length(fields.len(), &"a single value")),
+            None => Err(de::Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"newtype variant",
+            )),
+        }
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.fields {
+            Some(fields) => de::Deserializer::deserialize_any(Value::Array(fields), visitor),
+            None => Err(de::Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"tuple variant",
+            )),
+        }
+    }
+
+    fn struct_variant<V>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.fields {
+            // Written with `StructRepresentation::Object`.
+            Some(mut fields) if fields.len() == 1 && matches!(fields[0], Value::Object(_)) => {
+                de::Deserializer::deserialize_any(fields.remove(0), visitor)
+            }
+            Some(fields) => de::Deserializer::deserialize_any(Value::Array(fields), visitor),
+            None => Err(de::Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"struct variant",
+            )),
+        }
+    }
+}
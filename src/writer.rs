@@ -0,0 +1,240 @@
+//! Write UBJSON from a stream of tokens, without serde.
+
+use std::io::Write;
+
+use byteorder::WriteBytesExt;
+use serde::ser::Error as _;
+
+use crate::error::{Error, Result};
+use crate::marker;
+use crate::ser::{integer_fits, integer_marker, stage_integer, write_header_with_length};
+use crate::token::Token;
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// Writes UBJSON one `Token` at a time, checking that the tokens make up well-formed values.
+///
+/// A container given a count in its start token must get exactly that many entries, and its end
+/// token writes nothing. In a strongly-typed container, every value must have the container's type,
+/// and is written without its marker. Integers are written with the smallest marker that can hold
+/// them, and floats as float64, unless a strongly-typed container says otherwise.
+pub struct UbjsonWriter<W> {
+    writer: W,
+    /// Containers that have been started but not ended, innermost last.
+    stack: Vec<Frame>,
+}
+
+/// Where the writer is in an open container.
+struct Frame {
+    keyed: bool,
+    ty: Option<u8>,
+    /// Entries left to write, if the container is counted.
+    remaining: Option<usize>,
+    /// Whether an object expects a key next, rather than a value.
+    at_key: bool,
+}
+
+impl<W> UbjsonWriter<W>
+where
+    W: Write,
+{
+    /// Creates a new writer over an IO stream.
+    pub fn new(writer: W) -> Self {
+        UbjsonWriter {
+            writer,
+            stack: Vec::new(),
+        }
+    }
+
+    /// Returns how many containers are currently open.
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// Returns the stream written to, failing if any container has been left open.
+    pub fn finish(self) -> Result<W> {
+        if !self.stack.is_empty() {
+            return Err(Error::custom("container left open"));
+        }
+        Ok(self.writer)
+    }
+
+    /// Writes the next token.
+    ///
+    /// Fails without writing anything if the token cannot come next, such as an end that does
+    /// not match the innermost container or a key outside of an object.
+    pub fn write_token(&mut self, token: &Token) -> Result<()> {
+        match *token {
+            Token::ArrayEnd | Token::ObjectEnd => {
+                let keyed = *token == Token::ObjectEnd;
+                let frame = match self.stack.last() {
+                    Some(frame) if frame.keyed == keyed => frame,
+                    _ => return Err(Error::custom("container end without a matching start")),
+                };
+                if keyed && !frame.at_key {
+                    return Err(Error::custom("object ended where a value was expected"));
+                }
+                match frame.remaining {
+                    Some(0) => {}
+                    Some(n) => {
+                        return Err(Error::custom(format_args!(
+                            "container ended with {} entries missing",
+                            n
+                        )))
+                    }
+                    None if keyed => self.writer.write_u8(marker::OBJ_END)?,
+                    None => self.writer.write_u8(marker::ARR_END)?,
+                }
+                self.stack.pop();
+                Ok(())
+            }
+            Token::Key(ref key) => {
+                let frame = match self.stack.last_mut() {
+                    Some(frame) if frame.keyed && frame.at_key => frame,
+                    Some(frame) if frame.keyed => {
+                        return Err(Error::custom("key where a value was expected"))
+                    }
+                    _ => return Err(Error::custom("key outside of an object")),
+                };
+                take_entry(frame)?;
+                frame.at_key = false;
+                write_header_with_length(&mut self.writer, &[], key.len() as u64)?;
+                self.writer.write_all(key.as_bytes())?;
+                Ok(())
+            }
+            _ => {
+                let ty = match self.stack.last() {
+                    None => None,
+                    Some(frame) if frame.keyed && frame.at_key => {
+                        return Err(Error::custom("value where a key was expected"))
+                    }
+                    Some(frame) if !frame.keyed && frame.remaining == Some(0) => {
+                        return Err(Error::custom("more entries than the container's count"))
+                    }
+                    Some(frame) => frame.ty,
+                };
+                let m = value_marker(token, ty)?;
+                if let Some(frame) = self.stack.last_mut() {
+                    if frame.keyed {
+                        frame.at_key = true;
+                    } else {
+                        take_entry(frame)?;
+                    }
+                }
+                self.write_value(token, m, ty.is_some())
+            }
+        }
+    }
+
+    /// Writes a value with the marker `m`, which is left out if it is implied by the enclosing
+    /// container.
+    ///
+    /// Everything up to a string's bytes or a container's first entry goes to the stream in a
+    /// single call.
+    fn write_value(&mut self, token: &Token, m: u8, implied: bool) -> Result<()> {
+        let mut buf = [m; 9];
+        let start = if implied { 1 } else { 0 };
+        match *token {
+            Token::Int(v) => {
+                let end = 1 + stage_integer(&mut buf[1..], m, v);
+                self.writer.write_all(&buf[start..end])?;
+            }
+            Token::Float(v) if m == marker::F32 => {
+                buf[1..5].copy_from_slice(&(v as f32).to_be_bytes());
+                self.writer.write_all(&buf[start..5])?;
+            }
+            Token::Float(v) => {
+                buf[1..9].copy_from_slice(&v.to_be_bytes());
+                self.writer.write_all(&buf[start..9])?;
+            }
+            Token::HighPrecision(ref s) | Token::Str(ref s) => {
+                write_header_with_length(&mut self.writer, &buf[start..1], s.len() as u64)?;
+                self.writer.write_all(s.as_bytes())?;
+            }
+            Token::Char(c) => {
+                buf[1] = c as u8;
+                self.writer.write_all(&buf[start..2])?;
+            }
+            Token::ArrayStart { len, ty } | Token::ObjectStart { len, ty } => {
+                let mut end = 1;
+                if let Some(ty) = ty {
+                    buf[end..end + 2].copy_from_slice(&[marker::TYPE, ty]);
+                    end += 2;
+                }
+                match len {
+                    Some(len) => {
+                        buf[end] = marker::LENGTH;
+                        end += 1;
+                        write_header_with_length(&mut self.writer, &buf[start..end], len as u64)?;
+                    }
+                    None => self.writer.write_all(&buf[start..end])?,
+                }
+                self.stack.push(Frame {
+                    keyed: m == marker::OBJ_START,
+                    ty,
+                    remaining: len,
+                    at_key: true,
+                });
+            }
+            _ => self.writer.write_all(&buf[start..1])?,
+        }
+        Ok(())
+    }
+}
+
+/// Picks the marker a value token is written with, checking it against `ty`, the type of the
+/// enclosing container.
+fn value_marker(token: &Token, ty: Option<u8>) -> Result<u8> {
+    let m = match *token {
+        Token::Null => marker::NULL,
+        Token::Bool(true) => marker::TRUE,
+        Token::Bool(false) => marker::FALSE,
+        Token::Int(v) => match ty {
+            Some(ty) if integer_fits(v, ty) => ty,
+            _ => integer_marker(v),
+        },
+        Token::Float(_) if ty == Some(marker::F32) => marker::F32,
+        Token::Float(_) => marker::F64,
+        Token::HighPrecision(_) => marker::HI_PRECISION,
+        Token::Char(c) if c.is_ascii() => marker::CHAR,
+        Token::Char(_) => return Err(Error::InvalidChar),
+        Token::Str(_) => marker::STRING,
+        Token::ArrayStart { len, ty } | Token::ObjectStart { len, ty } => {
+            match ty {
+                Some(m @ marker::NOOP)
+                | Some(m @ marker::ARR_END)
+                | Some(m @ marker::OBJ_END)
+                | Some(m @ marker::TYPE)
+                | Some(m @ marker::LENGTH) => return Err(Error::InvalidMarker(m)),
+                Some(_) if len.is_none() => {
+                    return Err(Error::custom("strongly-typed container needs a count"))
+                }
+                _ => {}
+            }
+            if let Token::ArrayStart { .. } = *token {
+                marker::ARR_START
+            } else {
+                marker::OBJ_START
+            }
+        }
+        Token::ArrayEnd | Token::ObjectEnd | Token::Key(_) => {
+            return Err(Error::custom("not a value"))
+        }
+    };
+    match ty {
+        Some(ty) if ty != m => Err(Error::custom("value does not match the container's type")),
+        _ => Ok(m),
+    }
+}
+
+/// Counts an entry against a counted container, failing if it is already full.
+fn take_entry(frame: &mut Frame) -> Result<()> {
+    match frame.remaining {
+        Some(0) => Err(Error::custom("more entries than the container's count")),
+        Some(ref mut n) => {
+            *n -= 1;
+            Ok(())
+        }
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,44 @@
+#![cfg(feature = "chrono")]
+
+extern crate chrono;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_ubjson;
+
+use chrono::{DateTime, TimeZone, Utc};
+use serde_ubjson::{from_slice, to_vec};
+
+#[test]
+fn chrono_rfc3339() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Event {
+        #[serde(with = "serde_ubjson::chrono::rfc3339")]
+        at: DateTime<Utc>,
+    }
+
+    let event = Event {
+        at: Utc.with_ymd_and_hms(2020, 1, 2, 3, 4, 5).unwrap(),
+    };
+    let bytes = to_vec(&event).unwrap();
+    assert_eq!(bytes, b"[#U\x01SU\x142020-01-02T03:04:05Z");
+    assert_eq!(from_slice::<Event>(&bytes).unwrap(), event);
+
+    assert!(from_slice::<Event>(b"[#U\x01SU\x03now").is_err());
+}
+
+#[test]
+fn chrono_ts_seconds() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Event {
+        #[serde(with = "serde_ubjson::chrono::ts_seconds")]
+        at: DateTime<Utc>,
+    }
+
+    let event = Event {
+        at: Utc.with_ymd_and_hms(2020, 1, 2, 3, 4, 5).unwrap(),
+    };
+    let bytes = to_vec(&event).unwrap();
+    assert_eq!(bytes, b"[#U\x01l\x5e\x0d\x5d\xa5");
+    assert_eq!(from_slice::<Event>(&bytes).unwrap(), event);
+}
@@ -0,0 +1,1111 @@
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_bytes;
+extern crate serde_ubjson;
+
+use serde::de::Deserialize;
+use serde::ser::Serialize;
+use serde_ubjson::{from_slice, Deserializer, Error, Serializer, Value};
+
+#[test]
+fn deserialize_scalars() {
+    assert!(from_slice::<bool>(b"T").unwrap());
+    assert_eq!(from_slice::<i8>(b"i\x80").unwrap(), i8::MIN);
+    assert_eq!(from_slice::<i16>(b"I\x01\x00").unwrap(), 256);
+    assert_eq!(
+        from_slice::<i64>(b"L\x7f\xff\xff\xff\xff\xff\xff\xff").unwrap(),
+        i64::MAX
+    );
+    assert_eq!(
+        from_slice::<f64>(b"D\x40\x09\x21\xfb\x54\x44\x2d\x18").unwrap(),
+        std::f64::consts::PI
+    );
+    assert_eq!(from_slice::<char>(b"CA").unwrap(), 'A');
+    assert_eq!(from_slice::<&str>(b"SU\x05hello").unwrap(), "hello");
+}
+
+#[test]
+fn deserialize_containers() {
+    assert_eq!(from_slice::<Vec<u8>>(b"[U\x01U\x02]").unwrap(), vec![1, 2]);
+    assert_eq!(
+        from_slice::<Vec<u8>>(b"[#U\x02U\x01U\x02").unwrap(),
+        vec![1, 2]
+    );
+    assert_eq!(
+        from_slice::<Vec<u8>>(b"[$U#U\x02\x01\x02").unwrap(),
+        vec![1, 2]
+    );
+    assert_eq!(
+        from_slice::<Vec<u8>>(b"[NU\x01NU\x02N]").unwrap(),
+        vec![1, 2]
+    );
+
+    let map: std::collections::BTreeMap<String, i32> =
+        from_slice(b"{U\x01ai\x01U\x01bi\x02}").unwrap();
+    assert_eq!(map.len(), 2);
+    assert_eq!(map["a"], 1);
+    assert_eq!(map["b"], 2);
+}
+
+#[test]
+fn deserialize_truncated() {
+    match from_slice::<i32>(b"l\x00\x00") {
+        Err(Error::UnexpectedEof) => {}
+        other => panic!("expected UnexpectedEof, got {:?}", other),
+    }
+    match from_slice::<Vec<u8>>(b"[U\x01") {
+        Err(Error::UnexpectedEof) => {}
+        other => panic!("expected UnexpectedEof, got {:?}", other),
+    }
+}
+
+#[test]
+fn depth_limit() {
+    let mut nested = vec![b'['; 4];
+    nested.extend_from_slice(&[b']'; 4]);
+
+    let mut de = Deserializer::from_slice(&nested).max_depth(4);
+    assert!(Vec::<Vec<Vec<Vec<()>>>>::deserialize(&mut de).is_ok());
+
+    let mut de = Deserializer::from_slice(&nested).max_depth(3);
+    match Vec::<Vec<Vec<Vec<()>>>>::deserialize(&mut de) {
+        Err(Error::DepthLimitExceeded) => {}
+        other => panic!("expected DepthLimitExceeded, got {:?}", other),
+    }
+
+    let deep = vec![b'['; 100_000];
+    match from_slice::<serde::de::IgnoredAny>(&deep) {
+        Err(Error::DepthLimitExceeded) => {}
+        other => panic!("expected DepthLimitExceeded, got {:?}", other),
+    }
+}
+
+#[test]
+fn length_limit() {
+    let mut de = Deserializer::from_slice(b"[#U\x03U\x01U\x02U\x03").max_length(2);
+    match Vec::<u8>::deserialize(&mut de) {
+        Err(Error::LengthLimitExceeded) => {}
+        other => panic!("expected LengthLimitExceeded, got {:?}", other),
+    }
+
+    let mut de = Deserializer::from_slice(b"[U\x01U\x02U\x03]").max_length(2);
+    match Vec::<u8>::deserialize(&mut de) {
+        Err(Error::LengthLimitExceeded) => {}
+        other => panic!("expected LengthLimitExceeded, got {:?}", other),
+    }
+
+    let mut de = Deserializer::from_slice(b"SU\x05hello").max_length(4);
+    match String::deserialize(&mut de) {
+        Err(Error::LengthLimitExceeded) => {}
+        other => panic!("expected LengthLimitExceeded, got {:?}", other),
+    }
+
+    // A huge declared count must not be trusted for pre-allocation.
+    match from_slice::<Vec<u8>>(b"[#L\x00\x00\x00\x00\x7f\xff\xff\xffU\x01") {
+        Err(Error::UnexpectedEof) => {}
+        other => panic!("expected UnexpectedEof, got {:?}", other),
+    }
+}
+
+#[test]
+#[cfg(target_pointer_width = "32")]
+fn length_overflow() {
+    match from_slice::<Vec<u8>>(b"[#L\x00\x00\x00\x01\x00\x00\x00\x00") {
+        Err(Error::LengthOverflow) => {}
+        other => panic!("expected LengthOverflow, got {:?}", other),
+    }
+}
+
+#[test]
+fn oversized_count() {
+    // Eight-byte elements with a count of 2^31 - 1 in a 20-byte payload.
+    match from_slice::<Vec<f64>>(b"[$D#l\x7f\xff\xff\xffD\x00\x00\x00\x00\x00\x00\x00\x00") {
+        Err(Error::UnexpectedEof) => {}
+        other => panic!("expected UnexpectedEof, got {:?}", other),
+    }
+    match from_slice::<Vec<u8>>(b"[#L\x7f\xff\xff\xff\xff\xff\xff\xff") {
+        Err(Error::UnexpectedEof) => {}
+        other => panic!("expected UnexpectedEof, got {:?}", other),
+    }
+    match from_slice::<std::collections::BTreeMap<String, u8>>(b"{#U\x02U\x01aU\x01") {
+        Err(Error::UnexpectedEof) => {}
+        other => panic!("expected UnexpectedEof, got {:?}", other),
+    }
+}
+
+#[test]
+fn deserialize_option() {
+    assert_eq!(from_slice::<Option<u8>>(b"Z").unwrap(), None);
+    assert_eq!(from_slice::<Option<u8>>(b"U\x05").unwrap(), Some(5));
+    assert_eq!(
+        from_slice::<Vec<Option<&str>>>(b"[#U\x02ZSU\x01a").unwrap(),
+        vec![None, Some("a")]
+    );
+    assert_eq!(
+        from_slice::<Vec<Option<i8>>>(b"[$i#U\x02\x01\x02").unwrap(),
+        vec![Some(1), Some(2)]
+    );
+
+    // `()` and `None` share the null encoding, so decoding picks `None`.
+    let unit = serde_ubjson::to_vec(&Some(())).unwrap();
+    assert_eq!(unit, b"Z");
+    assert_eq!(from_slice::<Option<()>>(&unit).unwrap(), None);
+}
+
+#[test]
+fn require_canonical_integers() {
+    fn strict<'de, T: Deserialize<'de>>(input: &'de [u8]) -> Result<T, Error> {
+        T::deserialize(&mut Deserializer::from_slice(input).require_canonical_integers(true))
+    }
+
+    assert_eq!(strict::<i64>(b"i\x05").unwrap(), 5);
+    assert_eq!(strict::<i64>(b"U\xc8").unwrap(), 200);
+    assert_eq!(strict::<i64>(b"I\xff\x7f").unwrap(), -129);
+    assert_eq!(strict::<i64>(b"l\x00\x00\x80\x00").unwrap(), 32768);
+    for input in &[
+        &b"l\x00\x00\x00\x05"[..],
+        b"I\x00\xc8",
+        b"L\x00\x00\x00\x00\x00\x00\x80\x00",
+        // Lengths must be canonical too.
+        b"SI\x00\x02hi",
+    ] {
+        match strict::<serde::de::IgnoredAny>(input) {
+            Err(Error::NonCanonical) => {}
+            other => panic!("expected NonCanonical, got {:?}", other),
+        }
+        assert!(strict::<serde_ubjson::Value>(input).is_err());
+        from_slice::<serde::de::IgnoredAny>(input).unwrap();
+    }
+
+    // The values of a strongly-typed container share its marker, however small they are.
+    assert_eq!(
+        strict::<Vec<i32>>(b"[$l#U\x02\x00\x00\x00\x01\x00\x01\x00\x00").unwrap(),
+        vec![1, 65536]
+    );
+    assert!(strict::<serde::de::IgnoredAny>(b"[l\x00\x00\x00\x01]").is_err());
+    assert_eq!(strict::<char>(b"CA").unwrap(), 'A');
+    assert!(strict::<char>(b"I\x00\x41").is_err());
+}
+
+#[test]
+fn deserialize_f32_from_f64() {
+    fn strict(input: &[u8]) -> serde_ubjson::Result<f32> {
+        f32::deserialize(&mut Deserializer::from_slice(input).require_exact_f32(true))
+    }
+
+    let half = b"D\x3f\xe0\x00\x00\x00\x00\x00\x00";
+    let tenth = b"D\x3f\xb9\x99\x99\x99\x99\x99\x9a";
+    assert_eq!(from_slice::<f32>(b"d\x3f\x00\x00\x00").unwrap(), 0.5);
+    assert_eq!(from_slice::<f32>(half).unwrap(), 0.5);
+    assert_eq!(from_slice::<f32>(tenth).unwrap(), 0.1);
+    assert_eq!(
+        from_slice::<Vec<f32>>(b"[$D#U\x01\x3f\xe0\x00\x00\x00\x00\x00\x00").unwrap(),
+        vec![0.5]
+    );
+
+    assert_eq!(strict(half).unwrap(), 0.5);
+    assert!(strict(b"D\x7f\xf8\x00\x00\x00\x00\x00\x00")
+        .unwrap()
+        .is_nan());
+    assert!(matches!(strict(tenth), Err(Error::InexactFloat(v)) if v == 0.1));
+}
+
+#[test]
+fn deserialize_bytes() {
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Blob<'a> {
+        borrowed: &'a [u8],
+        #[serde(with = "serde_bytes")]
+        owned: Vec<u8>,
+    }
+
+    let input = b"[#U\x02[$U#U\x03\x01\x02\x03[$U#U\x02\xfe\xff";
+    let blob: Blob = from_slice(input).unwrap();
+    assert_eq!(blob.borrowed, b"\x01\x02\x03");
+    assert_eq!(blob.borrowed.as_ptr(), input[10..].as_ptr());
+    assert_eq!(blob.owned, b"\xfe\xff");
+
+    // Arrays of any other form are still read one element at a time.
+    let bytes: serde_bytes::ByteBuf = from_slice(b"[U\x01i\x02]").unwrap();
+    assert_eq!(Vec::from(bytes), b"\x01\x02");
+    let bytes: serde_bytes::ByteBuf = from_slice(b"[$i#U\x01\x03").unwrap();
+    assert_eq!(Vec::from(bytes), b"\x03");
+    assert!(from_slice::<serde_bytes::ByteBuf>(b"[$U#U\x03\x01").is_err());
+}
+
+#[test]
+fn faithful_options() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Patch {
+        name: Option<Option<String>>,
+        tags: Vec<Option<Option<u8>>>,
+    }
+
+    for &(optimize, open) in &[(false, false), (true, false), (false, true)] {
+        let patch = Patch {
+            name: Some(None),
+            tags: vec![None, Some(None), Some(Some(3)), Some(None)],
+        };
+        let mut buf = Vec::new();
+        patch
+            .serialize(
+                &mut Serializer::new(&mut buf)
+                    .faithful_options(true)
+                    .optimize_seqs(optimize)
+                    .always_unsized_containers(open),
+            )
+            .unwrap();
+        let mut de = Deserializer::from_slice(&buf).faithful_options(true);
+        assert_eq!(Patch::deserialize(&mut de).unwrap(), patch);
+    }
+
+    let mut de = Deserializer::from_slice(b"[#U\x01[#U\x01Z").faithful_options(true);
+    assert_eq!(
+        Option::<Option<Option<u8>>>::deserialize(&mut de).unwrap(),
+        Some(Some(None))
+    );
+    // The elements of a strongly-typed array of `T`, `F` or `Z` take up no input, so each must be
+    // read as its own `Some` rather than as the inner value of the one before.
+    for value in &[vec![Some(true); 3], vec![Some(false); 3], vec![None; 3]] {
+        let mut buf = Vec::new();
+        value
+            .serialize(
+                &mut Serializer::new(&mut buf)
+                    .faithful_options(true)
+                    .optimize_seqs(true),
+            )
+            .unwrap();
+        let mut de = Deserializer::from_slice(&buf).faithful_options(true);
+        assert_eq!(&Vec::<Option<bool>>::deserialize(&mut de).unwrap(), value);
+    }
+    let mut de = Deserializer::from_slice(b"[$T#U\x03").faithful_options(true);
+    assert_eq!(
+        Vec::<Option<bool>>::deserialize(&mut de).unwrap(),
+        vec![Some(true); 3]
+    );
+
+    // Without the wrapping array, the inner option cannot be told apart.
+    let mut de = Deserializer::from_slice(b"U\x05").faithful_options(true);
+    assert!(Option::<Option<u8>>::deserialize(&mut de).is_err());
+    assert_eq!(from_slice::<Option<Option<u8>>>(b"Z").unwrap(), None);
+}
+
+#[test]
+fn constant_typed_arrays() {
+    assert_eq!(from_slice::<Vec<()>>(b"[$Z#U\x03").unwrap(), vec![(); 3]);
+    assert_eq!(
+        from_slice::<Vec<Option<u8>>>(b"[$Z#U\x02").unwrap(),
+        vec![None, None]
+    );
+    assert_eq!(
+        from_slice::<Vec<bool>>(b"[$T#U\x03").unwrap(),
+        vec![true; 3]
+    );
+    assert_eq!(
+        from_slice::<(bool, bool)>(b"[$F#U\x02").unwrap(),
+        (false, false)
+    );
+    assert_eq!(
+        from_slice::<Value>(b"[$T#U\x02").unwrap(),
+        Value::Array(vec![Value::Bool(true), Value::Bool(true)])
+    );
+    assert!(from_slice::<Vec<bool>>(b"[$Z#U\x01").is_err());
+
+    let mut de =
+        Deserializer::from_slice(b"[$T#L\x00\x00\x01\x00\x00\x00\x00\x00").max_length(1000);
+    assert!(matches!(
+        Vec::<bool>::deserialize(&mut de),
+        Err(Error::LengthLimitExceeded)
+    ));
+}
+
+#[test]
+fn zero_width_count_limit() {
+    // A few bytes declaring nearly 2^63 elements that take up no input must fail fast.
+    for input in &[
+        &b"[$Z#L\x7f\xff\xff\xff\xff\xff\xff\xff"[..],
+        b"[$Z#L\x00\x00\x00\x7f\xff\xff\xff\xff",
+    ] {
+        for result in &[
+            from_slice::<Vec<()>>(input).map(drop),
+            from_slice::<Vec<Option<()>>>(input).map(drop),
+            from_slice::<Value>(input).map(drop),
+            from_slice::<serde::de::IgnoredAny>(input).map(drop),
+            serde_ubjson::debug::to_block_notation(input).map(drop),
+        ] {
+            assert!(matches!(result, Err(Error::LengthLimitExceeded)));
+        }
+    }
+    assert!(matches!(
+        from_slice::<Vec<bool>>(b"[$T#l\x00\x10\x00\x01"),
+        Err(Error::LengthLimitExceeded)
+    ));
+
+    let input = b"[$T#l\x00\x10\x00\x00";
+    assert_eq!(from_slice::<Vec<bool>>(input).unwrap().len(), 1 << 20);
+    let mut de = Deserializer::from_slice(input).max_zero_width_count(10);
+    assert!(matches!(
+        Vec::<bool>::deserialize(&mut de),
+        Err(Error::LengthLimitExceeded)
+    ));
+}
+
+#[test]
+fn mixed_container_forms() {
+    use std::collections::BTreeMap;
+
+    // A typed byte array inside an unsized object.
+    let blobs: BTreeMap<String, serde_bytes::ByteBuf> =
+        from_slice(b"{U\x01a[$U#U\x02\x01\x02U\x01b[$U#U\x00}").unwrap();
+    assert_eq!(&blobs["a"][..], [1, 2]);
+    assert!(blobs["b"].is_empty());
+    // A typed array of objects, each counted or unsized.
+    let rows: Vec<BTreeMap<String, u8>> =
+        from_slice(b"[${#U\x02#U\x01U\x01xU\x01U\x01yU\x02}").unwrap();
+    assert_eq!(rows.len(), 2);
+    assert_eq!((rows[0]["x"], rows[1]["y"]), (1, 2));
+    // A typed object whose values are typed arrays.
+    let columns: BTreeMap<String, Vec<i16>> =
+        from_slice(b"{$[#U\x02U\x01a$I#U\x01\x01\x2cU\x01b#U\x02U\x01i\xff").unwrap();
+    assert_eq!(columns["a"], [300]);
+    assert_eq!(columns["b"], [1, -1]);
+    // Counted tuples inside an unsized array.
+    let pairs: Vec<(u8, bool)> = from_slice(b"[[#U\x02U\x01T[U\x02F]]").unwrap();
+    assert_eq!(pairs, [(1, true), (2, false)]);
+}
+
+#[test]
+fn empty_tuples() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Empty();
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Marker(Empty);
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Signal {
+        Ping(),
+        Mark(Marker),
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Frame {
+        marker: Marker,
+        nothing: [u8; 0],
+        nested: Vec<Vec<Vec<u8>>>,
+        signals: Vec<Signal>,
+    }
+
+    let frame = Frame {
+        marker: Marker(Empty()),
+        nothing: [],
+        nested: vec![vec![vec![]], vec![]],
+        signals: vec![Signal::Ping(), Signal::Mark(Marker(Empty()))],
+    };
+    let cases: &[(bool, &[u8])] = &[
+        (
+            false,
+            b"[#U\x04[#U\x00[#U\x00[#U\x02[#U\x01[#U\x00[#U\x00\
+              [#U\x02[#U\x01U\x00[#U\x02U\x01[#U\x00",
+        ),
+        (true, b"[[][][[[]][]][[U\x00][U\x01[]]]]"),
+    ];
+    for &(open, bytes) in cases {
+        let mut buf = Vec::new();
+        frame
+            .serialize(&mut Serializer::new(&mut buf).always_unsized_containers(open))
+            .unwrap();
+        assert_eq!(buf, bytes);
+        assert_eq!(from_slice::<Frame>(bytes).unwrap(), frame);
+        let value: Value = from_slice(bytes).unwrap();
+        assert_eq!(serde_ubjson::from_value::<Frame>(value).unwrap(), frame);
+    }
+
+    // Any empty array will do, typed ones included.
+    for input in &[&b"[]"[..], b"[#U\x00", b"[$U#U\x00", b"[$Z#I\x00\x00"] {
+        assert_eq!(from_slice::<Empty>(input).unwrap(), Empty());
+        assert_eq!(from_slice::<[u8; 0]>(input).unwrap(), [0u8; 0]);
+    }
+    assert!(from_slice::<Empty>(b"[#U\x01Z").is_err());
+}
+
+#[test]
+fn zero_sized_types() {
+    use serde_ubjson::ser::StructRepresentation;
+    use std::marker::PhantomData;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Marker;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Tagged<T> {
+        id: u8,
+        kind: PhantomData<T>,
+        marker: Marker,
+        next: u8,
+    }
+
+    let value = Tagged::<String> {
+        id: 1,
+        kind: PhantomData,
+        marker: Marker,
+        next: 2,
+    };
+    let cases: &[(StructRepresentation, &[u8])] = &[
+        (StructRepresentation::Array, b"[#U\x04U\x01ZZU\x02"),
+        (
+            StructRepresentation::Object,
+            b"{#U\x04U\x02idU\x01U\x04kindZU\x06markerZU\x04nextU\x02",
+        ),
+    ];
+    for &(repr, bytes) in cases {
+        let mut buf = Vec::new();
+        value
+            .serialize(&mut Serializer::new(&mut buf).struct_representation(repr))
+            .unwrap();
+        assert_eq!(buf, bytes);
+        assert_eq!(from_slice::<Tagged<String>>(bytes).unwrap(), value);
+    }
+
+    // Under `faithful_options`, a `Some` holding a zero-sized value is wrapped like one holding
+    // `None`, and reads back without leaving the wrapper behind.
+    let value = (Some(PhantomData::<u8>), Some(()), 5u8);
+    for &open in &[false, true] {
+        let mut buf = Vec::new();
+        value
+            .serialize(
+                &mut Serializer::new(&mut buf)
+                    .faithful_options(true)
+                    .always_unsized_containers(open),
+            )
+            .unwrap();
+        let mut de = Deserializer::from_slice(&buf).faithful_options(true);
+        let back = <(Option<PhantomData<u8>>, Option<()>, u8)>::deserialize(&mut de).unwrap();
+        assert_eq!(back, value);
+        de.end().unwrap();
+    }
+
+    // The same goes for sequences of them, which `optimize_seqs` writes as strongly-typed arrays.
+    let units = vec![Some(()); 3];
+    let phantoms = vec![Some(PhantomData::<u8>); 3];
+    for &(open, empty_array) in &[(false, false), (true, false), (false, true), (true, true)] {
+        let mut buf = Vec::new();
+        (&units, &phantoms)
+            .serialize(
+                &mut Serializer::new(&mut buf)
+                    .faithful_options(true)
+                    .optimize_seqs(true)
+                    .always_unsized_containers(open)
+                    .unit_as_empty_array(empty_array),
+            )
+            .unwrap();
+        let mut de = Deserializer::from_slice(&buf).faithful_options(true);
+        let back = <(Vec<Option<()>>, Vec<Option<PhantomData<u8>>>)>::deserialize(&mut de).unwrap();
+        assert_eq!(back, (units.clone(), phantoms.clone()));
+        de.end().unwrap();
+    }
+    let mut de = Deserializer::from_slice(b"[$Z#U\x03").faithful_options(true);
+    assert_eq!(
+        Vec::<Option<()>>::deserialize(&mut de).unwrap(),
+        vec![None; 3]
+    );
+    let mut de = Deserializer::from_slice(b"[$[#U\x03]]]").faithful_options(true);
+    assert_eq!(
+        Vec::<Option<PhantomData<u8>>>::deserialize(&mut de).unwrap(),
+        phantoms
+    );
+}
+
+#[test]
+fn deserialize_newtype_struct() {
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Id(u64);
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Ids(Vec<u8>);
+
+    assert_eq!(from_slice::<Id>(b"I\x01\x2c").unwrap(), Id(300));
+    assert_eq!(
+        from_slice::<Ids>(b"[#U\x02U\x01U\x02").unwrap(),
+        Ids(vec![1, 2])
+    );
+}
+
+#[test]
+fn deserialize_integers_of_any_width() {
+    for input in &[
+        &b"i\x05"[..],
+        b"U\x05",
+        b"I\x00\x05",
+        b"l\x00\x00\x00\x05",
+        b"L\x00\x00\x00\x00\x00\x00\x00\x05",
+        b"HU\x015",
+    ] {
+        assert_eq!(from_slice::<i8>(input).unwrap(), 5);
+        assert_eq!(from_slice::<u16>(input).unwrap(), 5);
+        assert_eq!(from_slice::<i64>(input).unwrap(), 5);
+        assert_eq!(from_slice::<u64>(input).unwrap(), 5);
+    }
+    assert_eq!(from_slice::<i16>(b"U\xff").unwrap(), 255);
+    assert_eq!(
+        from_slice::<u32>(b"l\x7f\xff\xff\xff").unwrap(),
+        i32::MAX as u32
+    );
+    assert_eq!(
+        from_slice::<u64>(b"HU\x1418446744073709551615").unwrap(),
+        u64::MAX
+    );
+
+    assert!(from_slice::<u8>(b"i\xff").is_err());
+    assert!(from_slice::<i8>(b"U\xc8").is_err());
+    assert!(from_slice::<u16>(b"l\x00\x01\x00\x00").is_err());
+    assert!(from_slice::<i32>(b"L\x00\x00\x00\x01\x00\x00\x00\x00").is_err());
+    assert!(from_slice::<u64>(b"L\xff\xff\xff\xff\xff\xff\xff\xff").is_err());
+
+    // A field that the serializer narrowed still reads back at its declared width.
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Counters {
+        total: i64,
+        failures: u32,
+    }
+    let counters = Counters {
+        total: 200,
+        failures: 0,
+    };
+    let buf = serde_ubjson::to_vec(&counters).unwrap();
+    assert_eq!(buf, b"[#U\x02U\xc8U\x00");
+    assert_eq!(from_slice::<Counters>(&buf).unwrap(), counters);
+}
+
+#[test]
+fn integer_out_of_range() {
+    match from_slice::<u8>(b"i\xff") {
+        Err(Error::IntegerOutOfRange {
+            target: "u8",
+            value: -1,
+        }) => {}
+        other => panic!("expected IntegerOutOfRange, got {:?}", other),
+    }
+    match from_slice::<i32>(b"L\x00\x00\x00\x01\x00\x00\x00\x00") {
+        Err(Error::IntegerOutOfRange {
+            target: "i32",
+            value: 0x1_0000_0000,
+        }) => {}
+        other => panic!("expected IntegerOutOfRange, got {:?}", other),
+    }
+    match from_slice::<i64>(b"HU\x1418446744073709551615") {
+        Err(Error::IntegerOutOfRange {
+            target: "i64",
+            value,
+        }) if value == i128::from(u64::MAX) => {}
+        other => panic!("expected IntegerOutOfRange, got {:?}", other),
+    }
+    assert_eq!(
+        from_slice::<i8>(b"U\xff").unwrap_err().to_string(),
+        "integer 255 does not fit in i8"
+    );
+    // A number that is not an integer at all is still an invalid value.
+    assert!(from_slice::<u64>(b"HU\x031.5").is_err());
+}
+
+#[test]
+fn deserialize_unit() {
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Marker;
+
+    for input in &[&b"Z"[..], b"[]", b"[#U\x00", b"[$Z#U\x00"] {
+        from_slice::<()>(input).unwrap();
+        assert_eq!(from_slice::<Marker>(input).unwrap(), Marker);
+    }
+    assert!(from_slice::<()>(b"[Z]").is_err());
+    assert!(from_slice::<()>(b"[#U\x01Z").is_err());
+
+    // Written with `unit_as_empty_array`, `Some(())` stays apart from `None`.
+    assert_eq!(from_slice::<Option<()>>(b"[]").unwrap(), Some(()));
+    assert_eq!(
+        from_slice::<Vec<Option<()>>>(b"[#U\x02Z[]").unwrap(),
+        vec![None, Some(())]
+    );
+    let value: serde_ubjson::Value = from_slice(b"[]").unwrap();
+    serde_ubjson::from_value::<()>(value).unwrap();
+}
+
+#[test]
+fn deserialize_enum() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum E {
+        Unit,
+        Newtype(String),
+        Tuple(u8, i64),
+        Struct { a: bool, b: Option<char> },
+    }
+
+    let values = vec![
+        E::Unit,
+        E::Newtype("x".to_owned()),
+        E::Tuple(1, -1),
+        E::Struct { a: true, b: None },
+        E::Unit,
+    ];
+    let bytes = serde_ubjson::to_vec(&values).unwrap();
+    assert_eq!(from_slice::<Vec<E>>(&bytes).unwrap(), values);
+
+    assert_eq!(from_slice::<E>(b"U\x00").unwrap(), E::Unit);
+    assert_eq!(
+        from_slice::<E>(b"[U\x01SU\x01x]").unwrap(),
+        E::Newtype("x".to_owned())
+    );
+    assert!(from_slice::<E>(b"U\x04").is_err());
+    assert!(from_slice::<E>(b"U\x01").is_err());
+    assert!(from_slice::<E>(b"[#U\x03U\x01SU\x01xZ").is_err());
+}
+
+#[test]
+fn deserialize_enum_by_name() {
+    use serde_ubjson::ser::EnumRepresentation;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum E {
+        Unit,
+        Newtype(String),
+        Struct { a: bool },
+    }
+
+    let values = vec![E::Unit, E::Newtype("x".to_owned()), E::Struct { a: true }];
+    let mut bytes = Vec::new();
+    let mut ser =
+        serde_ubjson::Serializer::new(&mut bytes).enum_representation(EnumRepresentation::Name);
+    serde::Serialize::serialize(&values, &mut ser).unwrap();
+    assert_eq!(from_slice::<Vec<E>>(&bytes).unwrap(), values);
+}
+
+#[test]
+fn deserialize_struct_object_skip_and_flatten() {
+    use serde_ubjson::ser::StructRepresentation;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Inner {
+        b: u8,
+        #[serde(skip_serializing_if = "Option::is_none", default)]
+        c: Option<u8>,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Envelope {
+        version: u8,
+        #[serde(flatten)]
+        inner: Inner,
+    }
+
+    for value in [
+        Envelope {
+            version: 1,
+            inner: Inner { b: 2, c: None },
+        },
+        Envelope {
+            version: 1,
+            inner: Inner { b: 2, c: Some(3) },
+        },
+    ] {
+        let mut bytes = Vec::new();
+        let mut ser = serde_ubjson::Serializer::new(&mut bytes)
+            .struct_representation(StructRepresentation::Object);
+        serde::Serialize::serialize(&value, &mut ser).unwrap();
+        assert_eq!(from_slice::<Envelope>(&bytes).unwrap(), value);
+    }
+}
+
+#[test]
+fn deserialize_bool() {
+    assert!(from_slice::<bool>(b"T").unwrap());
+    assert!(!from_slice::<bool>(b"F").unwrap());
+    assert_eq!(
+        from_slice::<Vec<bool>>(b"[TNF]").unwrap(),
+        vec![true, false]
+    );
+    for input in &[&b"U\x01"[..], b"i\x00", b"Z", b"SU\x04true"] {
+        match from_slice::<bool>(input) {
+            Err(Error::InvalidMarker(m)) => assert_eq!(m, input[0]),
+            other => panic!("expected InvalidMarker, got {:?}", other),
+        }
+    }
+}
+
+#[test]
+fn deserialize_char() {
+    assert_eq!(from_slice::<char>(b"CA").unwrap(), 'A');
+    assert_eq!(from_slice::<char>(b"SU\x02\xc3\x80").unwrap(), 'À');
+    assert_eq!(from_slice::<char>(b"SU\x03\xea\xb0\x80").unwrap(), '가');
+    for &c in &['A', 'À', '가', '\u{10ffff}'] {
+        let bytes = serde_ubjson::to_vec(&c).unwrap();
+        assert_eq!(from_slice::<char>(&bytes).unwrap(), c);
+    }
+    for input in &[
+        &b"C\x80"[..],
+        b"SU\x00",
+        b"SU\x02ab",
+        b"l\x00\x00\xd8\x00",
+        b"i\xff",
+    ] {
+        match from_slice::<char>(input) {
+            Err(Error::InvalidChar) => {}
+            other => panic!("expected InvalidChar, got {:?}", other),
+        }
+    }
+}
+
+#[test]
+fn deny_duplicate_keys() {
+    use std::collections::BTreeMap;
+
+    let input = b"{U\x01ai\x01U\x01bi\x02U\x01ai\x03}";
+    let map: BTreeMap<String, i8> = from_slice(input).unwrap();
+    assert_eq!(map["a"], 3);
+
+    let mut de = Deserializer::from_slice(input).deny_duplicate_keys(true);
+    match BTreeMap::<String, i8>::deserialize(&mut de) {
+        Err(Error::DuplicateKey(ref key)) if key == "a" => {}
+        other => panic!("expected DuplicateKey, got {:?}", other),
+    }
+
+    // Keys only need to be unique within their own object.
+    let input = b"[#U\x02{#U\x01U\x01ai\x01{#U\x01U\x01ai\x02";
+    let mut de = Deserializer::from_slice(input).deny_duplicate_keys(true);
+    assert_eq!(
+        Vec::<BTreeMap<String, i8>>::deserialize(&mut de)
+            .unwrap()
+            .len(),
+        2
+    );
+}
+
+#[test]
+fn variant_and_tuple_share_layout() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum E {
+        Unit,
+        Newtype(String),
+    }
+
+    let variant = serde_ubjson::to_vec(&E::Newtype("x".to_owned())).unwrap();
+    let tuple = serde_ubjson::to_vec(&(1u8, "x")).unwrap();
+    assert_eq!(variant, tuple);
+    assert_eq!(from_slice::<E>(&tuple).unwrap(), E::Newtype("x".to_owned()));
+    assert_eq!(from_slice::<(u8, &str)>(&variant).unwrap(), (1, "x"));
+}
+
+#[test]
+fn skip_unknown_fields() {
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Old {
+        a: u8,
+        z: bool,
+    }
+
+    // Unknown fields of every shape, including typed and nested containers, are skipped.
+    let input = b"{U\x01aU\x01\
+        U\x01bSU\x03xyz\
+        U\x01c[$D#U\x02\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\
+        U\x01d{$[#U\x01U\x01k$U#U\x01\x07\
+        U\x01e[N[Zi\x01]HU\x0212]\
+        U\x01zT}";
+    assert_eq!(from_slice::<Old>(input).unwrap(), Old { a: 1, z: true });
+
+    use serde::de::IgnoredAny;
+    assert!(from_slice::<IgnoredAny>(b"[#U\x02[]{}").is_ok());
+    for input in &[&b"[$l#U\x02\x00\x00\x00\x00"[..], b"SU\x05abc", b"{U\x01a"] {
+        match from_slice::<IgnoredAny>(input) {
+            Err(Error::UnexpectedEof) => {}
+            other => panic!("expected UnexpectedEof, got {:?}", other),
+        }
+    }
+}
+
+#[test]
+fn deny_unknown_fields() {
+    use std::collections::BTreeMap;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Config {
+        retries: u8,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    enum Command {
+        Retry { retries: u8 },
+    }
+
+    let input = b"{U\x07retriesU\x03U\x07retiresT}";
+    assert_eq!(from_slice::<Config>(input).unwrap(), Config { retries: 3 });
+
+    let mut de = Deserializer::from_slice(input).deny_unknown_fields(true);
+    match Config::deserialize(&mut de) {
+        Err(Error::UnknownField(ref key)) if key == "retires" => {}
+        other => panic!("expected UnknownField, got {:?}", other),
+    }
+
+    let input = b"[U\x00{U\x07retriesU\x03U\x01xZ}]";
+    assert_eq!(
+        from_slice::<Command>(input).unwrap(),
+        Command::Retry { retries: 3 }
+    );
+    let mut de = Deserializer::from_slice(input).deny_unknown_fields(true);
+    assert!(matches!(
+        Command::deserialize(&mut de),
+        Err(Error::UnknownField(_))
+    ));
+
+    // Maps and positional structs have no field names to check.
+    let mut de = Deserializer::from_slice(b"{U\x01xZ}").deny_unknown_fields(true);
+    assert_eq!(
+        BTreeMap::<String, ()>::deserialize(&mut de).unwrap().len(),
+        1
+    );
+    let mut de = Deserializer::from_slice(b"[U\x03]").deny_unknown_fields(true);
+    assert_eq!(Config::deserialize(&mut de).unwrap(), Config { retries: 3 });
+}
+
+#[test]
+fn not_human_readable() {
+    struct Probe(bool);
+
+    impl<'de> Deserialize<'de> for Probe {
+        fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let readable = deserializer.is_human_readable();
+            serde::de::IgnoredAny::deserialize(deserializer)?;
+            Ok(Probe(readable))
+        }
+    }
+
+    assert!(!from_slice::<Probe>(b"Z").unwrap().0);
+    assert!(
+        !serde_ubjson::from_value::<Probe>(serde_ubjson::Value::Null)
+            .unwrap()
+            .0
+    );
+
+    let mut de = Deserializer::from_slice(b"Z").human_readable(true);
+    assert!(Probe::deserialize(&mut de).unwrap().0);
+}
+
+#[test]
+fn deserialize_hi_precision() {
+    assert_eq!(from_slice::<u64>(b"HU\x0218").unwrap(), 18);
+    assert_eq!(from_slice::<i64>(b"HU\x03-18").unwrap(), -18);
+    assert_eq!(from_slice::<f64>(b"HU\x061.5e-3").unwrap(), 0.0015);
+    assert_eq!(from_slice::<&str>(b"HU\x061.5e-3").unwrap(), "1.5e-3");
+    assert_eq!(
+        from_slice::<u128>(b"HU\x1418446744073709551616").unwrap(),
+        1 << 64
+    );
+
+    let max = serde_ubjson::to_vec(&u64::MAX).unwrap();
+    assert_eq!(max[0], b'H');
+    assert_eq!(from_slice::<u64>(&max).unwrap(), u64::MAX);
+    assert_eq!(from_slice::<f64>(&max).unwrap(), u64::MAX as f64);
+
+    for input in &[&b"HU\x031.5"[..], b"HU\x03256", b"HU\x03abc"] {
+        assert!(from_slice::<u8>(input).is_err());
+    }
+    assert!(from_slice::<i64>(b"HU\x1418446744073709551616").is_err());
+}
+
+#[test]
+fn stream_deserializer() {
+    let input = b"U\x01NNi\xffU\x02N";
+    let mut stream = Deserializer::from_slice(input).into_stream::<i8>();
+    assert_eq!(stream.size_hint(), (0, Some(input.len())));
+    assert_eq!(stream.next().unwrap().unwrap(), 1);
+    assert_eq!(stream.byte_offset(), 2);
+    assert_eq!(stream.next().unwrap().unwrap(), -1);
+    assert_eq!(stream.next().unwrap().unwrap(), 2);
+    assert!(stream.next().is_none());
+    assert_eq!(stream.byte_offset(), input.len());
+
+    // A value that fails to decode ends the stream, leaving the offset at its start.
+    let input = b"SU\x01aSU\x02b\xffSU\x01c";
+    let mut stream = Deserializer::from_slice(input).into_stream::<String>();
+    assert_eq!(stream.next().unwrap().unwrap(), "a");
+    assert!(stream.next().unwrap().is_err());
+    assert_eq!(stream.byte_offset(), 4);
+    assert!(stream.next().is_none());
+    assert!(stream.next().is_none());
+    assert_eq!(stream.size_hint(), (0, Some(0)));
+
+    let empty = Deserializer::from_slice(b"NN").into_stream::<u8>();
+    assert_eq!(empty.count(), 0);
+}
+
+#[test]
+fn trailing_bytes() {
+    use serde_ubjson::from_slice_partial;
+
+    assert_eq!(from_slice::<u8>(b"U\x01NN").unwrap(), 1);
+    assert!(matches!(
+        from_slice::<u8>(b"U\x01NU\x02"),
+        Err(Error::TrailingBytes { remaining: 2 })
+    ));
+
+    let (first, rest) = from_slice_partial::<u8>(b"U\x01U\x02").unwrap();
+    assert_eq!((first, rest), (1, &b"U\x02"[..]));
+    let (second, rest) = from_slice_partial::<u8>(rest).unwrap();
+    assert_eq!((second, rest), (2, &b""[..]));
+
+    let mut de = Deserializer::from_slice(b"TNF");
+    assert!(bool::deserialize(&mut de).unwrap());
+    assert!(matches!(
+        de.end(),
+        Err(Error::TrailingBytes { remaining: 1 })
+    ));
+    assert!(!bool::deserialize(&mut de).unwrap());
+    de.end().unwrap();
+}
+
+#[test]
+fn deserialize_tuple_arity() {
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Pair(u8, bool);
+
+    assert_eq!(
+        from_slice::<(u8, bool)>(b"[#U\x02U\x01T").unwrap(),
+        (1, true)
+    );
+    assert_eq!(from_slice::<Pair>(b"[U\x01T]").unwrap(), Pair(1, true));
+    assert_eq!(
+        from_slice::<[i8; 3]>(b"[$i#U\x03\x01\x02\x03").unwrap(),
+        [1, 2, 3]
+    );
+
+    // A count that disagrees with the arity fails before any element is read.
+    for &(input, found) in &[(&b"[#U\x03U\x01T"[..], 3), (b"[#U\x01U\x01", 1)] {
+        let mismatch =
+            |err| matches!(err, Error::LengthMismatch { expected: 2, found: f } if f == found);
+        assert!(mismatch(from_slice::<(u8, bool)>(input).unwrap_err()));
+        assert!(mismatch(from_slice::<Pair>(input).unwrap_err()));
+    }
+    assert!(matches!(
+        from_slice::<[i8; 3]>(b"[$i#U\x04\x01\x02\x03\x04"),
+        Err(Error::LengthMismatch {
+            expected: 3,
+            found: 4
+        })
+    ));
+    assert!(from_slice::<Pair>(b"[U\x01TZ]").is_err());
+    assert!(from_slice::<Pair>(b"[U\x01]").is_err());
+}
+
+#[test]
+fn deserialize_struct_arity() {
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Point {
+        x: i8,
+        y: i8,
+    }
+
+    assert_eq!(
+        from_slice::<Point>(b"[#U\x02i\x01i\x02").unwrap(),
+        Point { x: 1, y: 2 }
+    );
+    assert_eq!(
+        from_slice::<Point>(b"{U\x01yi\x02U\x01xi\x01}").unwrap(),
+        Point { x: 1, y: 2 }
+    );
+    assert!(matches!(
+        from_slice::<Point>(b"[#U\x03i\x01i\x02i\x03"),
+        Err(Error::LengthMismatch {
+            expected: 2,
+            found: 3
+        })
+    ));
+    assert_eq!(
+        Error::LengthMismatch {
+            expected: 2,
+            found: 3
+        }
+        .to_string(),
+        "expected a container of 2 elements, found 3"
+    );
+}
+
+#[test]
+fn marker_names() {
+    use serde_ubjson::marker::{self, marker_name};
+
+    assert_eq!(marker_name(marker::I16), Some("int16"));
+    assert_eq!(
+        marker_name(marker::HI_PRECISION),
+        Some("high-precision number")
+    );
+    assert_eq!(marker_name(b'x'), None);
+
+    let err = from_slice::<bool>(b"SU\x01a").unwrap_err();
+    assert_eq!(err.to_string(), "invalid marker 'S' (string)");
+    let err = from_slice::<u8>(b"x").unwrap_err();
+    assert_eq!(err.to_string(), "invalid marker 'x'");
+}
+
+#[test]
+fn error_is_send_sync() {
+    fn assert_send_sync<T: Send + Sync + 'static>() {}
+
+    assert_send_sync::<Error>();
+    let err: Box<dyn std::error::Error + Send + Sync> =
+        Box::new(from_slice::<u8>(b"").unwrap_err());
+    assert_eq!(err.to_string(), "unexpected end of input");
+}
+
+#[test]
+fn deserialize_struct_either_representation() {
+    use serde::Serialize;
+    use serde_ubjson::ser::StructRepresentation;
+    use serde_ubjson::Serializer;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Config {
+        name: String,
+        retries: u8,
+    }
+
+    let config = Config {
+        name: "a".to_owned(),
+        retries: 3,
+    };
+    for &repr in &[StructRepresentation::Array, StructRepresentation::Object] {
+        for &unsized_containers in &[false, true] {
+            let mut buf = Vec::new();
+            let mut ser = Serializer::new(&mut buf)
+                .struct_representation(repr)
+                .always_unsized_containers(unsized_containers);
+            config.serialize(&mut ser).unwrap();
+            assert_eq!(from_slice::<Config>(&buf).unwrap(), config);
+        }
+    }
+
+    // Keys may come in any order.
+    assert_eq!(
+        from_slice::<Config>(b"{#U\x02U\x07retriesU\x03U\x04nameSU\x01a").unwrap(),
+        config
+    );
+}
+
+#[test]
+fn deserialize_empty_maps_and_structs() {
+    use std::collections::HashMap;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Empty {}
+
+    for input in &[&b"{#U\x00"[..], b"{}", b"{$U#U\x00"] {
+        assert!(from_slice::<HashMap<String, u8>>(input).unwrap().is_empty());
+        assert_eq!(from_slice::<Empty>(input).unwrap(), Empty {});
+    }
+    assert_eq!(from_slice::<Empty>(b"[#U\x00").unwrap(), Empty {});
+    assert_eq!(from_slice::<Empty>(b"[]").unwrap(), Empty {});
+
+    // A counted empty object has no closing `}`, so what follows is the next value.
+    assert_eq!(
+        from_slice::<(HashMap<String, u8>, bool)>(b"[#U\x02{#U\x00T").unwrap(),
+        (HashMap::new(), true)
+    );
+    assert!(from_slice::<(HashMap<String, u8>, bool)>(b"[#U\x02{#U\x00}T").is_err());
+}
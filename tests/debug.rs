@@ -0,0 +1,68 @@
+extern crate serde_ubjson;
+
+use serde_ubjson::debug::to_block_notation;
+use serde_ubjson::Error;
+
+#[test]
+fn block_notation_scalars() {
+    assert_eq!(
+        to_block_notation(b"SU\x05hello").unwrap(),
+        "[S][U][5][hello]"
+    );
+    assert_eq!(to_block_notation(b"I\x01\x00").unwrap(), "[I][256]");
+    assert_eq!(to_block_notation(b"d\x3f\xc0\x00\x00").unwrap(), "[d][1.5]");
+    assert_eq!(
+        to_block_notation(b"CaZTF").unwrap(),
+        "[C][a]\n[Z]\n[T]\n[F]"
+    );
+    assert_eq!(
+        to_block_notation(b"HU\x139223372036854775808").unwrap(),
+        "[H][U][19][9223372036854775808]"
+    );
+}
+
+#[test]
+fn block_notation_containers() {
+    assert_eq!(
+        to_block_notation(b"{U\x03latd\x41\xf0\x00\x00U\x04tags[SU\x01aN]}").unwrap(),
+        "[{]\n    [U][3][lat][d][30]\n    [U][4][tags][[]\n        [S][U][1][a]\n        [N]\n    []]\n[}]"
+    );
+    assert_eq!(
+        to_block_notation(b"[$U#U\x03\x01\x02\x03").unwrap(),
+        "[[][$][U][#][U][3]\n    [1]\n    [2]\n    [3]"
+    );
+    assert_eq!(to_block_notation(b"[]").unwrap(), "[[]\n[]]");
+
+    // Implied markers stay in where nothing else would show the element.
+    assert_eq!(
+        to_block_notation(b"[$T#U\x03").unwrap(),
+        "[[][$][T][#][U][3]\n    [T]\n    [T]\n    [T]"
+    );
+    assert_eq!(
+        to_block_notation(b"[$[#U\x02]#U\x01Z").unwrap(),
+        "[[][$][[][#][U][2]\n    [[]\n    []]\n    [[][#][U][1]\n        [Z]"
+    );
+
+    // No-ops inside a container are shown but not counted as elements.
+    assert_eq!(
+        to_block_notation(b"[#U\x01Ni\x05").unwrap(),
+        "[[][#][U][1]\n    [N]\n    [i][5]"
+    );
+    assert_eq!(
+        to_block_notation(b"{U\x01aNTN}").unwrap(),
+        "[{]\n    [U][1][a][N][T]\n    [N]\n[}]"
+    );
+}
+
+#[test]
+fn block_notation_malformed() {
+    assert!(to_block_notation(b"[U\x01").is_err());
+    assert!(to_block_notation(b"SU\x05hel").is_err());
+    assert!(to_block_notation(b"[$U\x01").is_err());
+    assert!(to_block_notation(b"X").is_err());
+    assert!(to_block_notation(b"[$[#U\x02[]").is_err());
+    assert!(matches!(
+        to_block_notation(b"[$N#U\x01"),
+        Err(Error::InvalidMarker(b'N'))
+    ));
+}
@@ -0,0 +1,83 @@
+#![cfg(feature = "error_path")]
+
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_ubjson;
+
+use std::collections::BTreeMap;
+
+use serde_ubjson::{Error, Serializer};
+
+#[derive(Serialize)]
+struct Endpoint {
+    url: &'static str,
+    headers: BTreeMap<(u8, u8), &'static str>,
+}
+
+#[derive(Serialize)]
+struct Config {
+    endpoints: Vec<Endpoint>,
+}
+
+#[derive(Serialize)]
+struct Root {
+    config: Config,
+}
+
+fn root() -> Root {
+    let endpoint = |headers| Endpoint { url: "x", headers };
+    let mut bad = BTreeMap::new();
+    bad.insert((1, 2), "y");
+    Root {
+        config: Config {
+            endpoints: vec![
+                endpoint(BTreeMap::new()),
+                endpoint(BTreeMap::new()),
+                endpoint(bad),
+            ],
+        },
+    }
+}
+
+#[test]
+fn error_path() {
+    let err = serde_ubjson::to_vec(&root()).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "error at `config.endpoints[2].headers`: key must be a string, found tuple"
+    );
+    match err {
+        Error::WithPath { path, error } => {
+            assert_eq!(path, "config.endpoints[2].headers");
+            assert!(matches!(*error, Error::KeyMustBeAString("tuple")));
+        }
+        err => panic!("expected a path, got {:?}", err),
+    }
+
+    // Map values are named by their keys, held back for sorting or not.
+    let mut map = BTreeMap::new();
+    map.insert("a", vec![1.0]);
+    map.insert("b", vec![1.0, f64::NAN]);
+    for &sorted in &[false, true] {
+        let mut ser = Serializer::new(Vec::new())
+            .non_finite_float(serde_ubjson::ser::NonFiniteFloat::Error)
+            .sort_map_keys(sorted)
+            .optimize_seqs(sorted);
+        let err = serde::Serialize::serialize(&map, &mut ser).unwrap_err();
+        assert_eq!(err.to_string(), "error at `b[1]`: float must be finite");
+    }
+
+    // Errors at the top level, or from the writer, have no path.
+    let mut bad = BTreeMap::new();
+    bad.insert((1, 2), "y");
+    assert!(matches!(
+        serde_ubjson::to_vec(&bad),
+        Err(Error::KeyMustBeAString("tuple"))
+    ));
+    let mut buf = [0; 4];
+    assert!(matches!(
+        serde_ubjson::to_slice(&vec![1u8; 10], &mut buf),
+        Err(Error::BufferFull)
+    ));
+}
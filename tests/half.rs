@@ -0,0 +1,47 @@
+#![cfg(feature = "half")]
+
+extern crate half;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_ubjson;
+
+use half::f16;
+use serde_ubjson::{from_slice, from_value, to_vec, Value};
+
+#[test]
+fn half_as_f32() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Vertex {
+        #[serde(with = "serde_ubjson::half::as_f32")]
+        x: f16,
+    }
+
+    for &x in &[f16::from_f32(1.5), f16::MIN_POSITIVE_SUBNORMAL, f16::MAX] {
+        let v = Vertex { x };
+        let bytes = to_vec(&v).unwrap();
+        let mut expected = b"[#U\x01d".to_vec();
+        expected.extend_from_slice(&x.to_f32().to_be_bytes());
+        assert_eq!(bytes, expected);
+        assert_eq!(from_slice::<Vertex>(&bytes).unwrap(), v);
+    }
+}
+
+#[test]
+fn half_as_hi_precision() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Vertex {
+        #[serde(with = "serde_ubjson::half::as_hi_precision")]
+        x: f16,
+    }
+
+    let v = Vertex {
+        x: f16::from_f32(-0.099975586),
+    };
+    assert_eq!(to_vec(&v).unwrap(), b"[#U\x01HU\x0c-0.099975586");
+    let value = Value::Array(vec![Value::HighPrecision("-0.099975586".to_owned())]);
+    assert_eq!(from_value::<Vertex>(value).unwrap(), v);
+    assert_eq!(from_slice::<Vertex>(&to_vec(&v).unwrap()).unwrap(), v);
+
+    assert!(to_vec(&Vertex { x: f16::NAN }).is_err());
+}
@@ -0,0 +1,41 @@
+#![cfg(feature = "indexmap")]
+
+extern crate indexmap;
+extern crate serde;
+extern crate serde_ubjson;
+
+use indexmap::IndexMap;
+use serde::Serialize;
+use serde_ubjson::{from_slice, to_vec, Serializer};
+
+fn keys<V>(map: &IndexMap<String, V>) -> Vec<&str> {
+    map.keys().map(String::as_str).collect()
+}
+
+#[test]
+fn indexmap_keeps_wire_order() {
+    // Counted, unsized and strongly-typed objects all hand over their keys in the order written.
+    for input in &[
+        &b"{#U\x03U\x01zU\x01U\x01aU\x02U\x01mU\x03"[..],
+        b"{U\x01zU\x01U\x01aU\x02U\x01mU\x03}",
+        b"{$U#U\x03U\x01z\x01U\x01a\x02U\x01m\x03",
+    ] {
+        let map: IndexMap<String, u8> = from_slice(input).unwrap();
+        assert_eq!(keys(&map), ["z", "a", "m"]);
+        assert_eq!(map.values().cloned().collect::<Vec<_>>(), [1, 2, 3]);
+    }
+}
+
+#[test]
+fn indexmap_reserializes_identically() {
+    let input = b"{#U\x03U\x01zi\x01U\x01aSU\x02hiU\x01m[#U\x01Z";
+    let map: IndexMap<String, serde_ubjson::Value> = from_slice(input).unwrap();
+    assert_eq!(to_vec(&map).unwrap(), &input[..]);
+
+    // Sorting keys on the way out gives up the original order.
+    let mut buf = Vec::new();
+    map.serialize(&mut Serializer::new(&mut buf).sort_map_keys(true))
+        .unwrap();
+    let sorted: IndexMap<String, serde_ubjson::Value> = from_slice(&buf).unwrap();
+    assert_eq!(keys(&sorted), ["a", "m", "z"]);
+}
@@ -0,0 +1,83 @@
+#![cfg(feature = "serde_json")]
+
+extern crate serde_ubjson;
+
+use std::io;
+
+use serde_ubjson::json::{json_to_ubjson, ubjson_to_json};
+use serde_ubjson::Error;
+
+fn to_json(input: &[u8]) -> String {
+    let mut out = Vec::new();
+    ubjson_to_json(input, &mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+fn to_ubjson(input: &str) -> Vec<u8> {
+    let mut out = Vec::new();
+    json_to_ubjson(input.as_bytes(), &mut out).unwrap();
+    out
+}
+
+#[test]
+fn ubjson_to_json_values() {
+    assert_eq!(
+        to_json(b"{U\x01a[i\x01d\x3f\xc0\x00\x00CxZ]U\x02b\"{#U\x01U\x01cT}"),
+        r#"{"a":[1,1.5,"x",null],"b\"":{"c":true}}"#
+    );
+    assert_eq!(to_json(b"[$U#U\x03\x01\x02\x03"), "[1,2,3]");
+    assert_eq!(to_json(b"[]{}[[]]"), "[]\n{}\n[[]]");
+    assert_eq!(
+        to_json(b"HU\x1612345678901234567890.5"),
+        "12345678901234567890.5"
+    );
+    assert_eq!(to_json(b"D\x7f\xf0\x00\x00\x00\x00\x00\x00"), "null");
+    assert_eq!(to_json(b""), "");
+
+    let mut out = Vec::new();
+    assert!(ubjson_to_json(&b"HU\x021]"[..], &mut out).is_err());
+    assert!(ubjson_to_json(&b"[U\x01"[..], &mut out).is_err());
+
+    // A count alone cannot make it write a `null` for each of billions of elements.
+    let mut out = Vec::new();
+    assert!(matches!(
+        ubjson_to_json(&b"[$Z#l\x7f\xff\xff\xff"[..], &mut out),
+        Err(Error::LengthLimitExceeded)
+    ));
+    assert!(out.is_empty());
+}
+
+#[test]
+fn json_to_ubjson_values() {
+    assert_eq!(
+        to_ubjson(r#"{"a": [1, -200, 70000, 1.5, "x", null], "b": {"c": true}}"#),
+        &b"{U\x01a[i\x01I\xff\x38l\x00\x01\x11\x70D\x3f\xf8\x00\x00\x00\x00\x00\x00\
+           SU\x01xZ]U\x01b{U\x01cT}}"[..]
+    );
+    assert_eq!(
+        to_ubjson("18446744073709551615"),
+        b"HU\x1418446744073709551615"
+    );
+    assert_eq!(to_ubjson(" 1 2\n[] "), b"i\x01i\x02[]");
+    assert_eq!(to_ubjson(""), b"");
+
+    let mut out = Vec::new();
+    assert!(json_to_ubjson(&b"[1,"[..], &mut out).is_err());
+
+    // Errors from the output come through as they are, not as a message.
+    let mut out = [0u8; 2];
+    match json_to_ubjson(&b"[1, 2, 3]"[..], &mut out[..]) {
+        Err(Error::Io(err)) => assert_eq!(err.kind(), io::ErrorKind::WriteZero),
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn json_roundtrip() {
+    let json = r#"{"name":"café","tags":["a","b"],"n":[0,255,-1,3.25],"ok":false}"#;
+    let ubjson = to_ubjson(json);
+    assert_eq!(
+        to_json(&ubjson),
+        r#"{"name":"café","tags":["a","b"],"n":[0,255,-1,3.25],"ok":false}"#
+    );
+}
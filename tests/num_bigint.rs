@@ -0,0 +1,77 @@
+#![cfg(feature = "num-bigint")]
+
+extern crate num_bigint;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_ubjson;
+
+use num_bigint::{BigInt, BigUint};
+use serde_ubjson::{from_slice, from_value, to_vec, Value};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Ledger {
+    #[serde(with = "serde_ubjson::num_bigint::bigint")]
+    balance: BigInt,
+    #[serde(with = "serde_ubjson::num_bigint::biguint")]
+    supply: BigUint,
+}
+
+#[test]
+fn bigints_roundtrip() {
+    let cases: &[(&str, &str, &[u8])] = &[
+        ("-5", "5", b"[#U\x02i\xfbU\x05"),
+        (
+            "-9223372036854775809",
+            "18446744073709551615",
+            b"[#U\x02HU\x14-9223372036854775809HU\x1418446744073709551615",
+        ),
+        (
+            "123456789012345678901234567890",
+            "9223372036854775808",
+            b"[#U\x02HU\x1e123456789012345678901234567890HU\x139223372036854775808",
+        ),
+    ];
+    for &(balance, supply, bytes) in cases {
+        let ledger = Ledger {
+            balance: balance.parse().unwrap(),
+            supply: supply.parse().unwrap(),
+        };
+        assert_eq!(to_vec(&ledger).unwrap(), bytes);
+        assert_eq!(from_slice::<Ledger>(bytes).unwrap(), ledger);
+        let value: Value = from_slice(bytes).unwrap();
+        assert_eq!(from_value::<Ledger>(value).unwrap(), ledger);
+    }
+}
+
+#[test]
+fn bigints_from_other_encoders() {
+    // What the serializer writes for a `u64` or `u128` too large for an `int64` reads back.
+    let bytes = to_vec(&(u64::MAX, u128::MAX)).unwrap();
+    let ledger: Ledger = from_slice(&bytes).unwrap();
+    assert_eq!(ledger.balance, BigInt::from(u64::MAX));
+    assert_eq!(ledger.supply, BigUint::from(u128::MAX));
+
+    let ledger: Ledger = from_slice(b"[#U\x02L\x80\x00\x00\x00\x00\x00\x00\x00U\x00").unwrap();
+    assert_eq!(ledger.balance, BigInt::from(i64::MIN));
+    assert_eq!(ledger.supply, BigUint::from(0u8));
+
+    let ledger: Ledger = from_slice(b"[#U\x02SU\x02-7SU\x0212").unwrap();
+    assert_eq!(ledger.balance, BigInt::from(-7));
+    assert_eq!(ledger.supply, BigUint::from(12u8));
+}
+
+#[test]
+fn bigints_reject_non_integers() {
+    for bytes in &[
+        &b"[#U\x02HU\x031.5U\x00"[..],
+        b"[#U\x02HU\x031e3U\x00",
+        b"[#U\x02HU\x00U\x00",
+        b"[#U\x02HU\x01-U\x00",
+        b"[#U\x02D\x3f\xf8\x00\x00\x00\x00\x00\x00U\x00",
+        b"[#U\x02U\x01HU\x02-1",
+        b"[#U\x02U\x01i\xff",
+    ] {
+        assert!(from_slice::<Ledger>(bytes).is_err());
+    }
+}
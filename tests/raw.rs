@@ -0,0 +1,74 @@
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_ubjson;
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use serde_ubjson::{from_slice, to_raw_value, to_vec, Error, RawValue, Serializer, Value};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Envelope {
+    id: u8,
+    payload: RawValue,
+}
+
+#[test]
+fn raw_value_roundtrip() {
+    let payload = to_raw_value(&vec![1u8, 2, 3]).unwrap();
+    assert_eq!(payload.get(), b"[#U\x03U\x01U\x02U\x03");
+
+    let envelope = Envelope { id: 7, payload };
+    let buf = to_vec(&envelope).unwrap();
+    assert_eq!(buf, &b"[#U\x02U\x07[#U\x03U\x01U\x02U\x03"[..]);
+
+    let decoded: Envelope = from_slice(&buf).unwrap();
+    assert_eq!(decoded, envelope);
+    assert_eq!(decoded.payload.parse::<Vec<u8>>().unwrap(), vec![1, 2, 3]);
+}
+
+#[test]
+fn raw_value_is_not_reencoded() {
+    // A wider encoding than the serializer would pick survives the round trip as it was.
+    let payload = RawValue::from_vec(b"l\x00\x00\x00\x05".to_vec()).unwrap();
+    let mut map = BTreeMap::new();
+    map.insert("cached", payload);
+    let buf = to_vec(&map).unwrap();
+    assert_eq!(buf, &b"{#U\x01U\x06cachedl\x00\x00\x00\x05"[..]);
+
+    let mut buf = Vec::new();
+    map.serialize(&mut Serializer::new(&mut buf).always_unsized_containers(true))
+        .unwrap();
+    assert_eq!(buf, &b"{U\x06cachedl\x00\x00\x00\x05}"[..]);
+
+    let decoded: BTreeMap<String, RawValue> = from_slice(&buf).unwrap();
+    assert_eq!(decoded["cached"].get(), b"l\x00\x00\x00\x05");
+}
+
+#[test]
+fn raw_value_in_typed_container() {
+    // The marker implied by the container is put back in front of each value.
+    let values: Vec<RawValue> = from_slice(b"[$I#U\x02\x01\x00\x02\x00").unwrap();
+    assert_eq!(values[0].get(), b"I\x01\x00");
+    assert_eq!(values[1].get(), b"I\x02\x00");
+}
+
+#[test]
+fn raw_value_from_vec() {
+    assert!(RawValue::from_vec(b"SU\x02hi".to_vec()).is_ok());
+    assert!(RawValue::from_vec(Vec::new()).is_err());
+    assert!(RawValue::from_vec(b"SU\x02h".to_vec()).is_err());
+    assert!(RawValue::from_vec(b"ZZ".to_vec()).is_err());
+    assert!(matches!(
+        RawValue::from_vec(b"ZTN".to_vec()),
+        Err(Error::TrailingBytes { remaining: 2 })
+    ));
+}
+
+#[test]
+fn raw_value_from_value() {
+    let value = Value::Array(vec![Value::Int(300), Value::Null]);
+    let raw: RawValue = serde_ubjson::from_value(value).unwrap();
+    assert_eq!(raw.get(), b"[#U\x02I\x01\x2cZ");
+}
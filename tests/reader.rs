@@ -0,0 +1,216 @@
+extern crate serde_ubjson;
+
+use serde_ubjson::{Error, Token, UbjsonReader};
+
+fn tokens(input: &[u8]) -> Vec<Token> {
+    let mut reader = UbjsonReader::new(input);
+    let mut tokens = Vec::new();
+    while let Some(token) = reader.next_token().unwrap() {
+        tokens.push(token);
+    }
+    assert_eq!(reader.depth(), 0);
+    tokens
+}
+
+#[test]
+fn read_scalars() {
+    assert_eq!(
+        tokens(b"ZTFi\xffU\xc8I\x01\x00l\x00\x01\x00\x00L\x00\x00\x00\x01\x00\x00\x00\x00"),
+        vec![
+            Token::Null,
+            Token::Bool(true),
+            Token::Bool(false),
+            Token::Int(-1),
+            Token::Int(200),
+            Token::Int(256),
+            Token::Int(65536),
+            Token::Int(1 << 32),
+        ]
+    );
+    assert_eq!(
+        tokens(b"d\x3f\xc0\x00\x00D\x3f\xf8\x00\x00\x00\x00\x00\x00CaSU\x02hiHU\x0212"),
+        vec![
+            Token::Float(1.5),
+            Token::Float(1.5),
+            Token::Char('a'),
+            Token::Str("hi".to_owned()),
+            Token::HighPrecision("12".to_owned()),
+        ]
+    );
+}
+
+#[test]
+fn read_containers() {
+    assert_eq!(
+        tokens(b"{U\x01a[U\x01[]]U\x01b{#U\x01U\x01cZ}"),
+        vec![
+            Token::ObjectStart {
+                len: None,
+                ty: None
+            },
+            Token::Key("a".to_owned()),
+            Token::ArrayStart {
+                len: None,
+                ty: None
+            },
+            Token::Int(1),
+            Token::ArrayStart {
+                len: None,
+                ty: None
+            },
+            Token::ArrayEnd,
+            Token::ArrayEnd,
+            Token::Key("b".to_owned()),
+            Token::ObjectStart {
+                len: Some(1),
+                ty: None,
+            },
+            Token::Key("c".to_owned()),
+            Token::Null,
+            Token::ObjectEnd,
+            Token::ObjectEnd,
+        ]
+    );
+
+    assert_eq!(
+        tokens(b"[$i#U\x02\x01\xff{$T#U\x01U\x01x[#U\x00"),
+        vec![
+            Token::ArrayStart {
+                len: Some(2),
+                ty: Some(b'i'),
+            },
+            Token::Int(1),
+            Token::Int(-1),
+            Token::ArrayEnd,
+            Token::ObjectStart {
+                len: Some(1),
+                ty: Some(b'T'),
+            },
+            Token::Key("x".to_owned()),
+            Token::Bool(true),
+            Token::ObjectEnd,
+            Token::ArrayStart {
+                len: Some(0),
+                ty: None,
+            },
+            Token::ArrayEnd,
+        ]
+    );
+}
+
+#[test]
+fn read_skips_noops() {
+    assert_eq!(
+        tokens(b"N[NTN]NN{NU\x01aNZN}N"),
+        vec![
+            Token::ArrayStart {
+                len: None,
+                ty: None
+            },
+            Token::Bool(true),
+            Token::ArrayEnd,
+            Token::ObjectStart {
+                len: None,
+                ty: None
+            },
+            Token::Key("a".to_owned()),
+            Token::Null,
+            Token::ObjectEnd,
+        ]
+    );
+}
+
+#[test]
+fn read_errors() {
+    fn first_error(input: &[u8]) -> Error {
+        let mut reader = UbjsonReader::new(input).max_depth(2);
+        loop {
+            match reader.next_token() {
+                Ok(Some(_)) => {}
+                Ok(None) => panic!("no error reading {:?}", input),
+                Err(err) => return err,
+            }
+        }
+    }
+
+    for input in &[&b"[U"[..], b"SU\x05abc", b"[T", b"{U\x01a", b"l\x00\x00"] {
+        match first_error(input) {
+            Error::UnexpectedEof => {}
+            other => panic!("expected UnexpectedEof, got {:?}", other),
+        }
+    }
+    assert!(matches!(first_error(b"[[[]]]"), Error::DepthLimitExceeded));
+    assert!(matches!(first_error(b"[$U]"), Error::InvalidMarker(b']')));
+    assert!(matches!(first_error(b"x"), Error::InvalidMarker(b'x')));
+    assert!(matches!(first_error(b"SU\x01\xff"), Error::InvalidUtf8));
+}
+
+#[test]
+fn read_zero_width_count_limit() {
+    // The count is rejected with the header, before a single element is handed out.
+    for input in &[
+        &b"[$T#L\x03\x00\x00\x00\x00\x00\x00\x00"[..],
+        b"[$T#l\x7f\xff\xff\xff",
+        b"[$Z#l\x00\x10\x00\x01",
+        b"[$F#l\x00\x10\x00\x01",
+    ] {
+        let mut reader = UbjsonReader::new(*input);
+        assert!(matches!(
+            reader.next_token(),
+            Err(Error::LengthLimitExceeded)
+        ));
+    }
+    assert_eq!(tokens(b"[$Z#l\x00\x10\x00\x00").len(), (1 << 20) + 2);
+
+    let mut reader = UbjsonReader::new(&b"[$T#U\x03"[..]).max_zero_width_count(2);
+    assert!(matches!(
+        reader.next_token(),
+        Err(Error::LengthLimitExceeded)
+    ));
+    let mut reader = UbjsonReader::new(&b"[$T#U\x02"[..]).max_zero_width_count(2);
+    assert!(matches!(
+        reader.next_token(),
+        Ok(Some(Token::ArrayStart { .. }))
+    ));
+}
+
+#[test]
+fn validate() {
+    use serde_ubjson::validate;
+
+    for input in &[
+        &b"Z"[..],
+        b"NU\x01NN",
+        b"[$U#U\x02\x01\x02",
+        b"{U\x01a[TF]U\x01b{#U\x00}",
+        b"[[[]]]",
+    ] {
+        validate(input).unwrap();
+    }
+
+    assert!(matches!(validate(b""), Err(Error::UnexpectedEof)));
+    assert!(matches!(validate(b"[U\x01"), Err(Error::UnexpectedEof)));
+    assert!(matches!(validate(b"[#U\x02Z"), Err(Error::UnexpectedEof)));
+    assert!(matches!(
+        validate(b"[$U#U\x02\x01"),
+        Err(Error::UnexpectedEof)
+    ));
+    assert!(matches!(
+        validate(b"[$UZ]"),
+        Err(Error::InvalidMarker(b'Z'))
+    ));
+    assert!(matches!(validate(b"[}"), Err(Error::InvalidMarker(b'}'))));
+    assert!(matches!(validate(b"{U\x01\xffZ}"), Err(Error::InvalidUtf8)));
+    assert!(matches!(
+        validate(b"{SU\x01aZ}"),
+        Err(Error::InvalidMarker(b'S'))
+    ));
+    assert!(matches!(
+        validate(b"[#U\x01ZZ"),
+        Err(Error::TrailingBytes { remaining: 1 })
+    ));
+    assert!(matches!(
+        validate(b"TNU\x01"),
+        Err(Error::TrailingBytes { remaining: 2 })
+    ));
+}
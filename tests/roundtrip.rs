@@ -0,0 +1,194 @@
+extern crate proptest;
+extern crate serde;
+extern crate serde_bytes;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_ubjson;
+
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+
+use proptest::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_bytes::ByteBuf;
+use serde_ubjson::ser::{CountEncoding, StructRepresentation};
+use serde_ubjson::{from_slice, from_value, to_vec, Serializer, Value};
+
+fn roundtrip<T>(value: &T) -> std::result::Result<(), TestCaseError>
+where
+    T: Debug + PartialEq + Serialize + DeserializeOwned,
+{
+    let bytes = to_vec(value).map_err(|e| TestCaseError::fail(e.to_string()))?;
+    let decoded: T = from_slice(&bytes).map_err(|e| TestCaseError::fail(e.to_string()))?;
+    prop_assert_eq!(&decoded, value);
+    Ok(())
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Record {
+    id: u64,
+    name: String,
+    scores: Vec<i16>,
+    parent: Option<Box<Record>>,
+}
+
+fn record() -> impl Strategy<Value = Record> {
+    let leaf = (
+        any::<u64>(),
+        ".*",
+        prop::collection::vec(any::<i16>(), 0..8),
+    )
+        .prop_map(|(id, name, scores)| Record {
+            id,
+            name,
+            scores,
+            parent: None,
+        });
+    leaf.prop_recursive(4, 16, 1, |inner| {
+        (any::<u64>(), ".*", inner).prop_map(|(id, name, parent)| Record {
+            id,
+            name,
+            scores: Vec::new(),
+            parent: Some(Box::new(parent)),
+        })
+    })
+}
+
+/// Containers of every shape the serializer can write, nested in one another.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Nested {
+    /// An array of objects, which may itself become a typed array of `{`.
+    rows: Vec<BTreeMap<String, u16>>,
+    /// An object whose values are arrays, typed or not.
+    columns: BTreeMap<String, Vec<i32>>,
+    /// Byte arrays, always counted, inside objects that may be unsized.
+    blobs: BTreeMap<String, ByteBuf>,
+    /// Counted tuples inside arrays that may be unsized.
+    pairs: Vec<(u8, Option<bool>)>,
+    flags: Vec<Vec<bool>>,
+    records: Vec<Record>,
+}
+
+fn nested() -> impl Strategy<Value = Nested> {
+    let key = "[a-c]{0,2}";
+    (
+        prop::collection::vec(prop::collection::btree_map(key, any::<u16>(), 0..4), 0..4),
+        prop::collection::btree_map(key, prop::collection::vec(-300..300i32, 0..6), 0..4),
+        prop::collection::btree_map(key, any::<Vec<u8>>().prop_map(ByteBuf::from), 0..3),
+        prop::collection::vec(any::<(u8, Option<bool>)>(), 0..6),
+        prop::collection::vec(prop::collection::vec(Just(true), 0..5), 0..3),
+        prop::collection::vec(record(), 0..2),
+    )
+        .prop_map(|(rows, columns, blobs, pairs, flags, records)| Nested {
+            rows,
+            columns,
+            blobs,
+            pairs,
+            flags,
+            records,
+        })
+}
+
+/// Serializes `value` with the options picked by the bits of `options`.
+fn to_vec_with(value: &Nested, options: u8) -> Vec<u8> {
+    let representation = if options & 16 != 0 {
+        StructRepresentation::Object
+    } else {
+        StructRepresentation::Array
+    };
+    let encoding = if options & 32 != 0 {
+        CountEncoding::FixedI32
+    } else {
+        CountEncoding::Narrow
+    };
+    let mut buf = Vec::new();
+    value
+        .serialize(
+            &mut Serializer::new(&mut buf)
+                .optimize_seqs(options & 1 != 0)
+                .optimize_maps(options & 2 != 0)
+                .always_unsized_containers(options & 4 != 0)
+                .sort_map_keys(options & 8 != 0)
+                .struct_representation(representation)
+                .count_encoding(encoding),
+        )
+        .unwrap();
+    buf
+}
+
+proptest! {
+    #[test]
+    fn roundtrip_integers(
+        a in any::<i8>(),
+        b in any::<i16>(),
+        c in any::<i32>(),
+        d in any::<i64>(),
+        e in any::<u8>(),
+        f in any::<u16>(),
+        g in any::<u32>(),
+        h in any::<u64>(),
+    ) {
+        roundtrip(&a)?;
+        roundtrip(&b)?;
+        roundtrip(&c)?;
+        roundtrip(&d)?;
+        roundtrip(&e)?;
+        roundtrip(&f)?;
+        roundtrip(&g)?;
+        roundtrip(&h)?;
+    }
+
+    #[test]
+    fn roundtrip_128_bit_integers(a in any::<i128>(), b in any::<u128>()) {
+        roundtrip(&a)?;
+        roundtrip(&b)?;
+    }
+
+    #[test]
+    fn roundtrip_floats(a in any::<f32>(), b in any::<f64>()) {
+        let bytes = to_vec(&a).unwrap();
+        prop_assert_eq!(from_slice::<f32>(&bytes).unwrap().to_bits(), a.to_bits());
+        let bytes = to_vec(&b).unwrap();
+        prop_assert_eq!(from_slice::<f64>(&bytes).unwrap().to_bits(), b.to_bits());
+    }
+
+    #[test]
+    fn roundtrip_text(a in any::<bool>(), b in any::<char>(), c in ".*") {
+        roundtrip(&a)?;
+        roundtrip(&b)?;
+        roundtrip(&c)?;
+    }
+
+    #[test]
+    fn roundtrip_collections(
+        a in prop::collection::vec(any::<Option<(i32, String)>>(), 0..16),
+        b in prop::collection::btree_map(".*", prop::collection::vec(any::<u64>(), 0..4), 0..8),
+    ) {
+        roundtrip(&a)?;
+        roundtrip::<BTreeMap<String, Vec<u64>>>(&b)?;
+    }
+
+    #[test]
+    fn roundtrip_structs(a in record()) {
+        roundtrip(&a)?;
+    }
+
+    #[test]
+    fn roundtrip_mixed_containers(a in nested()) {
+        for options in 0..64 {
+            let bytes = to_vec_with(&a, options);
+            let decoded: Nested = from_slice(&bytes)
+                .map_err(|e| TestCaseError::fail(format!("options {}: {}", options, e)))?;
+            prop_assert_eq!(&decoded, &a);
+            let value: Value = from_slice(&bytes).unwrap();
+            prop_assert_eq!(&from_value::<Nested>(value).unwrap(), &a);
+        }
+    }
+
+    #[test]
+    fn decode_arbitrary_bytes(bytes in prop::collection::vec(any::<u8>(), 0..256)) {
+        // Garbage may fail to decode, but must never panic. See also the fuzz target in `fuzz/`.
+        let _ = from_slice::<Value>(&bytes);
+    }
+}
@@ -0,0 +1,41 @@
+#![cfg(feature = "rust_decimal")]
+
+extern crate rust_decimal;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_ubjson;
+
+use std::str::FromStr;
+
+use rust_decimal::Decimal;
+use serde_ubjson::{from_slice, from_value, to_vec, Value};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Trade {
+    #[serde(with = "serde_ubjson::rust_decimal")]
+    price: Decimal,
+}
+
+#[test]
+fn decimal_as_hi_precision() {
+    let trade = Trade {
+        price: Decimal::from_str("-12345678901234567890.50").unwrap(),
+    };
+    let bytes = to_vec(&trade).unwrap();
+    assert_eq!(bytes, &b"[#U\x01HU\x18-12345678901234567890.50"[..]);
+    let back = from_slice::<Trade>(&bytes).unwrap();
+    assert_eq!(back, trade);
+    assert_eq!(back.price.scale(), 2);
+
+    let value = Value::Array(vec![Value::HighPrecision("1.5e3".to_owned())]);
+    assert_eq!(
+        from_value::<Trade>(value).unwrap().price,
+        Decimal::from(1500)
+    );
+    assert_eq!(
+        from_slice::<Trade>(b"[#U\x01U\x07").unwrap().price,
+        Decimal::from(7)
+    );
+    assert!(from_slice::<Trade>(b"[#U\x01HU\x03abc").is_err());
+}
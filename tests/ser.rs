@@ -1,4 +1,6 @@
 extern crate serde;
+#[macro_use]
+extern crate serde_derive;
 extern crate serde_bytes;
 extern crate serde_ubjson;
 
@@ -32,118 +34,143 @@ fn serialize_bool() {
 #[test]
 fn serialize_i8() {
     test_cases! {
-        (i8::min_value(), b"i\x80"),
-        (0i8,             b"i\x00"),
-        (i8::max_value(), b"i\x7f"),
+        (i8::MIN, b"i\x80"),
+        (0i8,     b"i\x00"),
+        (i8::MAX, b"i\x7f"),
     }
 }
 
 #[test]
 fn serialize_i16() {
     test_cases! {
-        (i16::min_value(),               b"I\x80\x00"),
-        (i16::from(i8::min_value()) - 1, b"I\xff\x7f"),
-        (i16::from(i8::min_value()),     b"i\x80"),
-        (0i16,                           b"i\x00"),
-        (i16::from(i8::max_value()),     b"i\x7f"),
-        (i16::from(i8::max_value()) + 1, b"U\x80"),
-        (i16::from(u8::max_value()),     b"U\xff"),
-        (i16::from(u8::max_value()) + 1, b"I\x01\x00"),
-        (i16::max_value(),               b"I\x7f\xff"),
+        (i16::MIN,               b"I\x80\x00"),
+        (i16::from(i8::MIN) - 1, b"I\xff\x7f"),
+        (i16::from(i8::MIN),     b"i\x80"),
+        (0i16,                   b"i\x00"),
+        (i16::from(i8::MAX),     b"i\x7f"),
+        (i16::from(i8::MAX) + 1, b"U\x80"),
+        (i16::from(u8::MAX),     b"U\xff"),
+        (i16::from(u8::MAX) + 1, b"I\x01\x00"),
+        (i16::MAX,               b"I\x7f\xff"),
     }
 }
 
-#[allow(clippy::cyclomatic_complexity)]
+#[allow(clippy::cognitive_complexity)]
 #[test]
 fn serialize_i32() {
     test_cases! {
-        (i32::min_value(),                b"l\x80\x00\x00\x00"),
-        (i32::from(i16::min_value()) - 1, b"l\xff\xff\x7f\xff"),
-        (i32::from(i16::min_value()),     b"I\x80\x00"),
-        (i32::from(i8::min_value()) - 1,  b"I\xff\x7f"),
-        (i32::from(i8::min_value()),      b"i\x80"),
-        (0i32,                            b"i\x00"),
-        (i32::from(i8::max_value()),      b"i\x7f"),
-        (i32::from(i8::max_value()) + 1,  b"U\x80"),
-        (i32::from(u8::max_value()),      b"U\xff"),
-        (i32::from(u8::max_value()) + 1,  b"I\x01\x00"),
-        (i32::from(i16::max_value()),     b"I\x7f\xff"),
-        (i32::from(i16::max_value()) + 1, b"l\x00\x00\x80\x00"),
-        (i32::max_value(),                b"l\x7f\xff\xff\xff"),
+        (i32::MIN,                b"l\x80\x00\x00\x00"),
+        (i32::from(i16::MIN) - 1, b"l\xff\xff\x7f\xff"),
+        (i32::from(i16::MIN),     b"I\x80\x00"),
+        (i32::from(i8::MIN) - 1,  b"I\xff\x7f"),
+        (i32::from(i8::MIN),      b"i\x80"),
+        (0i32,                    b"i\x00"),
+        (i32::from(i8::MAX),      b"i\x7f"),
+        (i32::from(i8::MAX) + 1,  b"U\x80"),
+        (i32::from(u8::MAX),      b"U\xff"),
+        (i32::from(u8::MAX) + 1,  b"I\x01\x00"),
+        (i32::from(i16::MAX),     b"I\x7f\xff"),
+        (i32::from(i16::MAX) + 1, b"l\x00\x00\x80\x00"),
+        (i32::MAX,                b"l\x7f\xff\xff\xff"),
     }
 }
 
-#[allow(clippy::cyclomatic_complexity)]
+#[allow(clippy::cognitive_complexity)]
 #[test]
 fn serialize_i64() {
     test_cases! {
-        (i64::min_value(),                b"L\x80\x00\x00\x00\x00\x00\x00\x00"),
-        (i64::from(i32::min_value()) - 1, b"L\xff\xff\xff\xff\x7f\xff\xff\xff"),
-        (i64::from(i32::min_value()),     b"l\x80\x00\x00\x00"),
-        (i64::from(i16::min_value()) - 1, b"l\xff\xff\x7f\xff"),
-        (i64::from(i16::min_value()),     b"I\x80\x00"),
-        (i64::from(i8::min_value()) - 1,  b"I\xff\x7f"),
-        (i64::from(i8::min_value()),      b"i\x80"),
-        (0i64,                            b"i\x00"),
-        (i64::from(i8::max_value()),      b"i\x7f"),
-        (i64::from(i8::max_value()) + 1,  b"U\x80"),
-        (i64::from(u8::max_value()),      b"U\xff"),
-        (i64::from(u8::max_value()) + 1,  b"I\x01\x00"),
-        (i64::from(i16::max_value()),     b"I\x7f\xff"),
-        (i64::from(i16::max_value()) + 1, b"l\x00\x00\x80\x00"),
-        (i64::from(i32::max_value()),     b"l\x7f\xff\xff\xff"),
-        (i64::from(i32::max_value()) + 1, b"L\x00\x00\x00\x00\x80\x00\x00\x00"),
-        (i64::max_value(),                b"L\x7f\xff\xff\xff\xff\xff\xff\xff"),
+        (i64::MIN,                b"L\x80\x00\x00\x00\x00\x00\x00\x00"),
+        (i64::from(i32::MIN) - 1, b"L\xff\xff\xff\xff\x7f\xff\xff\xff"),
+        (i64::from(i32::MIN),     b"l\x80\x00\x00\x00"),
+        (i64::from(i16::MIN) - 1, b"l\xff\xff\x7f\xff"),
+        (i64::from(i16::MIN),     b"I\x80\x00"),
+        (i64::from(i8::MIN) - 1,  b"I\xff\x7f"),
+        (i64::from(i8::MIN),      b"i\x80"),
+        (0i64,                    b"i\x00"),
+        (i64::from(i8::MAX),      b"i\x7f"),
+        (i64::from(i8::MAX) + 1,  b"U\x80"),
+        (i64::from(u8::MAX),      b"U\xff"),
+        (i64::from(u8::MAX) + 1,  b"I\x01\x00"),
+        (i64::from(i16::MAX),     b"I\x7f\xff"),
+        (i64::from(i16::MAX) + 1, b"l\x00\x00\x80\x00"),
+        (i64::from(i32::MAX),     b"l\x7f\xff\xff\xff"),
+        (i64::from(i32::MAX) + 1, b"L\x00\x00\x00\x00\x80\x00\x00\x00"),
+        (i64::MAX,                b"L\x7f\xff\xff\xff\xff\xff\xff\xff"),
     }
 }
 
 #[test]
 fn serialize_u8() {
     test_cases! {
-        (u8::min_value(), b"U\x00"),
-        (u8::max_value(), b"U\xff"),
+        (u8::MIN, b"U\x00"),
+        (u8::MAX, b"U\xff"),
     }
 }
 
 #[test]
 fn serialize_u16() {
     test_cases! {
-        (0u16, b"U\x00"),
-        (u16::from(u8::max_value()),      b"U\xff"),
-        (u16::from(u8::max_value()) + 1,  b"I\x01\x00"),
-        (i16::max_value() as u16,         b"I\x7f\xff"),
-        (i16::max_value() as u16 + 1,     b"l\x00\x00\x80\x00"),
-        (u16::max_value(),                b"l\x00\x00\xff\xff"),
+        (0u16,                   b"U\x00"),
+        (u16::from(u8::MAX),     b"U\xff"),
+        (u16::from(u8::MAX) + 1, b"I\x01\x00"),
+        (i16::MAX as u16,        b"I\x7f\xff"),
+        (i16::MAX as u16 + 1,    b"l\x00\x00\x80\x00"),
+        (u16::MAX,               b"l\x00\x00\xff\xff"),
     }
 }
 
 #[test]
 fn serialize_u32() {
     test_cases! {
-        (0u32,                            b"U\x00"),
-        (u32::from(u8::max_value()),      b"U\xff"),
-        (u32::from(u8::max_value()) + 1,  b"I\x01\x00"),
-        (i16::max_value() as u32,         b"I\x7f\xff"),
-        (i16::max_value() as u32 + 1,     b"l\x00\x00\x80\x00"),
-        (i32::max_value() as u32,         b"l\x7f\xff\xff\xff"),
-        (i32::max_value() as u32 + 1,     b"L\x00\x00\x00\x00\x80\x00\x00\x00"),
-        (u32::max_value(),                b"L\x00\x00\x00\x00\xff\xff\xff\xff"),
+        (0u32,                   b"U\x00"),
+        (u32::from(u8::MAX),     b"U\xff"),
+        (u32::from(u8::MAX) + 1, b"I\x01\x00"),
+        (i16::MAX as u32,        b"I\x7f\xff"),
+        (i16::MAX as u32 + 1,    b"l\x00\x00\x80\x00"),
+        (i32::MAX as u32,        b"l\x7f\xff\xff\xff"),
+        (i32::MAX as u32 + 1,    b"L\x00\x00\x00\x00\x80\x00\x00\x00"),
+        (u32::MAX,               b"L\x00\x00\x00\x00\xff\xff\xff\xff"),
     }
 }
 
 #[test]
 fn serialize_u64() {
     test_cases! {
-        (0u64,                            b"U\x00"),
-        (u64::from(u8::max_value()),      b"U\xff"),
-        (u64::from(u8::max_value()) + 1,  b"I\x01\x00"),
-        (i16::max_value() as u64,         b"I\x7f\xff"),
-        (i16::max_value() as u64 + 1,     b"l\x00\x00\x80\x00"),
-        (i32::max_value() as u64,         b"l\x7f\xff\xff\xff"),
-        (i32::max_value() as u64 + 1,     b"L\x00\x00\x00\x00\x80\x00\x00\x00"),
-        (i64::max_value() as u64,         b"L\x7f\xff\xff\xff\xff\xff\xff\xff"),
-        (i64::max_value() as u64 + 1,     b"HU\x139223372036854775808"),
-        (u64::max_value(),                b"HU\x1418446744073709551615"),
+        (0u64,                   b"U\x00"),
+        (u64::from(u8::MAX),     b"U\xff"),
+        (u64::from(u8::MAX) + 1, b"I\x01\x00"),
+        (i16::MAX as u64,        b"I\x7f\xff"),
+        (i16::MAX as u64 + 1,    b"l\x00\x00\x80\x00"),
+        (i32::MAX as u64,        b"l\x7f\xff\xff\xff"),
+        (i32::MAX as u64 + 1,    b"L\x00\x00\x00\x00\x80\x00\x00\x00"),
+        (i64::MAX as u64,        b"L\x7f\xff\xff\xff\xff\xff\xff\xff"),
+        (i64::MAX as u64 + 1,    b"HU\x139223372036854775808"),
+        (u64::MAX,               b"HU\x1418446744073709551615"),
+    }
+}
+
+#[test]
+fn serialize_i128() {
+    test_cases! {
+        (i128::MIN,                   b"HU\x28-170141183460469231731687303715884105728"),
+        (i128::from(i64::MIN) - 1,    b"HU\x14-9223372036854775809"),
+        (i128::from(i64::MIN),        b"L\x80\x00\x00\x00\x00\x00\x00\x00"),
+        (0i128,                       b"i\x00"),
+        (i128::from(u8::MAX),         b"U\xff"),
+        (i128::from(i64::MAX),        b"L\x7f\xff\xff\xff\xff\xff\xff\xff"),
+        (i128::from(i64::MAX) + 1,    b"HU\x139223372036854775808"),
+        (i128::MAX,                   b"HU\x27170141183460469231731687303715884105727"),
+    }
+}
+
+#[test]
+fn serialize_u128() {
+    test_cases! {
+        (0u128,                       b"U\x00"),
+        (u128::from(u32::MAX),        b"L\x00\x00\x00\x00\xff\xff\xff\xff"),
+        (u128::from(u64::MAX),        b"HU\x1418446744073709551615"),
+        (u128::from(u64::MAX) + 1,    b"HU\x1418446744073709551616"),
+        (u128::MAX,                   b"HU\x27340282366920938463463374607431768211455"),
     }
 }
 
@@ -167,6 +194,46 @@ fn serialize_f64() {
     }
 }
 
+/// Every multi-byte payload is big-endian, most significant byte first, whatever the host's byte
+/// order. Each value here has distinct bytes, so a swapped or shuffled layout cannot pass.
+#[test]
+fn big_endian_layout() {
+    use serde_ubjson::from_slice;
+
+    test_cases! {
+        (0x0102i16,                            b"I\x01\x02"),
+        (-0x0102i16,                           b"I\xfe\xfe"),
+        (0x0102_0304i32,                       b"l\x01\x02\x03\x04"),
+        (-0x0102_0304i32,                      b"l\xfe\xfd\xfc\xfc"),
+        (0x0102_0304_0506_0708i64,             b"L\x01\x02\x03\x04\x05\x06\x07\x08"),
+        (-0x0102_0304_0506_0708i64,            b"L\xfe\xfd\xfc\xfb\xfa\xf9\xf8\xf8"),
+        (f32::from_bits(0x4102_0304),          b"d\x41\x02\x03\x04"),
+        (f32::from_bits(0xc102_0304),          b"d\xc1\x02\x03\x04"),
+        (f64::from_bits(0x4102_0304_0506_0708), b"D\x41\x02\x03\x04\x05\x06\x07\x08"),
+        (f64::from_bits(0xc102_0304_0506_0708), b"D\xc1\x02\x03\x04\x05\x06\x07\x08"),
+    }
+
+    assert_eq!(from_slice::<i16>(b"I\xfe\xfe").unwrap(), -0x0102);
+    assert_eq!(
+        from_slice::<i32>(b"l\xfe\xfd\xfc\xfc").unwrap(),
+        -0x0102_0304
+    );
+    assert_eq!(
+        from_slice::<i64>(b"L\xfe\xfd\xfc\xfb\xfa\xf9\xf8\xf8").unwrap(),
+        -0x0102_0304_0506_0708
+    );
+    assert_eq!(
+        from_slice::<f32>(b"d\xc1\x02\x03\x04").unwrap().to_bits(),
+        0xc102_0304
+    );
+    assert_eq!(
+        from_slice::<f64>(b"D\xc1\x02\x03\x04\x05\x06\x07\x08")
+            .unwrap()
+            .to_bits(),
+        0xc102_0304_0506_0708
+    );
+}
+
 #[test]
 fn serialize_char() {
     test_cases! {
@@ -175,3 +242,1350 @@ fn serialize_char() {
         ('가', b"l\x00\x00\xac\x00"),
     }
 }
+
+#[test]
+fn serialize_str() {
+    test_cases! {
+        ("",      b"SU\x00"),
+        ("hello", b"SU\x05hello"),
+    }
+
+    // The length takes the narrowest integer marker, which changes at 256 and 32768 bytes.
+    for &(len, header) in &[
+        (255, &b"SU\xff"[..]),
+        (256, b"SI\x01\x00"),
+        (300, b"SI\x01\x2c"),
+        (32767, b"SI\x7f\xff"),
+        (32768, b"Sl\x00\x00\x80\x00"),
+    ] {
+        let s = "a".repeat(len);
+        let buf = serde_ubjson::to_vec(&s).unwrap();
+        assert_eq!(&buf[..header.len()], header, "length {}", len);
+        assert_eq!(&buf[header.len()..], s.as_bytes(), "length {}", len);
+    }
+}
+
+#[test]
+fn serialize_bytes() {
+    use serde_bytes::{ByteBuf, Bytes};
+    test_cases! {
+        (Bytes::new(b""),               b"[$U#U\x00"),
+        (Bytes::new(b"\x01\x02\x03"),   b"[$U#U\x03\x01\x02\x03"),
+        (ByteBuf::from(vec![1, 2, 3]),  b"[$U#U\x03\x01\x02\x03"),
+    }
+}
+
+#[test]
+fn serialize_byte_stream() {
+    let data: Vec<u8> = (0..=255).cycle().take(100_000).collect();
+    let mut buf = Vec::new();
+    Serializer::new(&mut buf)
+        .serialize_byte_stream(data.len() as u64, &data[..])
+        .unwrap();
+    assert_eq!(
+        buf,
+        serde_ubjson::to_vec(&serde_bytes::Bytes::new(&data)).unwrap()
+    );
+
+    let mut buf = Vec::new();
+    Serializer::new(&mut buf)
+        .serialize_byte_stream(4, &b"\x01\x02\x03\x04\x05"[..])
+        .unwrap();
+    assert_eq!(buf, b"[$U#U\x04\x01\x02\x03\x04");
+
+    let mut buf = Vec::new();
+    match Serializer::new(&mut buf).serialize_byte_stream(4, &b"\x01\x02"[..]) {
+        Err(serde_ubjson::Error::UnexpectedEof) => {}
+        other => panic!("expected UnexpectedEof, got {:?}", other),
+    }
+}
+
+#[test]
+fn serialize_typed_array() {
+    use serde_ubjson::{Error, TypeTag};
+
+    fn typed<T: serde_ubjson::TypedElement>(ty: TypeTag, items: &[T]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        Serializer::new(&mut buf)
+            .serialize_typed_array(ty, items.iter().cloned())
+            .unwrap();
+        buf
+    }
+
+    assert_eq!(
+        typed(TypeTag::F32, &[1.5f32, -2.0]),
+        b"[$d#U\x02\x3f\xc0\x00\x00\xc0\x00\x00\x00"
+    );
+    assert_eq!(typed(TypeTag::F32, &[0.5f64]), b"[$d#U\x01\x3f\x00\x00\x00");
+    assert_eq!(typed(TypeTag::I16, &[1u8, 2]), b"[$I#U\x02\x00\x01\x00\x02");
+    assert_eq!(typed(TypeTag::U8, &[0i64; 0]), b"[$U#U\x00");
+    assert_eq!(
+        serde_ubjson::from_slice::<Vec<f32>>(&typed(TypeTag::F32, &[1.5f32, -2.0])).unwrap(),
+        vec![1.5, -2.0]
+    );
+
+    let mut buf = Vec::new();
+    let mut ser = Serializer::new(&mut buf);
+    assert!(ser
+        .serialize_typed_array(TypeTag::U8, vec![256i32])
+        .is_err());
+    assert!(ser
+        .serialize_typed_array(TypeTag::I32, vec![1.0f64])
+        .is_err());
+    assert!(ser
+        .serialize_typed_array(TypeTag::F32, vec![0.1f64])
+        .is_err());
+
+    // An iterator whose length is wrong is caught rather than corrupting the count.
+    struct Short(u8);
+    impl Iterator for Short {
+        type Item = u8;
+        fn next(&mut self) -> Option<u8> {
+            self.0 = self.0.checked_sub(1)?;
+            Some(self.0)
+        }
+        fn size_hint(&self) -> (usize, Option<usize>) {
+            (3, Some(3))
+        }
+    }
+    impl ExactSizeIterator for Short {}
+    match Serializer::new(Vec::new()).serialize_typed_array(TypeTag::U8, Short(2)) {
+        Err(Error::LengthMismatch {
+            expected: 3,
+            found: 2,
+        }) => {}
+        other => panic!("expected LengthMismatch, got {:?}", other),
+    }
+
+    // One that runs on past its length is stopped at the first extra item, not drained.
+    struct Endless(u64);
+    impl Iterator for Endless {
+        type Item = u8;
+        fn next(&mut self) -> Option<u8> {
+            self.0 += 1;
+            Some(0)
+        }
+        fn size_hint(&self) -> (usize, Option<usize>) {
+            (2, Some(2))
+        }
+    }
+    impl ExactSizeIterator for Endless {}
+    let mut items = Endless(0);
+    match Serializer::new(Vec::new()).serialize_typed_array(TypeTag::U8, &mut items) {
+        Err(Error::LengthMismatch {
+            expected: 2,
+            found: 3,
+        }) => {}
+        other => panic!("expected LengthMismatch, got {:?}", other),
+    }
+    assert_eq!(items.0, 3);
+}
+
+#[test]
+fn serialize_non_finite_float() {
+    use serde_ubjson::ser::NonFiniteFloat;
+    use serde_ubjson::Error;
+
+    test_cases! {
+        (f32::INFINITY,     b"d\x7f\x80\x00\x00"),
+        (f64::NEG_INFINITY, b"D\xff\xf0\x00\x00\x00\x00\x00\x00"),
+    }
+
+    let mut buf = Vec::new();
+    let mut ser = Serializer::new(&mut buf).non_finite_float(NonFiniteFloat::Null);
+    (f32::NAN, f64::INFINITY, 1.0f32)
+        .serialize(&mut ser)
+        .unwrap();
+    assert_eq!(buf, b"[#U\x03ZZd\x3f\x80\x00\x00");
+
+    let mut ser = Serializer::new(Vec::new()).non_finite_float(NonFiniteFloat::Error);
+    match f64::NAN.serialize(&mut ser) {
+        Err(Error::NonFiniteFloat) => {}
+        other => panic!("expected NonFiniteFloat, got {:?}", other),
+    }
+    assert!(f64::MAX.serialize(&mut ser).is_ok());
+}
+
+#[test]
+fn serialize_compact_floats() {
+    let mut buf = Vec::new();
+    let mut ser = Serializer::new(&mut buf).compact_floats(true);
+    (1.5f64, 0.1f64, -0.0f64, f64::INFINITY)
+        .serialize(&mut ser)
+        .unwrap();
+    assert_eq!(
+        buf,
+        &b"[#U\x04d\x3f\xc0\x00\x00D\x3f\xb9\x99\x99\x99\x99\x99\x9ad\x80\x00\x00\x00d\x7f\x80\x00\x00"[..]
+    );
+}
+
+#[test]
+fn serialize_negative_zero() {
+    let value = (-0.0f32, -0.0f64, 0.0f64);
+
+    assert_eq!(
+        serde_ubjson::to_vec(&value).unwrap(),
+        &b"[#U\x03d\x80\x00\x00\x00D\x80\x00\x00\x00\x00\x00\x00\x00D\x00\x00\x00\x00\x00\x00\x00\x00"[..]
+    );
+
+    let mut buf = Vec::new();
+    value
+        .serialize(&mut Serializer::new(&mut buf).normalize_negative_zero(true))
+        .unwrap();
+    assert_eq!(
+        buf,
+        &b"[#U\x03d\x00\x00\x00\x00D\x00\x00\x00\x00\x00\x00\x00\x00D\x00\x00\x00\x00\x00\x00\x00\x00"[..]
+    );
+}
+
+#[test]
+fn serialize_with_capacity() {
+    let value = (1u8, "hello", [1.0f32, 2.0]);
+    let buf = serde_ubjson::to_vec_with_capacity(&value, 64).unwrap();
+    assert!(buf.capacity() >= 64);
+    assert_eq!(buf, serde_ubjson::to_vec(&value).unwrap());
+}
+
+#[test]
+fn serialize_to_buf() {
+    let mut buf = b"x".to_vec();
+    serde_ubjson::to_buf(&1u8, &mut buf).unwrap();
+    serde_ubjson::to_buf(&"a", &mut buf).unwrap();
+    assert_eq!(buf, b"xU\x01SU\x01a");
+
+    let ptr = buf.as_ptr();
+    buf.clear();
+    serde_ubjson::to_buf(&true, &mut buf).unwrap();
+    assert_eq!(buf, b"T");
+    assert_eq!(buf.as_ptr(), ptr);
+}
+
+#[test]
+fn to_writer_framed() {
+    let mut buf = Vec::new();
+    serde_ubjson::to_writer_framed(&mut buf, &"hi").unwrap();
+    serde_ubjson::to_writer_framed(&mut buf, &vec![1u8, 2]).unwrap();
+    assert_eq!(
+        buf,
+        &b"\x00\x00\x00\x05SU\x02hi\x00\x00\x00\x08[#U\x02U\x01U\x02"[..]
+    );
+}
+
+#[test]
+fn serialized_size() {
+    use serde_bytes::Bytes;
+    use std::collections::BTreeMap;
+
+    let mut map = BTreeMap::new();
+    map.insert("key", vec![Some(1u64 << 63), None]);
+    map.insert("other", vec![Some(300)]);
+
+    macro_rules! check {
+        ($($value:expr),*) => {
+            $(
+                let expected = serde_ubjson::to_vec(&$value).unwrap().len();
+                assert_eq!(serde_ubjson::serialized_size(&$value).unwrap(), expected);
+            )*
+        }
+    }
+    check!(
+        0u8,
+        -129i64,
+        u64::MAX,
+        'x',
+        "hello",
+        Bytes::new(&[0; 300]),
+        map
+    );
+}
+
+#[test]
+fn serialize_enum_representation() {
+    use serde_ubjson::ser::EnumRepresentation;
+
+    #[derive(Serialize)]
+    enum E {
+        Unit,
+        Newtype(u8),
+        Tuple(u8, u8),
+        Struct { a: u8 },
+    }
+
+    let values = [E::Unit, E::Newtype(1), E::Tuple(1, 2), E::Struct { a: 1 }];
+
+    let mut buf = Vec::new();
+    values.serialize(&mut Serializer::new(&mut buf)).unwrap();
+    assert_eq!(
+        buf,
+        &b"[#U\x04U\x00[#U\x02U\x01U\x01[#U\x03U\x02U\x01U\x02[#U\x02U\x03U\x01"[..]
+    );
+
+    let mut buf = Vec::new();
+    let mut ser = Serializer::new(&mut buf).enum_representation(EnumRepresentation::Name);
+    values.serialize(&mut ser).unwrap();
+    assert_eq!(
+        buf,
+        &b"[#U\x04SU\x04Unit[#U\x02SU\x07NewtypeU\x01[#U\x03SU\x05TupleU\x01U\x02\
+           [#U\x02SU\x06StructU\x01"[..]
+    );
+}
+
+#[test]
+fn serialize_struct_representation() {
+    use serde_ubjson::ser::StructRepresentation;
+
+    #[derive(Serialize)]
+    struct S {
+        id: u8,
+        name: &'static str,
+    }
+
+    #[derive(Serialize)]
+    enum E {
+        V { x: i8 },
+    }
+
+    let value = (S { id: 1, name: "a" }, E::V { x: -1 });
+
+    let mut buf = Vec::new();
+    value.serialize(&mut Serializer::new(&mut buf)).unwrap();
+    assert_eq!(buf, &b"[#U\x02[#U\x02U\x01SU\x01a[#U\x02U\x00i\xff"[..]);
+
+    let mut buf = Vec::new();
+    let mut ser = Serializer::new(&mut buf).struct_representation(StructRepresentation::Object);
+    value.serialize(&mut ser).unwrap();
+    assert_eq!(
+        buf,
+        &b"[#U\x02{#U\x02U\x02idU\x01U\x04nameSU\x01a[#U\x02U\x00{#U\x01U\x01xi\xff"[..]
+    );
+}
+
+#[test]
+fn serialize_struct_object_declaration_order() {
+    use serde_ubjson::ser::StructRepresentation;
+
+    #[derive(Serialize)]
+    struct S {
+        zeta: u8,
+        alpha: u8,
+        mid: u8,
+    }
+
+    let value = S {
+        zeta: 1,
+        alpha: 2,
+        mid: 3,
+    };
+    for &sorted in &[false, true] {
+        let mut buf = Vec::new();
+        let mut ser = Serializer::new(&mut buf)
+            .struct_representation(StructRepresentation::Object)
+            .sort_map_keys(sorted);
+        value.serialize(&mut ser).unwrap();
+        assert_eq!(
+            buf,
+            &b"{#U\x03U\x04zetaU\x01U\x05alphaU\x02U\x03midU\x03"[..]
+        );
+    }
+}
+
+#[test]
+fn serialize_struct_object_skip_and_flatten() {
+    use serde_ubjson::ser::StructRepresentation;
+
+    #[derive(Serialize)]
+    struct Inner {
+        b: u8,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        c: Option<u8>,
+    }
+
+    #[derive(Serialize)]
+    struct Skipping {
+        a: u8,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        b: Option<u8>,
+        #[serde(skip)]
+        #[allow(dead_code)]
+        c: u8,
+    }
+
+    #[derive(Serialize)]
+    struct Envelope {
+        version: u8,
+        #[serde(flatten)]
+        inner: Inner,
+    }
+
+    let mut buf = Vec::new();
+    let mut ser = Serializer::new(&mut buf).struct_representation(StructRepresentation::Object);
+    let value = (
+        Skipping {
+            a: 1,
+            b: None,
+            c: 9,
+        },
+        Skipping {
+            a: 1,
+            b: Some(2),
+            c: 9,
+        },
+    );
+    value.serialize(&mut ser).unwrap();
+    assert_eq!(
+        buf,
+        &b"[#U\x02{#U\x01U\x01aU\x01{#U\x02U\x01aU\x01U\x01bU\x02"[..]
+    );
+
+    let mut buf = Vec::new();
+    let mut ser = Serializer::new(&mut buf).struct_representation(StructRepresentation::Object);
+    let value = Envelope {
+        version: 2,
+        inner: Inner { b: 3, c: None },
+    };
+    value.serialize(&mut ser).unwrap();
+    assert_eq!(buf, &b"{U\x07versionU\x02U\x01bU\x03}"[..]);
+}
+
+#[test]
+fn write_raw_marker() {
+    use serde_ubjson::marker;
+
+    let mut buf = Vec::new();
+    {
+        let mut ser = Serializer::new(&mut buf);
+        for &m in &[marker::ARR_START, marker::TYPE, marker::I16, marker::LENGTH] {
+            ser.write_raw_marker(m).unwrap();
+        }
+        2u8.serialize(&mut ser).unwrap();
+        ser.write_raw_marker(marker::NOOP).unwrap();
+    }
+    assert_eq!(buf, b"[$I#U\x02N");
+}
+
+#[test]
+fn write_fragment() {
+    use serde_ubjson::marker;
+
+    let cached = serde_ubjson::to_vec(&vec!["a", "b"]).unwrap();
+
+    let mut buf = Vec::new();
+    {
+        let mut ser = Serializer::new(&mut buf);
+        for &m in &[marker::ARR_START, marker::LENGTH, marker::U8, 2] {
+            ser.write_raw_marker(m).unwrap();
+        }
+        ser.write_fragment(&cached).unwrap();
+        true.serialize(&mut ser).unwrap();
+    }
+    assert_eq!(buf, b"[#U\x02[#U\x02SU\x01aSU\x01bT");
+    let decoded: (Vec<String>, bool) = serde_ubjson::from_slice(&buf).unwrap();
+    assert_eq!(decoded, (vec!["a".to_owned(), "b".to_owned()], true));
+}
+
+#[test]
+fn encode_integer() {
+    use serde_ubjson::ser::{encode_integer, encode_unsigned_integer};
+
+    let mut buf = Vec::new();
+    encode_integer(&mut buf, -1).unwrap();
+    encode_integer(&mut buf, 200).unwrap();
+    encode_integer(&mut buf, i64::from(i32::MIN)).unwrap();
+    encode_unsigned_integer(&mut buf, 40_000).unwrap();
+    encode_unsigned_integer(&mut buf, u64::MAX).unwrap();
+    assert_eq!(
+        buf,
+        &b"i\xffU\xc8l\x80\x00\x00\x00l\x00\x00\x9c\x40HU\x1418446744073709551615"[..]
+    );
+}
+
+#[test]
+fn encode_scalars() {
+    use serde_ubjson::marker;
+    use serde_ubjson::ser::{
+        encode_bool, encode_f32, encode_f64, encode_integer, encode_key, encode_null, encode_str,
+    };
+
+    // An object composed by hand, one field at a time.
+    let mut buf = vec![marker::OBJ_START];
+    encode_key(&mut buf, "a").unwrap();
+    encode_null(&mut buf).unwrap();
+    encode_key(&mut buf, "b").unwrap();
+    encode_bool(&mut buf, true).unwrap();
+    encode_key(&mut buf, "c").unwrap();
+    encode_f32(&mut buf, 0.5).unwrap();
+    encode_key(&mut buf, "d").unwrap();
+    encode_f64(&mut buf, -0.5).unwrap();
+    encode_key(&mut buf, "e").unwrap();
+    encode_str(&mut buf, "hi").unwrap();
+    encode_key(&mut buf, "f").unwrap();
+    encode_integer(&mut buf, 300).unwrap();
+    buf.push(marker::OBJ_END);
+    assert_eq!(
+        buf,
+        &b"{U\x01aZU\x01bTU\x01cd\x3f\x00\x00\x00U\x01dD\xbf\xe0\x00\x00\x00\x00\x00\x00\
+           U\x01eSU\x02hiU\x01fI\x01\x2c}"[..]
+    );
+    serde_ubjson::validate(&buf).unwrap();
+}
+
+#[test]
+fn serialize_sorted_map_keys() {
+    use std::collections::HashMap;
+
+    let keys = ["b", "a", "ab", "B", "가", "é", ""];
+    let map: HashMap<&str, u8> = keys
+        .iter()
+        .enumerate()
+        .map(|(i, &k)| (k, i as u8))
+        .collect();
+    let nested: HashMap<&str, HashMap<&str, u8>> = vec![("z", map.clone()), ("y", map.clone())]
+        .into_iter()
+        .collect();
+
+    let expected_inner: &[u8] = b"U\x00U\x06U\x01BU\x03U\x01aU\x01U\x02abU\x02U\x01bU\x00\
+                                  U\x02\xc3\xa9U\x05U\x03\xea\xb0\x80U\x04";
+
+    let mut buf = Vec::new();
+    map.serialize(&mut Serializer::new(&mut buf).sort_map_keys(true))
+        .unwrap();
+    assert_eq!(buf, [&b"{#U\x07"[..], expected_inner].concat());
+
+    let mut buf = Vec::new();
+    nested
+        .serialize(&mut Serializer::new(&mut buf).sort_map_keys(true))
+        .unwrap();
+    let inner = [&b"{#U\x07"[..], expected_inner].concat();
+    assert_eq!(
+        buf,
+        [&b"{#U\x02U\x01y"[..], &inner, b"U\x01z", &inner].concat()
+    );
+
+    struct Unsized<'a>(&'a HashMap<&'a str, u8>);
+
+    impl<'a> Serialize for Unsized<'a> {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_map(self.0.iter().filter(|_| true))
+        }
+    }
+
+    let mut buf = Vec::new();
+    Unsized(&map)
+        .serialize(&mut Serializer::new(&mut buf).sort_map_keys(true))
+        .unwrap();
+    assert_eq!(buf, [&b"{"[..], expected_inner, b"}"].concat());
+}
+
+#[test]
+fn serialize_btree_map_in_key_order() {
+    use std::collections::BTreeMap;
+
+    let map: BTreeMap<&str, u8> = vec![("b", 0), ("a", 1), ("ab", 2), ("B", 3), ("가", 4)]
+        .into_iter()
+        .collect();
+    let expected: &[u8] =
+        b"{#U\x05U\x01BU\x03U\x01aU\x01U\x02abU\x02U\x01bU\x00U\x03\xea\xb0\x80U\x04";
+
+    assert_eq!(serde_ubjson::to_vec(&map).unwrap(), expected);
+    let mut buf = Vec::new();
+    map.serialize(&mut Serializer::new(&mut buf).sort_map_keys(true))
+        .unwrap();
+    assert_eq!(buf, expected);
+}
+
+#[test]
+fn byte_array() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Block {
+        #[serde(with = "serde_ubjson::byte_array")]
+        hash: [u8; 4],
+    }
+
+    let block = Block {
+        hash: [0xde, 0xad, 0xbe, 0xef],
+    };
+    let bytes = serde_ubjson::to_vec(&block).unwrap();
+    assert_eq!(bytes, b"[#U\x01[$U#U\x04\xde\xad\xbe\xef");
+    assert_eq!(serde_ubjson::from_slice::<Block>(&bytes).unwrap(), block);
+
+    // A plain array still goes through `serialize_tuple`.
+    assert_eq!(
+        serde_ubjson::to_vec(&[1u8, 2]).unwrap(),
+        b"[#U\x02U\x01U\x02"
+    );
+    assert!(serde_ubjson::from_slice::<Block>(b"[#U\x01[$U#U\x03\x01\x02\x03").is_err());
+}
+
+#[test]
+fn bool_enum() {
+    #[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+    enum Side {
+        Buy,
+        Sell,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Order {
+        #[serde(with = "serde_ubjson::bool_enum")]
+        side: Side,
+        qty: u8,
+    }
+
+    for &(side, expected) in &[
+        (Side::Buy, b"[#U\x02FU\x05"),
+        (Side::Sell, b"[#U\x02TU\x05"),
+    ] {
+        let order = Order { side, qty: 5 };
+        let bytes = serde_ubjson::to_vec(&order).unwrap();
+        assert_eq!(&bytes, expected);
+        assert_eq!(serde_ubjson::from_slice::<Order>(&bytes).unwrap(), order);
+    }
+
+    #[derive(Serialize, Deserialize)]
+    enum Three {
+        A,
+        B,
+        C,
+    }
+    #[derive(Serialize, Deserialize)]
+    struct Wrapper(#[serde(with = "serde_ubjson::bool_enum")] Three);
+    assert!(serde_ubjson::to_vec(&Wrapper(Three::B)).is_ok());
+    assert!(serde_ubjson::to_vec(&Wrapper(Three::C)).is_err());
+
+    // Only unit variants are accepted, not other values that would be written as 0 or 1.
+    #[derive(Serialize)]
+    struct Raw<T: Serialize>(#[serde(with = "serde_ubjson::bool_enum")] T);
+    #[derive(Serialize)]
+    enum Newtype {
+        A(u8),
+    }
+    assert!(serde_ubjson::to_vec(&Raw(1u32)).is_err());
+    assert!(serde_ubjson::to_vec(&Raw(false)).is_err());
+    assert!(serde_ubjson::to_vec(&Raw(Some(Side::Buy))).is_err());
+    assert!(serde_ubjson::to_vec(&Raw(Newtype::A(0))).is_err());
+
+    #[derive(Serialize, Deserialize)]
+    enum Single {
+        Only,
+    }
+    #[derive(Serialize, Deserialize)]
+    struct Flag(#[serde(with = "serde_ubjson::bool_enum")] Single);
+    assert!(serde_ubjson::from_slice::<Flag>(b"F").is_ok());
+    assert!(serde_ubjson::from_slice::<Flag>(b"T").is_err());
+}
+
+#[test]
+fn seq_count_form() {
+    use serde::Serializer as _;
+
+    // Sequences of known length are written like tuples, with a count and mixed element markers.
+    assert_eq!(
+        serde_ubjson::to_vec(&vec![1i64, 300, -1]).unwrap(),
+        b"[#U\x03i\x01I\x01\x2ci\xff"
+    );
+    assert_eq!(
+        serde_ubjson::to_vec(&vec![1i64, 300, -1]).unwrap(),
+        serde_ubjson::to_vec(&(1i64, 300i64, -1i64)).unwrap()
+    );
+    assert_eq!(serde_ubjson::to_vec(&Vec::<u8>::new()).unwrap(), b"[#U\x00");
+
+    // Without a length the array is closed with `]` instead.
+    let mut buf = Vec::new();
+    (&mut Serializer::new(&mut buf))
+        .collect_seq((1..4u8).filter(|&n| n != 2))
+        .unwrap();
+    assert_eq!(buf, b"[U\x01U\x03]");
+}
+
+#[test]
+fn serialize_always_unsized_containers() {
+    use serde_ubjson::ser::StructRepresentation;
+    use std::collections::BTreeMap;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct S {
+        a: u8,
+        b: Vec<u8>,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum E {
+        Tuple(u8, u8),
+        Struct { a: u8 },
+    }
+
+    fn unsized_bytes<T: Serialize>(value: &T, repr: StructRepresentation) -> Vec<u8> {
+        let mut buf = Vec::new();
+        let mut ser = Serializer::new(&mut buf)
+            .always_unsized_containers(true)
+            .struct_representation(repr);
+        value.serialize(&mut ser).unwrap();
+        buf
+    }
+
+    let value = S { a: 1, b: vec![2] };
+    let bytes = unsized_bytes(&value, StructRepresentation::Array);
+    assert_eq!(bytes, b"[U\x01[U\x02]]");
+    assert_eq!(serde_ubjson::from_slice::<S>(&bytes).unwrap(), value);
+    let bytes = unsized_bytes(&value, StructRepresentation::Object);
+    assert_eq!(bytes, b"{U\x01aU\x01U\x01b[U\x02]}");
+    assert_eq!(serde_ubjson::from_slice::<S>(&bytes).unwrap(), value);
+
+    let mut map = BTreeMap::new();
+    map.insert("k", (1u8, 2u8));
+    assert_eq!(
+        unsized_bytes(&map, StructRepresentation::Array),
+        b"{U\x01k[U\x01U\x02]}"
+    );
+
+    for (value, expected) in [
+        (E::Tuple(1, 2), &b"[U\x00U\x01U\x02]"[..]),
+        (E::Struct { a: 3 }, b"[U\x01{U\x01aU\x03}]"),
+    ] {
+        let bytes = unsized_bytes(&value, StructRepresentation::Object);
+        assert_eq!(bytes, expected);
+        assert_eq!(serde_ubjson::from_slice::<E>(&bytes).unwrap(), value);
+    }
+
+    // Byte arrays keep their count, as the typed form requires one.
+    assert_eq!(
+        unsized_bytes(
+            &serde_bytes::Bytes::new(b"\x01"),
+            StructRepresentation::Array
+        ),
+        b"[$U#U\x01\x01"
+    );
+}
+
+#[test]
+fn non_string_key_error() {
+    use serde_ubjson::Error;
+    use std::collections::BTreeMap;
+
+    let mut map = BTreeMap::new();
+    map.insert(1u32, "a");
+    match serde_ubjson::to_vec(&map) {
+        Err(Error::KeyMustBeAString(found)) => assert_eq!(found, "u32"),
+        other => panic!("expected KeyMustBeAString, got {:?}", other),
+    }
+
+    let mut map = BTreeMap::new();
+    map.insert((1u8, 2u8), "a");
+    let err = serde_ubjson::to_vec(&map).unwrap_err();
+    assert_eq!(err.to_string(), "key must be a string, found tuple");
+}
+
+#[test]
+fn stringify_non_string_keys() {
+    use std::collections::BTreeMap;
+
+    let mut map = BTreeMap::new();
+    map.insert(-1i32, 'x');
+    map.insert(20, 'y');
+    let mut buf = Vec::new();
+    map.serialize(&mut Serializer::new(&mut buf).stringify_non_string_keys(true))
+        .unwrap();
+    assert_eq!(buf, b"{#U\x02U\x02-1CxU\x0220Cy");
+    assert_eq!(
+        serde_ubjson::from_slice::<BTreeMap<i32, char>>(&buf).unwrap(),
+        map
+    );
+
+    let mut map = BTreeMap::new();
+    map.insert(true, 1u8);
+    let mut buf = Vec::new();
+    map.serialize(&mut Serializer::new(&mut buf).stringify_non_string_keys(true))
+        .unwrap();
+    assert_eq!(buf, b"{#U\x01U\x04trueU\x01");
+    assert_eq!(
+        serde_ubjson::from_slice::<BTreeMap<bool, u8>>(&buf).unwrap(),
+        map
+    );
+
+    // Floats and compound keys are still rejected.
+    let mut map = BTreeMap::new();
+    map.insert((1u8,), ());
+    let mut buf = Vec::new();
+    assert!(map
+        .serialize(&mut Serializer::new(&mut buf).stringify_non_string_keys(true))
+        .is_err());
+    assert!(serde_ubjson::from_slice::<BTreeMap<i32, ()>>(b"{U\x01xZ}").is_err());
+}
+
+#[test]
+fn not_human_readable() {
+    struct Probe;
+
+    impl Serialize for Probe {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let readable = serializer.is_human_readable();
+            serializer.serialize_bool(readable)
+        }
+    }
+
+    assert_eq!(serde_ubjson::to_vec(&Probe).unwrap(), b"F");
+
+    let mut buf = Vec::new();
+    Probe
+        .serialize(&mut Serializer::new(&mut buf).human_readable(true))
+        .unwrap();
+    assert_eq!(buf, b"T");
+}
+
+#[test]
+fn optimize_unsized_seqs() {
+    use serde::Serializer as _;
+
+    fn optimized<I>(iter: I) -> Vec<u8>
+    where
+        I: IntoIterator,
+        I::Item: Serialize,
+    {
+        let mut buf = Vec::new();
+        // Filtering hides the length, so `collect_seq` passes `None`.
+        (&mut Serializer::new(&mut buf).optimize_unsized_seqs(true))
+            .collect_seq(iter.into_iter().filter(|_| true))
+            .unwrap();
+        buf
+    }
+
+    let bytes = optimized(vec![0.5f64; 8]);
+    assert_eq!(&bytes[..6], b"[$D#U\x08");
+    assert_eq!(bytes.len(), 6 + 8 * 8);
+    assert_eq!(
+        optimized(vec!["a"; 6]),
+        b"[$S#U\x06U\x01aU\x01aU\x01aU\x01aU\x01aU\x01a"
+    );
+
+    // Integers are widened to the widest one.
+    let mut values = vec![1i32];
+    values.extend(-300..-289);
+    let bytes = optimized(values.clone());
+    assert_eq!(&bytes[..10], b"[$I#U\x0c\x00\x01\xfe\xd4");
+    assert_eq!(bytes.len(), 6 + 12 * 2);
+    assert_eq!(
+        serde_ubjson::from_slice::<Vec<i32>>(&bytes).unwrap(),
+        values
+    );
+    assert_eq!(
+        optimized(vec![0u16, 255, 1, 2, 3, 4, 5]),
+        b"[$U#U\x07\x00\xff\x01\x02\x03\x04\x05"
+    );
+
+    // Mixed types, or too few elements to pay for the header, are left unsized.
+    assert_eq!(optimized(vec![(1u8,), (2u8,)]).last(), Some(&b']'));
+    assert_eq!(optimized(vec![1u8, 2]), b"[U\x01U\x02]");
+    assert_eq!(optimized(std::iter::from_fn(|| None::<u8>)), b"[]");
+    assert_eq!(
+        optimized(vec![Some(1u8), None, Some(2), Some(3), Some(4), Some(5)]),
+        b"[U\x01ZU\x02U\x03U\x04U\x05]"
+    );
+
+    // Sequences too long to hold back fall back to the unsized form part way.
+    let long = optimized(vec![1.0f64; serde_ubjson::ser::OPTIMIZE_BUFFER_LIMIT]);
+    assert_eq!(long[0], b'[');
+    assert_eq!(long.last(), Some(&b']'));
+    assert_eq!(
+        serde_ubjson::from_slice::<Vec<f64>>(&long).unwrap().len(),
+        serde_ubjson::ser::OPTIMIZE_BUFFER_LIMIT
+    );
+}
+
+#[test]
+fn serialize_to_slice() {
+    use serde_ubjson::Error;
+
+    let mut frame = [0u8; 8];
+    assert_eq!(serde_ubjson::to_slice(&"hello", &mut frame).unwrap(), 8);
+    assert_eq!(&frame, b"SU\x05hello");
+
+    let mut frame = [0u8; 4];
+    assert_eq!(serde_ubjson::to_slice(&300u16, &mut frame).unwrap(), 3);
+    match serde_ubjson::to_slice(&"hello", &mut frame) {
+        Err(Error::BufferFull) => {}
+        other => panic!("expected BufferFull, got {:?}", other),
+    }
+
+    // Writing through `to_writer` keeps the underlying IO error.
+    match serde_ubjson::to_writer(&mut frame[..], &"hello") {
+        Err(Error::Io(ref e)) if e.kind() == std::io::ErrorKind::WriteZero => {}
+        other => panic!("expected a WriteZero IO error, got {:?}", other),
+    }
+}
+
+#[test]
+fn optimize_char_seqs() {
+    fn optimized(chars: &str) -> Vec<u8> {
+        let mut buf = Vec::new();
+        let chars: Vec<char> = chars.chars().collect();
+        chars
+            .serialize(&mut Serializer::new(&mut buf).optimize_seqs(true))
+            .unwrap();
+        assert_eq!(serde_ubjson::from_slice::<Vec<char>>(&buf).unwrap(), chars);
+        buf
+    }
+
+    assert_eq!(optimized("reboot"), b"[$C#U\x06reboot");
+    // A non-ASCII character is written as an integer, so the array cannot be typed.
+    assert_eq!(optimized("rébt"), b"[#U\x04CrU\xe9CbCt");
+    assert_eq!(optimized("ab"), b"[#U\x02CaCb");
+}
+
+#[test]
+fn optimize_constant_seqs() {
+    fn optimized<T: Serialize>(value: &T) -> Vec<u8> {
+        let mut buf = Vec::new();
+        value
+            .serialize(&mut Serializer::new(&mut buf).optimize_seqs(true))
+            .unwrap();
+        buf
+    }
+
+    assert_eq!(optimized(&vec![(); 300]), b"[$Z#I\x01\x2c");
+    assert_eq!(optimized(&vec![None::<u8>; 4]), b"[$Z#U\x04");
+    assert_eq!(optimized(&vec![true; 5]), b"[$T#U\x05");
+    assert_eq!(optimized(&vec![false; 3]), b"[$F#U\x03");
+    // Two elements come out no smaller typed, so they are written as they are.
+    assert_eq!(optimized(&vec![false; 2]), b"[#U\x02FF");
+    assert_eq!(optimized(&vec![true, false]), b"[#U\x02TF");
+    assert_eq!(optimized(&Vec::<bool>::new()), b"[#U\x00");
+}
+
+#[test]
+fn count_encoding() {
+    use serde_ubjson::ser::CountEncoding;
+
+    fn encoded<T: Serialize>(value: &T, encoding: CountEncoding) -> serde_ubjson::Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        value.serialize(
+            &mut Serializer::new(&mut buf)
+                .count_encoding(encoding)
+                .optimize_seqs(true),
+        )?;
+        Ok(buf)
+    }
+
+    let tags = vec!["a", "bc", "d"];
+    assert_eq!(
+        encoded(&tags, CountEncoding::Narrow).unwrap(),
+        b"[$S#U\x03U\x01aU\x02bcU\x01d"
+    );
+    // String lengths stay narrow.
+    assert_eq!(
+        encoded(&tags, CountEncoding::FixedI16).unwrap(),
+        b"[$S#I\x00\x03U\x01aU\x02bcU\x01d"
+    );
+    assert_eq!(
+        encoded(&vec![true, false], CountEncoding::FixedI32).unwrap(),
+        b"[#l\x00\x00\x00\x02TF"
+    );
+    assert_eq!(
+        encoded(&serde_bytes::Bytes::new(b"\x07"), CountEncoding::FixedI64).unwrap(),
+        b"[$U#L\x00\x00\x00\x00\x00\x00\x00\x01\x07"
+    );
+    assert_eq!(
+        encoded(&vec![(); 40_000], CountEncoding::FixedI32).unwrap(),
+        b"[$Z#l\x00\x00\x9c\x40"
+    );
+    assert!(encoded(&vec![(); 40_000], CountEncoding::FixedI16).is_err());
+
+    let mut buf = Vec::new();
+    let mut ser = Serializer::new(&mut buf).count_encoding(CountEncoding::FixedI16);
+    ser.serialize_typed_array(serde_ubjson::TypeTag::U8, vec![1u8, 2])
+        .unwrap();
+    assert_eq!(buf, b"[$U#I\x00\x02\x01\x02");
+}
+
+#[test]
+fn optimize_maps() {
+    use std::collections::BTreeMap;
+
+    fn optimized<T: Serialize>(value: &T, sorted: bool) -> Vec<u8> {
+        let mut buf = Vec::new();
+        value
+            .serialize(
+                &mut Serializer::new(&mut buf)
+                    .optimize_maps(true)
+                    .sort_map_keys(sorted),
+            )
+            .unwrap();
+        buf
+    }
+
+    let mut counts = BTreeMap::new();
+    counts.insert("a", 1u16);
+    counts.insert("b", 2);
+    counts.insert("c", 3);
+    let buf = optimized(&counts, false);
+    assert_eq!(buf, b"{$U#U\x03U\x01a\x01U\x01b\x02U\x01c\x03");
+    assert_eq!(
+        serde_ubjson::from_slice::<BTreeMap<String, u16>>(&buf).unwrap(),
+        counts.iter().map(|(k, &v)| (k.to_string(), v)).collect()
+    );
+    // Integers of different widths share the widest of them.
+    counts.insert("b", 300);
+    counts.insert("c", 400);
+    counts.insert("d", 500);
+    assert_eq!(
+        optimized(&counts, true),
+        &b"{$I#U\x04U\x01a\x00\x01U\x01b\x01\x2cU\x01c\x01\x90U\x01d\x01\xf4"[..]
+    );
+
+    let mut flags = std::collections::HashMap::new();
+    for key in &["x", "y", "z"] {
+        flags.insert(*key, true);
+    }
+    assert_eq!(optimized(&flags, true), b"{$T#U\x03U\x01xU\x01yU\x01z");
+
+    let mut mixed = BTreeMap::new();
+    mixed.insert("a", Some(1u8));
+    mixed.insert("b", None);
+    mixed.insert("c", Some(3));
+    assert_eq!(
+        optimized(&mixed, false),
+        b"{#U\x03U\x01aU\x01U\x01bZU\x01cU\x03"
+    );
+
+    // Past the limit, the map is written as it comes.
+    let big: BTreeMap<String, u8> = (0..10_000).map(|i| (format!("k{:05}", i), 7)).collect();
+    let buf = optimized(&big, false);
+    assert_eq!(&buf[..2], b"{#");
+    assert_eq!(
+        serde_ubjson::from_slice::<BTreeMap<String, u8>>(&buf).unwrap(),
+        big
+    );
+}
+
+#[test]
+fn unit_as_empty_array() {
+    #[derive(Serialize)]
+    struct Marker;
+
+    fn to_vec<T: Serialize>(value: &T) -> Vec<u8> {
+        let mut buf = Vec::new();
+        value
+            .serialize(&mut Serializer::new(&mut buf).unit_as_empty_array(true))
+            .unwrap();
+        buf
+    }
+
+    assert_eq!(to_vec(&()), b"[]");
+    assert_eq!(to_vec(&Marker), b"[]");
+    assert_eq!(to_vec(&(None::<()>, Some(()))), b"[#U\x02Z[]");
+    assert_eq!(serde_ubjson::to_vec(&()).unwrap(), b"Z");
+}
+
+#[test]
+fn faithful_options() {
+    fn to_vec<T: Serialize>(value: &T) -> Vec<u8> {
+        let mut buf = Vec::new();
+        value
+            .serialize(&mut Serializer::new(&mut buf).faithful_options(true))
+            .unwrap();
+        buf
+    }
+
+    assert_eq!(to_vec(&None::<Option<u8>>), b"Z");
+    assert_eq!(to_vec(&Some(None::<u8>)), b"[#U\x01Z");
+    assert_eq!(to_vec(&Some(Some(5u8))), b"[#U\x01U\x05");
+    assert_eq!(to_vec(&Some(Some(None::<u8>))), b"[#U\x01[#U\x01Z");
+    // Only an option directly inside `Some` is wrapped.
+    assert_eq!(to_vec(&Some(5u8)), b"U\x05");
+    assert_eq!(to_vec(&Some(vec![None::<u8>])), b"[#U\x01Z");
+    assert_eq!(serde_ubjson::to_vec(&Some(None::<u8>)).unwrap(), b"Z");
+}
+
+#[test]
+fn max_output_bytes() {
+    use serde_ubjson::Error;
+    use std::collections::HashMap;
+
+    let value = vec![300u16; 100];
+    let size = serde_ubjson::serialized_size(&value).unwrap() as u64;
+
+    let mut buf = Vec::new();
+    value
+        .serialize(&mut Serializer::new(&mut buf).max_output_bytes(size))
+        .unwrap();
+    assert_eq!(buf.len() as u64, size);
+
+    let mut buf = Vec::new();
+    match value.serialize(&mut Serializer::new(&mut buf).max_output_bytes(size - 1)) {
+        Err(Error::OutputLimitExceeded) => {}
+        other => panic!("expected OutputLimitExceeded, got {:?}", other),
+    }
+    assert!((buf.len() as u64) < size);
+
+    // Held-back maps and sequences are cut off before they are ever written.
+    let map: HashMap<String, Vec<u8>> = (0..100).map(|i| (i.to_string(), vec![0; 100])).collect();
+    let mut buf = Vec::new();
+    let mut ser = Serializer::new(&mut buf)
+        .sort_map_keys(true)
+        .max_output_bytes(1000);
+    match map.serialize(&mut ser) {
+        Err(Error::OutputLimitExceeded) => {}
+        other => panic!("expected OutputLimitExceeded, got {:?}", other),
+    }
+    assert_eq!(ser.bytes_written(), 4);
+    let mut ser = Serializer::new(Vec::new())
+        .optimize_seqs(true)
+        .max_output_bytes(1000);
+    match vec![vec![7u8; 100]; 100].serialize(&mut ser) {
+        Err(Error::OutputLimitExceeded) => {}
+        other => panic!("expected OutputLimitExceeded, got {:?}", other),
+    }
+    assert_eq!(ser.bytes_written(), 0);
+
+    // The limit is kept across `reset`, but the count starts over.
+    let mut ser = Serializer::new(Vec::new()).max_output_bytes(2);
+    "a".serialize(&mut ser).unwrap_err();
+    ser.reset(Vec::new());
+    'a'.serialize(&mut ser).unwrap();
+    'b'.serialize(&mut ser).unwrap_err();
+}
+
+#[test]
+fn bytes_written() {
+    let mut buf = Vec::new();
+    {
+        let mut ser = Serializer::new(&mut buf).sort_map_keys(true);
+        assert_eq!(ser.bytes_written(), 0);
+        "hello".serialize(&mut ser).unwrap();
+        assert_eq!(ser.bytes_written(), 8);
+        let map: std::collections::HashMap<_, _> = vec![("b", 1), ("a", 2)].into_iter().collect();
+        map.serialize(&mut ser).unwrap();
+        assert_eq!(ser.bytes_written(), 8 + 14);
+    }
+    assert_eq!(buf, b"SU\x05hello{#U\x02U\x01ai\x02U\x01bi\x01");
+}
+
+#[test]
+fn ip_addresses_are_compact() {
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+    fn roundtrip<T>(value: T, expected: &[u8])
+    where
+        T: Serialize + serde::de::DeserializeOwned + PartialEq + std::fmt::Debug,
+    {
+        let buf = serde_ubjson::to_vec(&value).unwrap();
+        assert_eq!(buf, expected, "serializing {:?}", value);
+        assert_eq!(serde_ubjson::from_slice::<T>(&buf).unwrap(), value);
+    }
+
+    let v4 = Ipv4Addr::new(192, 168, 0, 1);
+    let v4_bytes: &[u8] = b"[#U\x04U\xc0U\xa8U\x00U\x01";
+    let v6 = Ipv6Addr::LOCALHOST;
+    let v6_bytes = [&b"[#U\x10"[..], &b"U\x00".repeat(15), b"U\x01"].concat();
+
+    roundtrip(v4, v4_bytes);
+    roundtrip(v6, &v6_bytes);
+    roundtrip(IpAddr::V4(v4), &[&b"[#U\x02U\x00"[..], v4_bytes].concat());
+    roundtrip(IpAddr::V6(v6), &[&b"[#U\x02U\x01"[..], &v6_bytes].concat());
+    roundtrip(
+        SocketAddr::new(IpAddr::V4(v4), 8080),
+        &[&b"[#U\x02U\x00[#U\x02"[..], v4_bytes, b"I\x1f\x90"].concat(),
+    );
+    roundtrip(
+        SocketAddr::new(IpAddr::V6(v6), 443),
+        &[&b"[#U\x02U\x01[#U\x02"[..], &v6_bytes, b"I\x01\xbb"].concat(),
+    );
+}
+
+#[test]
+fn reset_serializer() {
+    let mut ser = Serializer::new(Vec::new()).compact_floats(true);
+    1.0f64.serialize(&mut ser).unwrap();
+    let first = ser.reset(Vec::new());
+    assert_eq!(first, b"d\x3f\x80\x00\x00");
+    assert_eq!(ser.bytes_written(), 0);
+
+    "a".serialize(&mut ser).unwrap();
+    2.0f64.serialize(&mut ser).unwrap();
+    assert_eq!(ser.bytes_written(), 9);
+    assert_eq!(ser.reset(Vec::new()), b"SU\x01ad\x40\x00\x00\x00");
+}
+
+#[test]
+fn io_errors_convert_with_question_mark() {
+    use std::io::Write;
+
+    fn write_framed(out: &mut Vec<u8>, value: &str) -> serde_ubjson::Result<()> {
+        out.write_all(b"N")?;
+        serde_ubjson::to_writer(&mut *out, &value)?;
+        out.flush()?;
+        Ok(())
+    }
+
+    let mut out = Vec::new();
+    write_framed(&mut out, "a").unwrap();
+    assert_eq!(out, b"NSU\x01a");
+
+    let err: serde_ubjson::Error = std::io::Error::from(std::io::ErrorKind::BrokenPipe).into();
+    assert!(
+        matches!(err, serde_ubjson::Error::Io(ref e) if e.kind() == std::io::ErrorKind::BrokenPipe)
+    );
+}
+
+#[test]
+fn custom_errors_can_be_downcast() {
+    use std::error::Error as _;
+
+    #[derive(Debug, PartialEq)]
+    struct Overdrawn(i64);
+
+    impl std::fmt::Display for Overdrawn {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(f, "balance {} is below zero", self.0)
+        }
+    }
+
+    impl std::error::Error for Overdrawn {}
+
+    fn write_balance(ser: &mut Serializer<Vec<u8>>, balance: i64) -> serde_ubjson::Result<()> {
+        if balance < 0 {
+            return Err(serde_ubjson::Error::custom_error(Overdrawn(balance)));
+        }
+        balance.serialize(ser)
+    }
+
+    let mut ser = Serializer::new(Vec::new());
+    write_balance(&mut ser, 5).unwrap();
+    let err = write_balance(&mut ser, -3).unwrap_err();
+    assert_eq!(err.to_string(), "balance -3 is below zero");
+    assert_eq!(
+        err.source().and_then(|e| e.downcast_ref::<Overdrawn>()),
+        Some(&Overdrawn(-3))
+    );
+    assert_eq!(ser.reset(Vec::new()), b"i\x05");
+
+    // `custom` only sees the message.
+    let err: serde_ubjson::Error = serde::ser::Error::custom(Overdrawn(-3));
+    assert!(matches!(err, serde_ubjson::Error::Message(_)));
+}
+
+#[test]
+fn to_vec_never_fails_with_io() {
+    use std::collections::BTreeMap;
+
+    let mut map = BTreeMap::new();
+    map.insert((1, 2), 3);
+    let err = serde_ubjson::to_vec(&map).unwrap_err();
+    assert!(matches!(err, serde_ubjson::Error::KeyMustBeAString(_)));
+
+    let err = serde_ubjson::to_vec_with_capacity(&map, 0).unwrap_err();
+    assert!(matches!(err, serde_ubjson::Error::KeyMustBeAString(_)));
+}
+
+#[test]
+fn clone_serializer() {
+    let mut prefix = Serializer::new(Vec::new()).compact_floats(true);
+    "id".serialize(&mut prefix).unwrap();
+
+    let mut left = prefix.clone();
+    let mut right = prefix.clone();
+    1.0f64.serialize(&mut left).unwrap();
+    true.serialize(&mut right).unwrap();
+
+    assert_eq!(left.bytes_written(), 10);
+    assert_eq!(left.reset(Vec::new()), b"SU\x02idd\x3f\x80\x00\x00");
+    assert_eq!(right.reset(Vec::new()), b"SU\x02idT");
+    assert_eq!(prefix.reset(Vec::new()), b"SU\x02id");
+}
+
+#[test]
+fn serialize_cow() {
+    use std::borrow::Cow;
+
+    #[derive(Serialize, Deserialize)]
+    struct Record<'a> {
+        #[serde(borrow)]
+        name: Cow<'a, str>,
+        #[serde(borrow, with = "serde_bytes")]
+        data: Cow<'a, [u8]>,
+    }
+
+    // A `Cow` serializes whatever it points to, so both variants go straight to `serialize_str`
+    // or `serialize_bytes` with no copy made.
+    let borrowed = Record {
+        name: Cow::Borrowed("ab"),
+        data: Cow::Borrowed(&b"\x01\x02"[..]),
+    };
+    let owned = Record {
+        name: Cow::Owned("ab".to_owned()),
+        data: Cow::Owned(vec![1, 2]),
+    };
+    let expected: &[u8] = b"[#U\x02SU\x02ab[$U#U\x02\x01\x02";
+    assert_eq!(serde_ubjson::to_vec(&borrowed).unwrap(), expected);
+    assert_eq!(serde_ubjson::to_vec(&owned).unwrap(), expected);
+
+    // Without `serde_bytes`, a byte slice is an ordinary sequence of integers.
+    let plain: Cow<[u8]> = Cow::Borrowed(&b"\x01\x02"[..]);
+    assert_eq!(serde_ubjson::to_vec(&plain).unwrap(), b"[#U\x02U\x01U\x02");
+
+    let decoded: Record = serde_ubjson::from_slice(expected).unwrap();
+    assert!(matches!(decoded.name, Cow::Borrowed("ab")));
+    assert_eq!(&*decoded.data, b"\x01\x02");
+}
+
+#[test]
+fn optimize_string_seqs() {
+    let long = "x".repeat(300);
+    let strings = vec!["a", "bc", "", "d", "e", &long];
+    let mut buf = Vec::new();
+    strings
+        .serialize(&mut Serializer::new(&mut buf).optimize_seqs(true))
+        .unwrap();
+    // Each length keeps its own narrowest marker, with no `S` in front.
+    let expected = [
+        &b"[$S#U\x06U\x01aU\x02bcU\x00U\x01dU\x01eI\x01\x2c"[..],
+        long.as_bytes(),
+    ]
+    .concat();
+    assert_eq!(buf, expected);
+
+    assert_eq!(
+        serde_ubjson::from_slice::<Vec<String>>(&buf).unwrap(),
+        strings
+    );
+    assert_eq!(
+        serde_ubjson::from_slice::<Vec<&str>>(&buf).unwrap(),
+        strings
+    );
+    let value: serde_ubjson::Value = serde_ubjson::from_slice(&buf).unwrap();
+    assert_eq!(
+        serde_ubjson::from_value::<Vec<String>>(value).unwrap(),
+        strings
+    );
+}
+
+#[test]
+fn serialize_empty_maps_and_structs() {
+    use serde_ubjson::ser::StructRepresentation;
+    use std::collections::HashMap;
+
+    #[derive(Debug, Serialize)]
+    struct Empty {}
+
+    #[derive(Debug)]
+    struct UnsizedEmpty;
+
+    impl Serialize for UnsizedEmpty {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_map(std::iter::from_fn(|| None::<(&str, u8)>))
+        }
+    }
+
+    test_cases! {
+        (HashMap::<String, u8>::new(), b"{#U\x00"),
+        (UnsizedEmpty,                 b"{}"),
+        (Empty {},                     b"[#U\x00"),
+    }
+
+    let mut buf = Vec::new();
+    Empty {}
+        .serialize(
+            &mut Serializer::new(&mut buf).struct_representation(StructRepresentation::Object),
+        )
+        .unwrap();
+    assert_eq!(buf, b"{#U\x00");
+}
@@ -0,0 +1,60 @@
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_ubjson;
+extern crate serde_with;
+
+use serde::de::Deserialize;
+use serde::ser::Serialize;
+use serde_ubjson::{from_slice, to_vec, Deserializer, Serializer};
+use serde_with::{serde_as, Bytes, DisplayFromStr, IfIsHumanReadable};
+
+#[serde_as]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Record {
+    #[serde_as(as = "DisplayFromStr")]
+    id: u64,
+    #[serde_as(as = "Bytes")]
+    digest: [u8; 4],
+    #[serde_as(as = "Bytes")]
+    payload: Vec<u8>,
+}
+
+#[test]
+fn display_from_str_and_bytes() {
+    let record = Record {
+        id: 12,
+        digest: [1, 2, 3, 4],
+        payload: vec![5, 6],
+    };
+    let buf = to_vec(&record).unwrap();
+    assert_eq!(
+        buf,
+        &b"[#U\x03SU\x0212[$U#U\x04\x01\x02\x03\x04[$U#U\x02\x05\x06"[..]
+    );
+    assert_eq!(from_slice::<Record>(&buf).unwrap(), record);
+}
+
+#[test]
+fn if_is_human_readable() {
+    #[serde_as]
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Account {
+        #[serde_as(as = "IfIsHumanReadable<DisplayFromStr>")]
+        balance: u64,
+    }
+
+    // The serializer is not human-readable by default, so the integer is written as is.
+    let account = Account { balance: 300 };
+    let buf = to_vec(&account).unwrap();
+    assert_eq!(buf, b"[#U\x01I\x01\x2c");
+    assert_eq!(from_slice::<Account>(&buf).unwrap(), account);
+
+    let mut buf = Vec::new();
+    account
+        .serialize(&mut Serializer::new(&mut buf).human_readable(true))
+        .unwrap();
+    assert_eq!(buf, b"[#U\x01SU\x03300");
+    let mut de = Deserializer::from_slice(&buf).human_readable(true);
+    assert_eq!(Account::deserialize(&mut de).unwrap(), account);
+}
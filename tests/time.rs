@@ -0,0 +1,48 @@
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_ubjson;
+
+use std::time::{Duration, UNIX_EPOCH};
+
+use serde_ubjson::{from_slice, to_vec};
+
+#[test]
+fn time_duration() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Sample {
+        #[serde(with = "serde_ubjson::time::duration")]
+        took: Duration,
+    }
+
+    let sample = Sample {
+        took: Duration::new(300, 5),
+    };
+    let bytes = to_vec(&sample).unwrap();
+    assert_eq!(bytes, b"[#U\x01[#U\x02I\x01\x2cU\x05");
+    assert_eq!(from_slice::<Sample>(&bytes).unwrap(), sample);
+
+    assert!(from_slice::<Sample>(b"[#U\x01[#U\x02U\x01l\x3b\x9a\xca\x00").is_err());
+}
+
+#[test]
+fn time_system_time() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Sample {
+        #[serde(with = "serde_ubjson::time::system_time")]
+        at: std::time::SystemTime,
+    }
+
+    for (at, expected) in [
+        (
+            UNIX_EPOCH + Duration::new(1, 2),
+            &b"[#U\x01l\x3b\x9a\xca\x02"[..],
+        ),
+        (UNIX_EPOCH - Duration::from_nanos(1), b"[#U\x01i\xff"),
+    ] {
+        let sample = Sample { at };
+        let bytes = to_vec(&sample).unwrap();
+        assert_eq!(bytes, expected);
+        assert_eq!(from_slice::<Sample>(&bytes).unwrap(), sample);
+    }
+}
@@ -0,0 +1,134 @@
+#![cfg(feature = "tokio")]
+
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_ubjson;
+extern crate tokio;
+
+use std::collections::BTreeMap;
+
+use serde_ubjson::tokio::{from_async_reader, AsyncReader};
+use serde_ubjson::{to_vec, Error, Value};
+use tokio::io::AsyncWriteExt;
+
+fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap()
+        .block_on(future)
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Message {
+    id: u32,
+    tags: Vec<String>,
+    scores: Vec<u8>,
+    extra: BTreeMap<String, Option<f64>>,
+}
+
+#[test]
+fn from_async_reader_in_pieces() {
+    let mut extra = BTreeMap::new();
+    extra.insert("a".to_owned(), Some(1.5));
+    extra.insert("b".to_owned(), None);
+    let message = Message {
+        id: 70_000,
+        tags: vec!["x".to_owned(), "yz".to_owned()],
+        scores: vec![1, 2, 3],
+        extra,
+    };
+    let mut input = b"NN".to_vec();
+    input.extend(to_vec(&message).unwrap());
+    // An unsized object holding a typed array, followed by a bare integer.
+    input.extend_from_slice(b"{U\x01k[$i#U\x02\x01\x02NU\x01e[]N}NU\x07");
+
+    block_on(async {
+        let (mut tx, mut rx) = tokio::io::duplex(4);
+        let writer = tokio::spawn(async move {
+            for chunk in input.chunks(3) {
+                tx.write_all(chunk).await.unwrap();
+                tokio::task::yield_now().await;
+            }
+        });
+        let first: Message = from_async_reader(&mut rx).await.unwrap();
+        assert_eq!(first, message);
+        let second: Value = from_async_reader(&mut rx).await.unwrap();
+        assert_eq!(
+            second,
+            serde_ubjson::from_slice::<Value>(b"{U\x01k[$i#U\x02\x01\x02U\x01e[]}").unwrap()
+        );
+        let third: u8 = from_async_reader(&mut rx).await.unwrap();
+        assert_eq!(third, 7);
+        writer.await.unwrap();
+        assert!(matches!(
+            from_async_reader::<_, Value>(&mut rx).await,
+            Err(Error::UnexpectedEof)
+        ));
+    });
+}
+
+#[test]
+fn from_async_reader_errors() {
+    block_on(async {
+        let cases: &[&[u8]] = &[b"[U\x01", b"SU\x05abc", b"{#U\x01U\x01a"];
+        for input in cases {
+            assert!(matches!(
+                from_async_reader::<_, Value>(&mut &input[..]).await,
+                Err(Error::UnexpectedEof)
+            ));
+        }
+        assert!(matches!(
+            from_async_reader::<_, Value>(&mut &b"[$U]"[..]).await,
+            Err(Error::InvalidMarker(b']'))
+        ));
+        assert!(matches!(
+            from_async_reader::<_, u8>(&mut &b"SU\x01a"[..]).await,
+            Err(Error::Message(_))
+        ));
+    });
+}
+
+#[test]
+fn async_reader_limits() {
+    block_on(async {
+        // Nearly 2^31 elements that take up no input must fail without spinning through them.
+        for input in &[
+            &b"[$Z#L\x00\x00\x00\x7f\xff\xff\xff\xff"[..],
+            b"[$T#L\x00\x00\x00\x7f\xff\xff\xff\xff",
+            b"[$F#L\x7f\xff\xff\xff\xff\xff\xff\xff",
+        ] {
+            assert!(matches!(
+                from_async_reader::<_, Vec<Value>>(&mut &input[..]).await,
+                Err(Error::LengthLimitExceeded)
+            ));
+        }
+        let units: Vec<()> = from_async_reader(&mut &b"[$Z#l\x00\x01\x00\x00"[..])
+            .await
+            .unwrap();
+        assert_eq!(units.len(), 1 << 16);
+
+        let mut reader = AsyncReader::new(&b"[$U#U\x03\x01\x02\x03[$Z#U\x02[[[]]]"[..])
+            .max_depth(2)
+            .max_zero_width_count(2);
+        assert_eq!(reader.read::<Vec<u8>>().await.unwrap(), [1, 2, 3]);
+        assert_eq!(reader.read::<Vec<()>>().await.unwrap(), vec![(); 2]);
+        assert!(matches!(
+            reader.read::<Value>().await,
+            Err(Error::DepthLimitExceeded)
+        ));
+        let mut reader = AsyncReader::new(&b"[$Z#U\x03"[..]).max_zero_width_count(2);
+        assert!(matches!(
+            reader.read::<Vec<()>>().await,
+            Err(Error::LengthLimitExceeded)
+        ));
+
+        for input in &[&b"[ZZZ]"[..], b"SU\x03abc", b"{U\x01aZU\x01bZU\x01cZ}"] {
+            let mut reader = AsyncReader::new(&input[..]).max_length(2);
+            assert!(matches!(
+                reader.read::<Value>().await,
+                Err(Error::LengthLimitExceeded)
+            ));
+        }
+    });
+}
@@ -0,0 +1,36 @@
+#![cfg(feature = "uuid")]
+
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_ubjson;
+extern crate uuid;
+
+use serde_ubjson::{from_slice, to_vec};
+use uuid::Uuid;
+
+#[test]
+fn uuid_bytes() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Record {
+        #[serde(with = "serde_ubjson::uuid")]
+        id: Uuid,
+    }
+
+    let record = Record {
+        id: Uuid::from_bytes([
+            0x67, 0xe5, 0x50, 0x44, 0x10, 0xb1, 0x42, 0x6f, 0x92, 0x47, 0xbb, 0x68, 0x0e, 0x5f,
+            0xe0, 0xc8,
+        ]),
+    };
+    let bytes = to_vec(&record).unwrap();
+    let mut expected = b"[#U\x01[$U#U\x10".to_vec();
+    expected.extend_from_slice(record.id.as_bytes());
+    assert_eq!(bytes, expected);
+    assert_eq!(from_slice::<Record>(&bytes).unwrap(), record);
+
+    assert!(from_slice::<Record>(b"[#U\x01[$U#U\x02\x01\x02").is_err());
+    let mut long = b"[#U\x01[$U#U\x11".to_vec();
+    long.extend_from_slice(&[0; 17]);
+    assert!(from_slice::<Record>(&long).is_err());
+}
@@ -0,0 +1,173 @@
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_ubjson;
+
+use std::collections::BTreeMap;
+
+use serde::de::IntoDeserializer;
+use serde::Deserialize;
+use serde_ubjson::{from_slice, from_value, to_vec, Error, Value};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum Shape {
+    Point,
+    Circle(f64),
+    Rect { w: u32, h: u32 },
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Record {
+    id: u64,
+    name: String,
+    tags: Vec<String>,
+    shape: Shape,
+    parent: Option<u32>,
+}
+
+#[test]
+fn value_round_trip() {
+    let mut object = BTreeMap::new();
+    object.insert("a".to_owned(), Value::Int(-300));
+    object.insert(
+        "b".to_owned(),
+        Value::Array(vec![Value::Null, Value::Bool(true)]),
+    );
+    let value = Value::Array(vec![
+        Value::Object(object),
+        Value::Float(1.5),
+        Value::Char('x'),
+        Value::String("hello".to_owned()),
+    ]);
+    let bytes = to_vec(&value).unwrap();
+    assert_eq!(from_slice::<Value>(&bytes).unwrap(), value);
+}
+
+#[test]
+fn value_null_with_unit_as_empty_array() {
+    use serde::Serialize;
+    use serde_ubjson::Serializer;
+
+    let value = Value::Array(vec![Value::Null, Value::Array(vec![])]);
+    let mut buf = Vec::new();
+    value
+        .serialize(&mut Serializer::new(&mut buf).unit_as_empty_array(true))
+        .unwrap();
+    assert_eq!(buf, b"[#U\x02Z[#U\x00");
+    assert_eq!(from_slice::<Value>(&buf).unwrap(), value);
+}
+
+#[test]
+fn value_high_precision() {
+    let value = Value::HighPrecision("18446744073709551616".to_owned());
+    assert_eq!(to_vec(&value).unwrap(), b"HU\x1418446744073709551616");
+}
+
+#[test]
+fn value_into_deserializer() {
+    let record = Record {
+        id: 7,
+        name: "seven".to_owned(),
+        tags: vec!["a".to_owned(), "b".to_owned()],
+        shape: Shape::Rect { w: 2, h: 3 },
+        parent: None,
+    };
+    let value: Value = from_slice(&to_vec(&record).unwrap()).unwrap();
+    assert_eq!(
+        Record::deserialize(value.clone().into_deserializer()).unwrap(),
+        record
+    );
+    assert_eq!(from_value::<Record>(value).unwrap(), record);
+
+    for shape in [Shape::Point, Shape::Circle(0.5)] {
+        let value: Value = from_slice(&to_vec(&shape).unwrap()).unwrap();
+        assert_eq!(from_value::<Shape>(value).unwrap(), shape);
+    }
+
+    match from_value::<Record>(Value::Int(1)) {
+        Err(Error::Message(_)) => {}
+        other => panic!("expected Message, got {:?}", other),
+    }
+}
+
+#[test]
+fn value_deserialize_with() {
+    #[derive(Deserialize)]
+    struct Lazy {
+        #[serde(deserialize_with = "Value::deserialize")]
+        payload: Value,
+    }
+
+    let bytes = to_vec(&(1u8, "x")).unwrap();
+    let mut input = b"[#U\x01".to_vec();
+    input.extend_from_slice(&bytes);
+    let lazy: Lazy = from_slice(&input).unwrap();
+    let (a, b): (u8, String) = from_value(lazy.payload).unwrap();
+    assert_eq!((a, b.as_str()), (1, "x"));
+}
+
+#[test]
+fn value_numbers() {
+    let big = Value::HighPrecision("18446744073709551616".to_owned());
+    let frac = Value::HighPrecision("-1.25e2".to_owned());
+
+    assert_eq!(Value::Int(-3).as_i64(), Some(-3));
+    assert_eq!(Value::Int(-3).as_u64(), None);
+    assert_eq!(Value::Int(-3).as_f64(), Some(-3.0));
+    assert_eq!(Value::Float(0.5).as_i64(), None);
+    assert_eq!(Value::HighPrecision("42".to_owned()).as_i64(), Some(42));
+    assert_eq!(big.as_u64(), None);
+    assert_eq!(big.as_f64(), Some(18446744073709551616.0));
+    assert_eq!(frac.as_i64(), None);
+    assert_eq!(frac.as_f64(), Some(-125.0));
+    assert_eq!(Value::String("1".to_owned()).as_f64(), None);
+
+    assert_eq!(
+        big.parse_number::<u128>().unwrap().unwrap(),
+        18446744073709551616
+    );
+    assert!(frac.parse_number::<u128>().unwrap().is_err());
+    assert_eq!(Value::Int(7).parse_number::<u8>().unwrap().unwrap(), 7);
+    assert!(Value::Null.parse_number::<u8>().is_none());
+}
+
+#[test]
+fn value_integer_out_of_range() {
+    match from_value::<u8>(Value::Int(300)) {
+        Err(Error::IntegerOutOfRange {
+            target: "u8",
+            value: 300,
+        }) => {}
+        other => panic!("expected IntegerOutOfRange, got {:?}", other),
+    }
+    match from_value::<u64>(Value::HighPrecision("-18446744073709551616".to_owned())) {
+        Err(Error::IntegerOutOfRange { target: "u64", .. }) => {}
+        other => panic!("expected IntegerOutOfRange, got {:?}", other),
+    }
+    assert_eq!(from_value::<i16>(Value::Int(-300)).unwrap(), -300);
+}
+
+#[test]
+fn value_hi_precision_roundtrip() {
+    let bytes = b"[#U\x02HU\x0a1.00000001HU\x1418446744073709551616";
+    let value: Value = from_slice(bytes).unwrap();
+    assert_eq!(
+        value,
+        Value::Array(vec![
+            Value::HighPrecision("1.00000001".to_owned()),
+            Value::HighPrecision("18446744073709551616".to_owned()),
+        ])
+    );
+    assert_eq!(to_vec(&value).unwrap(), &bytes[..]);
+
+    assert_eq!(from_value::<Value>(value.clone()).unwrap(), value);
+    assert_eq!(
+        from_value::<(f64, u128)>(value.clone()).unwrap(),
+        (1.00000001, 1 << 64)
+    );
+    assert_eq!(
+        from_value::<(String, String)>(value.clone()).unwrap().0,
+        "1.00000001"
+    );
+    assert!(from_value::<(i64, u128)>(value).is_err());
+}
@@ -0,0 +1,136 @@
+extern crate serde_ubjson;
+
+use serde_ubjson::{Token, UbjsonReader, UbjsonWriter};
+
+fn write(tokens: &[Token]) -> serde_ubjson::Result<Vec<u8>> {
+    let mut writer = UbjsonWriter::new(Vec::new());
+    for token in tokens {
+        writer.write_token(token)?;
+    }
+    writer.finish()
+}
+
+#[test]
+fn write_scalars() {
+    assert_eq!(
+        write(&[
+            Token::Null,
+            Token::Bool(true),
+            Token::Int(-1),
+            Token::Int(200),
+            Token::Int(-200),
+            Token::Int(1 << 20),
+            Token::Int(1 << 40),
+            Token::Float(1.5),
+            Token::Char('a'),
+            Token::Str("hi".to_owned()),
+            Token::HighPrecision("12".to_owned()),
+        ])
+        .unwrap(),
+        &b"ZTi\xffU\xc8I\xff\x38l\x00\x10\x00\x00L\x00\x00\x01\x00\x00\x00\x00\x00\
+           D\x3f\xf8\x00\x00\x00\x00\x00\x00CaSU\x02hiHU\x0212"[..]
+    );
+    assert!(write(&[Token::Char('é')]).is_err());
+}
+
+#[test]
+fn write_containers() {
+    assert_eq!(
+        write(&[
+            Token::ObjectStart {
+                len: None,
+                ty: None
+            },
+            Token::Key("a".to_owned()),
+            Token::ArrayStart {
+                len: Some(2),
+                ty: Some(b'd'),
+            },
+            Token::Float(1.5),
+            Token::Float(2.0),
+            Token::ArrayEnd,
+            Token::Key("b".to_owned()),
+            Token::ArrayStart {
+                len: Some(2),
+                ty: Some(b'I'),
+            },
+            Token::Int(1),
+            Token::Int(-2),
+            Token::ArrayEnd,
+            Token::ObjectEnd,
+        ])
+        .unwrap(),
+        &b"{U\x01a[$d#U\x02\x3f\xc0\x00\x00\x40\x00\x00\x00U\x01b[$I#U\x02\x00\x01\xff\xfe}"[..]
+    );
+}
+
+#[test]
+fn transcode_through_reader() {
+    let input: &[u8] = b"{U\x01a[i\x01[]]U\x01b{#U\x01U\x01cZU\x01c[$i#U\x02\x01\xff}SU\x01x";
+    let mut reader = UbjsonReader::new(input);
+    let mut writer = UbjsonWriter::new(Vec::new());
+    while let Some(token) = reader.next_token().unwrap() {
+        writer.write_token(&token).unwrap();
+    }
+    assert_eq!(writer.finish().unwrap(), input);
+}
+
+#[test]
+fn write_rejects_malformed_structure() {
+    let start = Token::ArrayStart {
+        len: None,
+        ty: None,
+    };
+    let counted = Token::ArrayStart {
+        len: Some(1),
+        ty: None,
+    };
+    let object = Token::ObjectStart {
+        len: None,
+        ty: None,
+    };
+    let key = Token::Key("k".to_owned());
+
+    for tokens in &[
+        vec![Token::ArrayEnd],
+        vec![object.clone(), Token::ArrayEnd],
+        vec![start.clone(), Token::ObjectEnd],
+        vec![key.clone()],
+        vec![start.clone(), key.clone()],
+        vec![object.clone(), Token::Null],
+        vec![object.clone(), key.clone(), key.clone()],
+        vec![object.clone(), key.clone(), Token::ObjectEnd],
+        vec![counted.clone(), Token::ArrayEnd],
+        vec![counted.clone(), Token::Null, Token::Null],
+        vec![Token::ArrayStart {
+            len: None,
+            ty: Some(b'U'),
+        }],
+        vec![
+            Token::ArrayStart {
+                len: Some(1),
+                ty: Some(b'U'),
+            },
+            Token::Int(-1),
+        ],
+        vec![
+            Token::ArrayStart {
+                len: Some(1),
+                ty: Some(b'S'),
+            },
+            Token::Null,
+        ],
+        vec![start.clone()],
+    ] {
+        assert!(write(tokens).is_err(), "accepted {:?}", tokens);
+    }
+
+    // A rejected token leaves nothing behind, so writing can carry on.
+    let mut writer = UbjsonWriter::new(Vec::new());
+    writer.write_token(&object).unwrap();
+    assert!(writer.write_token(&Token::Null).is_err());
+    writer.write_token(&key).unwrap();
+    writer.write_token(&Token::Null).unwrap();
+    writer.write_token(&Token::ObjectEnd).unwrap();
+    assert_eq!(writer.finish().unwrap(), b"{U\x01kZ}");
+}